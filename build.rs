@@ -41,7 +41,7 @@ fn generate_git_constants() -> std::io::Result<()> {
 "// Generated by build.rs
 
 /// Git hash of the commit the binary is built from.
-pub const GIT_COMMIT_HASH: &'static str = \"{commit_hash}\";
+pub const GIT_COMMIT_HASH: &str = \"{commit_hash}\";
 
 /// Flag which is set to true if the binary is built from a git directory that has uncommited changes.
 pub const GIT_IS_DIRTY: bool = {is_dirty};
@@ -0,0 +1,97 @@
+use crate::{
+    auth::{TokenRepository, TryReason},
+    auth0,
+    cli::{self, P6mEnvironment},
+    AuthToken,
+};
+use anyhow::{Context, Error};
+use clap::ArgMatches;
+use serde::Serialize;
+
+const KUBERNETES_SCOPE: &str = "login:kubernetes";
+
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq)]
+pub enum Output {
+    Default,
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AppSummary {
+    display_name: String,
+    org: Option<String>,
+    client_id: String,
+    kubernetes: bool,
+}
+
+pub async fn execute(environment: P6mEnvironment, matches: &ArgMatches) -> Result<(), Error> {
+    match cli::subcommand("p6m apps", matches)? {
+        Some(("list", subargs)) => list(environment, subargs).await,
+        Some((command, _)) => Err(Error::msg(format!(
+            "Unimplemented apps command: '{}'",
+            command
+        ))),
+        None => Err(Error::msg("No apps command given")),
+    }
+}
+
+/// Lists the Auth0 applications visible to the logged in user, the same set SSO uses to build kubeconfigs.
+async fn list(environment: P6mEnvironment, matches: &ArgMatches) -> Result<(), Error> {
+    let output = matches
+        .try_get_one("output")
+        .unwrap_or(Some(&Output::Default));
+
+    let organization = matches
+        .try_get_one::<String>("organization-name")
+        .unwrap_or(None);
+
+    let mut token_repository = TokenRepository::new(&environment.auth_n, &environment.auth_dir)?;
+
+    if let Some(organization) = organization {
+        token_repository
+            .with_organization(organization)
+            .context("Unknown organization")?;
+    }
+
+    token_repository
+        .try_refresh_or_login(&TryReason::AppsCommand)
+        .await?;
+
+    let id_token = token_repository
+        .clone()
+        .read_token(AuthToken::Id)
+        .context("unable to read ID token")?;
+
+    let apps: Vec<AppSummary> = auth0::Client::new(&token_repository.auth_n.apps_uri())
+        .with_token(id_token)
+        .with_organization(organization)
+        .apps()
+        .await
+        .context("Unable to fetch apps")?
+        .into_iter()
+        .map(|app| AppSummary {
+            display_name: app.display_name(),
+            org: app.org(),
+            client_id: app.client_id.clone(),
+            kubernetes: app.scopes.iter().any(|scope| scope == KUBERNETES_SCOPE),
+        })
+        .collect();
+
+    match output {
+        Some(Output::Json) => println!("{}", serde_json::to_string_pretty(&apps)?),
+        None | Some(Output::Default) => {
+            for app in apps {
+                println!(
+                    "{}\t{}\t{}{}",
+                    app.display_name,
+                    app.org.as_deref().unwrap_or("-"),
+                    app.client_id,
+                    if app.kubernetes { "\tkubernetes" } else { "" }
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use crate::{
+    auth::{Claims, TokenRepository, TryReason},
+    cli::P6mEnvironment,
+    AuthToken,
+};
+use anyhow::{Context, Error};
+use clap::ArgMatches;
+
+/// Token whose claims `auth assert` checks.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum AssertToken {
+    Access,
+    Id,
+}
+
+impl From<AssertToken> for AuthToken {
+    fn from(value: AssertToken) -> Self {
+        match value {
+            AssertToken::Access => AuthToken::Access,
+            AssertToken::Id => AuthToken::Id,
+        }
+    }
+}
+
+/// Checks the current token against the requested claims, failing if any of them don't match.
+///
+/// Matching is [Claims::assert]'s, except that roles and permissions are compared regardless of order.
+pub async fn execute(environment: P6mEnvironment, matches: &ArgMatches) -> Result<(), Error> {
+    let token_type = matches
+        .get_one::<AssertToken>("token")
+        .copied()
+        .unwrap_or(AssertToken::Id);
+
+    let mut token_repository = TokenRepository::new(&environment.auth_n, &environment.auth_dir)?;
+    token_repository.with_offline(environment.offline);
+
+    if let Some(organization) = matches.get_one::<String>("organization-name") {
+        token_repository
+            .with_organization(organization)?
+            .with_scope(
+                "roles",
+                Claims {
+                    roles: Some(vec!["*".into()]), // ["*"] is a special case to allow any
+                    ..Default::default()
+                },
+            );
+    }
+
+    token_repository
+        .try_refresh_or_login(&TryReason::AssertCommand)
+        .await?;
+
+    let mut claims = token_repository
+        .read_claims(token_type.into())?
+        .context("missing token. Please run `p6m login`")?;
+    sort_claims(&mut claims);
+
+    claims
+        .assert(&desired_claims(matches))
+        .context("Token doesn't satisfy the required claims")?;
+
+    println!("Token satisfies the required claims");
+    Ok(())
+}
+
+fn desired_claims(matches: &ArgMatches) -> Claims {
+    let values = |id: &str| {
+        matches
+            .get_many::<String>(id)
+            .map(|values| values.cloned().collect::<Vec<String>>())
+    };
+
+    let mut claims = Claims {
+        roles: values("role"),
+        permissions: values("permission"),
+        ..Default::default()
+    };
+    sort_claims(&mut claims);
+    claims
+}
+
+fn sort_claims(claims: &mut Claims) {
+    if let Some(roles) = claims.roles.as_mut() {
+        roles.sort();
+    }
+    if let Some(permissions) = claims.permissions.as_mut() {
+        permissions.sort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::command;
+
+    #[test]
+    fn test_desired_claims() {
+        let matches = command()
+            .try_get_matches_from([
+                "p6m",
+                "auth",
+                "assert",
+                "--role",
+                "developer",
+                "--role",
+                "admin",
+            ])
+            .unwrap();
+        let (_, assert_matches) = matches.subcommand().unwrap();
+        let (_, assert_matches) = assert_matches.subcommand().unwrap();
+        let desired = desired_claims(assert_matches);
+
+        assert_eq!(
+            desired.roles,
+            Some(vec!["admin".to_string(), "developer".to_string()])
+        );
+        assert_eq!(desired.permissions, None);
+
+        let mut claims = Claims {
+            roles: Some(vec!["developer".into(), "admin".into()]),
+            permissions: Some(vec!["products:write".into()]),
+            ..Default::default()
+        };
+        sort_claims(&mut claims);
+        assert!(claims.assert(&desired).is_ok());
+
+        claims.roles = Some(vec!["developer".into()]);
+        assert!(claims.assert(&desired).is_err());
+    }
+}
@@ -1,4 +1,21 @@
+pub use openid::{OpenIdDiscoveryDocument, UserInfo};
 pub use token_repository::*;
+pub mod assert;
 mod openid;
 mod serde;
 mod token_repository;
+
+use crate::cli::{self, P6mEnvironment};
+use anyhow::Error;
+use clap::ArgMatches;
+
+pub async fn execute(environment: P6mEnvironment, matches: &ArgMatches) -> Result<(), Error> {
+    match cli::subcommand("p6m auth", matches)? {
+        Some(("assert", subargs)) => assert::execute(environment, subargs).await,
+        Some((command, _)) => Err(Error::msg(format!(
+            "Unimplemented auth command: '{}'",
+            command
+        ))),
+        None => Err(Error::msg("No auth command given")),
+    }
+}
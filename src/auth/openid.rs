@@ -513,3 +513,115 @@ impl AccessTokenResponse {
         )
     }
 }
+
+/// How many times [UserInfo::request] tries before giving up on transient failures.
+const USERINFO_ATTEMPTS: u32 = 3;
+const USERINFO_TIMEOUT: time::Duration = time::Duration::from_secs(10);
+const USERINFO_BACKOFF: time::Duration = time::Duration::from_millis(500);
+
+/// The identity provider's userinfo response. Claims other than the well-known ones are kept in `other`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct UserInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub org: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p6m_email: Option<String>,
+    #[serde(flatten)]
+    pub other: std::collections::BTreeMap<String, serde_json::Value>,
+}
+
+impl UserInfo {
+    /// Fetches the userinfo for `access_token`, retrying timeouts, connection errors and 5xx/429 responses.
+    pub async fn request(endpoint: &str, access_token: &str) -> Result<Self, anyhow::Error> {
+        let client = reqwest::Client::builder()
+            .timeout(USERINFO_TIMEOUT)
+            .build()?;
+
+        let mut attempt = 1;
+        let response = loop {
+            debug!("Fetching userinfo from {endpoint} (attempt {attempt}/{USERINFO_ATTEMPTS})");
+            let result = client.get(endpoint).bearer_auth(access_token).send().await;
+
+            let transient = match &result {
+                Ok(response) => {
+                    response.status().is_server_error()
+                        || response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS
+                }
+                Err(error) => error.is_timeout() || error.is_connect(),
+            };
+            if transient && attempt < USERINFO_ATTEMPTS {
+                sleep(USERINFO_BACKOFF * attempt).await;
+                attempt += 1;
+                continue;
+            }
+
+            break result.with_context(|| format!("Unable to reach {endpoint}"))?;
+        };
+
+        match response.status() {
+            reqwest::StatusCode::UNAUTHORIZED => anyhow::bail!(
+                "{endpoint} rejected the access token (401); it may have expired or been revoked. Run `p6m login`"
+            ),
+            status if !status.is_success() => anyhow::bail!("{endpoint} returned {status}"),
+            _ => {}
+        }
+
+        let raw_response = response
+            .text()
+            .await
+            .with_context(|| format!("Unable to read the userinfo response from {endpoint}"))?;
+        trace!("Userinfo response: {}", raw_response);
+        serde_json::from_str(&raw_response)
+            .with_context(|| format!("Unable to parse the userinfo response from {endpoint}"))
+    }
+
+    pub fn to_json(&self) -> Result<String, anyhow::Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+impl std::fmt::Display for UserInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fields = [
+            ("Name", &self.name),
+            ("Email", &self.email),
+            ("p6m Email", &self.p6m_email),
+            ("Organization", &self.org),
+            ("Subject", &self.sub),
+        ];
+        let lines: Vec<String> = fields
+            .iter()
+            .filter_map(|(label, value)| value.as_ref().map(|value| format!("{label}: {value}")))
+            .collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_info() {
+        let user_info: UserInfo = serde_json::from_str(
+            r#"{"sub": "auth0|123", "name": "Dev", "email": "dev@example.com", "p6m_email": "dev@p6m.dev", "nickname": "dev"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            user_info.to_string(),
+            "Name: Dev\nEmail: dev@example.com\np6m Email: dev@p6m.dev\nSubject: auth0|123"
+        );
+        assert_eq!(user_info.other["nickname"], "dev");
+        assert!(user_info
+            .to_json()
+            .unwrap()
+            .contains("\"nickname\": \"dev\""));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{App, AuthN, AuthToken, Client};
+use crate::{exit_code, App, AuthN, AuthToken, Client};
 use anyhow::{Context, Result};
 use camino::{Utf8Path, Utf8PathBuf};
 use chrono::{DateTime, Duration, Local, Utc};
@@ -7,6 +7,7 @@ use log::{debug, trace};
 use openid::AccessTokenResponse;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use sha2::{Digest, Sha256};
 use std::io::IsTerminal;
 use std::{
     collections::BTreeMap,
@@ -22,6 +23,9 @@ pub enum TryReason {
     LoginCommand,
     SsoCommand,
     WhoAmICommand,
+    TokenCommand,
+    AssertCommand,
+    AppsCommand,
     LoginTo(App),
     RefreshFor(App),
 }
@@ -32,6 +36,9 @@ impl Display for TryReason {
             TryReason::LoginCommand => write!(f, "for `login` command"),
             TryReason::SsoCommand => write!(f, "for `sso` command"),
             TryReason::WhoAmICommand => write!(f, "for `whoami` command"),
+            TryReason::TokenCommand => write!(f, "for `token` command"),
+            TryReason::AssertCommand => write!(f, "for `auth assert` command"),
+            TryReason::AppsCommand => write!(f, "for `apps` command"),
             TryReason::LoginTo(source) => write!(f, "to {}", source.name),
             TryReason::RefreshFor(source) => write!(f, "for {}", source.name),
         }
@@ -72,16 +79,61 @@ impl Display for AuthReason {
     }
 }
 
+/// How long before they expire tokens are refreshed, unless overridden by `P6M_REFRESH_MARGIN_MINUTES`.
+const DEFAULT_REFRESH_MARGIN_MINUTES: i64 = 60;
+
+fn refresh_margin() -> Duration {
+    parse_refresh_margin(env::var("P6M_REFRESH_MARGIN_MINUTES").ok())
+}
+
+/// Whole, non-negative minutes; anything else falls back to the default rather than failing every command.
+fn parse_refresh_margin(minutes: Option<String>) -> Duration {
+    let minutes = minutes
+        .and_then(|minutes| minutes.trim().parse::<i64>().ok())
+        .filter(|minutes| *minutes >= 0)
+        .unwrap_or(DEFAULT_REFRESH_MARGIN_MINUTES);
+    Duration::minutes(minutes)
+}
+
+/// Whether a token expiring at `expiration` is still good for at least `margin`.
+pub fn token_valid(expiration: DateTime<Utc>, margin: Duration) -> bool {
+    valid_at(expiration, margin, Utc::now())
+}
+
+fn valid_at(expiration: DateTime<Utc>, margin: Duration, now: DateTime<Utc>) -> bool {
+    now + margin < expiration
+}
+
+/// The token's `aud` claim, which may be a single string or a list.
+fn token_audiences(token: &str) -> Vec<String> {
+    let aud = raw::split_token(token)
+        .ok()
+        .and_then(|TokenSlices { claims, .. }| raw::decode_json_token_slice(claims).ok())
+        .map(|claims| claims["aud"].clone())
+        .unwrap_or_default();
+    match aud {
+        Value::String(aud) => vec![aud],
+        Value::Array(auds) => auds
+            .into_iter()
+            .filter_map(|aud| aud.as_str().map(String::from))
+            .collect(),
+        _ => vec![],
+    }
+}
+
 /// Acts as an abstraction for reading and writing tokens from disk.
 #[derive(Debug, Clone)]
 pub struct TokenRepository {
     pub auth_n: AuthN,
     auth_dir: Utf8PathBuf,
     organization_id: Option<String>,
+    audience: Option<String>,
     force: bool,
+    offline: bool,
     scopes: Vec<String>,
     default_scopes: String,
     desired_claims: Claims,
+    refresh_margin: Duration,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -136,6 +188,13 @@ impl Display for Claims {
 }
 
 impl Claims {
+    /// Checks that every claim set in `desired_claims` is matched by these claims.
+    ///
+    /// - Arrays must match exactly, except that `["*"]` matches any non-empty array.
+    /// - Maps (`orgs`, from id to name) need every desired entry to match some actual entry, where
+    ///   `*` matches any id or name: `{"<id>": "*"}` or `{"*": "<name>"}` asserts membership in one
+    ///   org, and `{"*": "*"}` membership in any. An empty desired map requires an empty map.
+    /// - Anything else must be equal.
     pub fn assert(&self, desired_claims: &Claims) -> Result<()> {
         debug!("asserting claims: {:?}", self);
         debug!("desired_claims: {:?}", desired_claims);
@@ -180,6 +239,24 @@ impl Claims {
                             "Field {} mismatch: expected {:?}, found {:?}", key, desired_value, self_value
                         )),
                     },
+                    // Handle map matching: each desired entry must match an actual one.
+                    (Value::Object(exp_map), Value::Object(act_map)) if !exp_map.is_empty() => {
+                        let matches = |pattern: &Value, actual: &Value| {
+                            pattern == actual || pattern.as_str() == Some("*")
+                        };
+                        exp_map.iter().try_for_each(|(exp_key, exp_value)| {
+                            let found = act_map.iter().any(|(act_key, act_value)| {
+                                (exp_key == "*" || exp_key == act_key) && matches(exp_value, act_value)
+                            });
+                            if found {
+                                Ok(())
+                            } else {
+                                Err(anyhow::anyhow!(
+                                    "Field {} mismatch: expected an entry matching {:?}: {:?}, found {:?}", key, exp_key, exp_value, self_value
+                                ))
+                            }
+                        })
+                    },
                     // For non-array values, require exact equality.
                     _ if desired_value == self_value => Ok(()),
                     _ => Err(anyhow::anyhow!(
@@ -192,6 +269,74 @@ impl Claims {
         Ok(())
     }
 
+    /// Describes the human-relevant claims, one per line, omitting any that are missing.
+    pub fn describe(&self) -> Vec<String> {
+        let mut detail = vec![];
+
+        if let Some(email) = self.email.as_ref() {
+            detail.push(format!("Email: {email}"));
+        }
+        if let Some(org) = self.org.as_ref() {
+            detail.push(format!("Organization: {org}"));
+        }
+        if let Some(roles) = self.roles.as_ref().filter(|roles| !roles.is_empty()) {
+            detail.push(format!("Roles: {}", roles.join(", ")));
+        }
+        if let Some(permissions) = self
+            .permissions
+            .as_ref()
+            .filter(|permissions| !permissions.is_empty())
+        {
+            detail.push(format!("Permissions: {}", permissions.join(", ")));
+        }
+        if let Some(exp) = self.exp.and_then(|exp| DateTime::from_timestamp(exp, 0)) {
+            detail.push(format!(
+                "Expires: {}",
+                exp.with_timezone(&Local::now().timezone())
+            ));
+        }
+
+        detail
+    }
+
+    /// Labels and values of the human-relevant claims, with one value per list entry, for a table.
+    pub fn table_rows(&self) -> Vec<(&'static str, Vec<String>)> {
+        let list = |values: &Option<Vec<String>>| values.clone().unwrap_or_default();
+
+        vec![
+            ("Email", self.email.iter().cloned().collect()),
+            ("Organization", self.org.iter().cloned().collect()),
+            ("Roles", list(&self.roles)),
+            ("Permissions", list(&self.permissions)),
+            (
+                "Organizations",
+                self.orgs
+                    .iter()
+                    .flatten()
+                    .map(|(id, name)| format!("{name} ({id})"))
+                    .collect(),
+            ),
+            (
+                "Scopes",
+                self.scope
+                    .iter()
+                    .flat_map(|scope| scope.split_whitespace().map(String::from))
+                    .collect(),
+            ),
+            (
+                "Expires",
+                self.exp
+                    .and_then(|exp| DateTime::from_timestamp(exp, 0))
+                    .map(|exp| exp.with_timezone(&Local::now().timezone()).to_string())
+                    .into_iter()
+                    .collect(),
+            ),
+        ]
+        .into_iter()
+        .filter(|(_, values)| !values.is_empty())
+        .collect()
+    }
+
     pub fn merge(&mut self, from: Claims) {
         let mut existing =
             serde_json::to_value(self.clone()).expect("Failed to serialize existing");
@@ -248,21 +393,146 @@ impl Into<Claims> for Option<String> {
     }
 }
 
+/// Creates `path` (and its parents) readable only by the current user on Unix.
+fn create_private_dir(path: &Utf8Path) -> Result<()> {
+    fs::create_dir_all(path)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o700))?;
+    }
+
+    Ok(())
+}
+
+/// Writes `contents` to `path` readable only by the current user on Unix, since tokens are bearer credentials.
+fn write_private_file(path: &Utf8Path, contents: &str) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        // mode only applies to newly created files, so tighten tokens written by older versions too
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        file.write_all(contents.as_bytes())?;
+    }
+
+    #[cfg(not(unix))]
+    fs::write(path, contents)?;
+
+    Ok(())
+}
+
+fn format_table(rows: &[(&str, Vec<String>)]) -> String {
+    let width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+
+    rows.iter()
+        .flat_map(|(label, values)| {
+            values.iter().enumerate().map(move |(i, value)| {
+                let label = if i == 0 { *label } else { "" };
+                format!("{label:<width$}  {value}")
+            })
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Lists the organizations on a token as `name (id)` for error messages.
+fn describe_organizations(orgs: Option<&BTreeMap<String, String>>) -> String {
+    match orgs.filter(|orgs| !orgs.is_empty()) {
+        Some(orgs) => format!(
+            "available organizations: {}",
+            orgs.iter()
+                .map(|(id, name)| format!("{name} ({id})"))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        None => "you don't have access to any organizations. Ask an organization admin for access, then re-run `p6m login`".to_string(),
+    }
+}
+
+/// Finds the organization matching `organization` by id or name, returning its id and the
+/// matched id or name as spelled in the token.
+///
+/// Exact matches win, then case-insensitive matches, then a case-insensitive prefix, as long
+/// as only one organization matches.
+fn resolve_organization(
+    orgs: Option<&BTreeMap<String, String>>,
+    organization: &str,
+) -> Result<(String, String)> {
+    let candidates: Vec<(&String, &String)> = orgs
+        .map(|orgs| {
+            orgs.iter()
+                .flat_map(|(id, name)| [(id, id), (id, name)])
+                .collect()
+        })
+        .unwrap_or_default();
+    let wanted = organization.to_lowercase();
+
+    let matchers: [&dyn Fn(&String) -> bool; 3] = [
+        &|value| value == organization,
+        &|value| value.to_lowercase() == wanted,
+        &|value| value.to_lowercase().starts_with(&wanted),
+    ];
+
+    for matcher in matchers {
+        let mut matches: Vec<(&String, &String)> = candidates
+            .iter()
+            .filter(|(_, value)| matcher(value))
+            .cloned()
+            .collect();
+        matches.dedup_by_key(|(id, _)| *id);
+
+        match matches.as_slice() {
+            [] => continue,
+            [(id, value)] => return Ok((id.to_string(), value.to_string())),
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "organization {organization} is ambiguous; it matches {}",
+                    matches
+                        .iter()
+                        .map(|(id, _)| format!(
+                            "{} ({id})",
+                            orgs.and_then(|orgs| orgs.get(*id)).unwrap_or(id)
+                        ))
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ))
+            }
+        }
+    }
+
+    Err(anyhow::anyhow!(
+        "organization {organization} not found in your token; {}",
+        describe_organizations(orgs)
+    ))
+}
+
 impl TokenRepository {
     pub const DEFAULT_SCOPES: &str = "openid email offline_access login:cli";
 
     /// Creates a [TokenRepository] given a [P6mEnvironment].
     pub fn new(auth_n: &AuthN, auth_dir: &Utf8PathBuf) -> Result<Self> {
-        fs::create_dir_all(&auth_dir)?;
+        create_private_dir(auth_dir)?;
 
         let mut token_repository = TokenRepository {
             auth_n: auth_n.clone(),
             auth_dir: auth_dir.clone(),
             organization_id: None,
+            audience: None,
             force: false,
+            offline: false,
             scopes: auth_n.scopes.clone().unwrap_or_default(),
             default_scopes: Self::DEFAULT_SCOPES.to_string(),
             desired_claims: Claims::default(),
+            refresh_margin: refresh_margin(),
         };
 
         token_repository
@@ -281,11 +551,31 @@ impl TokenRepository {
         self
     }
 
+    /// Never touch the network; use whatever tokens are on disk, even if they're expired.
+    pub fn with_offline(&mut self, offline: bool) -> &mut Self {
+        self.offline = offline;
+        self
+    }
+
+    fn read_offline_tokens(&mut self) -> Result<&mut Self> {
+        if !self.is_logged_in() {
+            return Err(exit_code::not_logged_in(
+                "No tokens on disk; go online and run `p6m login`",
+            ));
+        }
+
+        debug!("Offline; using tokens on disk");
+        let claims: Claims = Into::into(self.read_tokens()?.id_token);
+        claims.assert(&self.desired_claims)?;
+
+        Ok(self)
+    }
+
     pub fn with_organization(&mut self, organization: &String) -> Result<&mut Self> {
         let token_repository = Self::new(&self.auth_n, &self.auth_dir)?;
 
         if !token_repository.is_logged_in() {
-            return Err(anyhow::Error::msg(
+            return Err(exit_code::not_logged_in(
                 "Please run `p6m login` before acquiring an organization token.",
             ));
         }
@@ -294,19 +584,8 @@ impl TokenRepository {
             .read_claims(AuthToken::Id)?
             .context("unable to read claims from id token")?;
 
-        let organization_id = id_claims
-            .clone()
-            .orgs
-            .and_then(|orgs| {
-                orgs.into_iter()
-                    .find(|org| {
-                        // match on either the key (org id) or the value (org name)
-                        org.0 == organization.to_string() || org.1 == organization.to_string()
-                    })
-                    .map(|o| o.0)
-            })
-            .context("missing desired organization in access token claims")
-            .map_err(|e| {
+        let (organization_id, organization) =
+            resolve_organization(id_claims.orgs.as_ref(), organization).map_err(|e| {
                 debug!("Unable to find organization {organization} in claims: {id_claims}",);
                 e
             })?;
@@ -315,7 +594,7 @@ impl TokenRepository {
         self.with_scope(
             format!("org:{}", organization_id).as_str(),
             Claims {
-                org: Some(organization.clone()),
+                org: Some(organization),
                 ..Default::default()
             },
         );
@@ -323,12 +602,30 @@ impl TokenRepository {
         Ok(self)
     }
 
+    /// Switches to the tokens of the app with the given client id, logging in to it if needed.
+    ///
+    /// The app's metadata is cached next to its tokens, so while those tokens are still valid
+    /// (e.g. on every `kubectl` call using `--output k8s-auth`) no network requests are made.
     pub async fn with_authn_app_id(&mut self, id: &String) -> Result<&mut Self> {
-        let app = Client::new(&self.auth_n.apps_uri())
-            .with_token(self.read_token(AuthToken::Id)?)
-            .app(id)
-            .await
-            .context("Unable to get app")?;
+        let app = match self.cached_app(id) {
+            Some(app) => {
+                debug!("Using cached app {id}");
+                app
+            }
+            None if self.offline => serde_json::from_str(
+                &fs::read_to_string(self.app_cache_path(id))
+                    .with_context(|| format!("App {id} isn't cached; go online and retry"))?,
+            )?,
+            None => {
+                let app = Client::new(&self.auth_n.apps_uri())
+                    .with_token(self.read_token(AuthToken::Id)?)
+                    .app(id)
+                    .await
+                    .context("Unable to get app")?;
+                self.write_cached_app(&app)?;
+                app
+            }
+        };
 
         self.with_app(&app).context("Unable to set app")?;
         trace!(
@@ -367,6 +664,10 @@ impl TokenRepository {
     }
 
     pub async fn try_login(&mut self, reason: &TryReason) -> Result<&mut Self> {
+        if self.offline {
+            return self.read_offline_tokens();
+        }
+
         let access_token_response = match self.force {
             true => {
                 self.clear()?;
@@ -393,6 +694,18 @@ impl TokenRepository {
     }
 
     pub async fn try_refresh(&mut self, reason: &TryReason) -> Result<&mut Self> {
+        if self.offline {
+            return self.read_offline_tokens();
+        }
+
+        // A refresh keeps the audience the tokens were issued for, so only a login can change it
+        if !self.audience_matches()? {
+            return Err(anyhow::anyhow!(
+                "stored tokens are for a different audience than {}",
+                self.audience.as_deref().unwrap_or_default()
+            ));
+        }
+
         let access_token_response = match (self.force, self.should_refresh()?) {
             (true, _) => {
                 self.refresh(TryAuthReason::Refresh((
@@ -427,6 +740,22 @@ impl TokenRepository {
         Ok(self)
     }
 
+    /// Refreshes tokens if possible, falling back to a forced login.
+    ///
+    /// The login itself refuses to prompt without a terminal, so scripts fail fast.
+    pub async fn try_refresh_or_login(&mut self, reason: &TryReason) -> Result<&mut Self> {
+        if let Err(e) = self.try_refresh(reason).await {
+            if self.offline {
+                return Err(e);
+            }
+            debug!("Unable to refresh: {}", e);
+            debug!("Unable to refresh, trying to login");
+            self.force().try_login(reason).await?;
+        }
+
+        Ok(self)
+    }
+
     async fn login(&mut self, reason: TryAuthReason) -> Result<AccessTokenResponse> {
         debug!("attempting login due to: {reason}");
 
@@ -434,7 +763,7 @@ impl TokenRepository {
         // Device code flow requires a TTY for user to copy the code.
         if !self.auth_n.is_interactive() && !std::io::stdin().is_terminal() {
             let cmd = env::args().into_iter().collect::<Vec<_>>().join(" ");
-            return Err(anyhow::Error::msg(format!(
+            return Err(exit_code::not_logged_in(format!(
                 "Please run `{cmd}` in an interactive session."
             )));
         }
@@ -493,25 +822,75 @@ impl TokenRepository {
 
     pub fn clear(&self) -> Result<()> {
         fs::remove_dir_all(&self.auth_dir)?;
-        fs::create_dir_all(&self.auth_dir)?;
+        create_private_dir(&self.auth_dir)?;
         Ok(())
     }
 
+    /// Requests tokens for another API audience, stored apart from the default audience's tokens.
+    ///
+    /// Call after [Self::with_organization], which needs the default tokens to resolve the organization.
+    pub fn with_audience(&mut self, audience: &str) -> Result<&mut Self> {
+        self.auth_n.with_audience(audience);
+        self.audience = Some(audience.to_string());
+        let digest = hex::encode(Sha256::digest(audience.as_bytes()));
+        self.auth_dir = self.auth_dir.join(format!("aud_{}", &digest[..16]));
+        create_private_dir(&self.auth_dir)?;
+        Ok(self)
+    }
+
+    /// Whether the stored access token, if any, was issued for the audience given to [Self::with_audience].
+    fn audience_matches(&self) -> Result<bool> {
+        match (&self.audience, self.read_token(AuthToken::Access)?) {
+            (Some(audience), Some(token)) => Ok(token_audiences(&token).contains(audience)),
+            _ => Ok(true),
+        }
+    }
+
     /// Appends organization_id to the path for the stored tokens
     fn with_organization_id(&mut self, organization_id: &String) -> Result<()> {
         self.organization_id = Some(organization_id.clone());
         self.auth_dir = self.auth_dir.join(organization_id);
-        fs::create_dir_all(&self.auth_dir)?;
+        create_private_dir(&self.auth_dir)?;
         Ok(())
     }
 
+    fn app_cache_path(&self, client_id: &str) -> Utf8PathBuf {
+        self.auth_dir.join(format!("app_{client_id}")).join("APP")
+    }
+
+    /// Returns the cached app for `client_id`, but only while its tokens don't need refreshing.
+    fn cached_app(&self, client_id: &str) -> Option<App> {
+        let app: App =
+            serde_json::from_str(&fs::read_to_string(self.app_cache_path(client_id)).ok()?)
+                .map_err(|e| {
+                    debug!("Ignoring unreadable app cache for {client_id}: {e}");
+                    e
+                })
+                .ok()?;
+
+        let mut app_repository = self.clone();
+        app_repository.with_app(&app).ok()?;
+        match app_repository.should_refresh() {
+            Ok(false) => Some(app),
+            _ => None,
+        }
+    }
+
+    fn write_cached_app(&self, app: &App) -> Result<()> {
+        let path = self.app_cache_path(&app.client_id);
+        if let Some(parent) = path.parent() {
+            create_private_dir(parent)?;
+        }
+        write_private_file(&path, &serde_json::to_string(app)?)
+    }
+
     fn with_app(&mut self, app: &App) -> Result<()> {
         self.auth_n = app.auth_n.clone().context("missing authn")?;
         self.auth_dir = self.auth_dir.join(format!("app_{}", app.client_id));
         self.scopes = self.auth_n.additional_scopes();
         self.default_scopes = self.scopes.join(" ");
         self.desired_claims = Claims::default();
-        fs::create_dir_all(&self.auth_dir)?;
+        create_private_dir(&self.auth_dir)?;
         Ok(())
     }
 
@@ -556,6 +935,16 @@ impl TokenRepository {
         Ok(claims)
     }
 
+    /// Reads the organizations (id to name) available on the ID token.
+    ///
+    /// Returns an empty map if the user is not logged in.
+    pub fn organizations(&self) -> Result<BTreeMap<String, String>> {
+        Ok(self
+            .read_claims(AuthToken::Id)?
+            .and_then(|claims| claims.orgs)
+            .unwrap_or_default())
+    }
+
     pub fn is_logged_in(&self) -> bool {
         let id_token = self.read_token(AuthToken::Id).unwrap_or(None);
         let access_token = self.read_token(AuthToken::Access).unwrap_or(None);
@@ -571,11 +960,8 @@ impl TokenRepository {
     pub fn should_refresh(&self) -> Result<bool> {
         trace!("Checking if tokens should be refreshed");
 
-        let id_pre_exp = self.clone().read_expiration(AuthToken::Id)? - Duration::hours(1);
-        let access_pre_exp = self.clone().read_expiration(AuthToken::Access)? - Duration::hours(1);
-
-        let access_token_will_exp = Utc::now() > access_pre_exp;
-        let id_token_will_exp = Utc::now() > id_pre_exp;
+        let access_token_will_exp = !self.token_valid(AuthToken::Access, self.refresh_margin)?;
+        let id_token_will_exp = !self.token_valid(AuthToken::Id, self.refresh_margin)?;
 
         debug!("Access token expiring? {access_token_will_exp}");
         debug!("Id token expiring? {id_token_will_exp}");
@@ -583,6 +969,14 @@ impl TokenRepository {
         Ok(access_token_will_exp || id_token_will_exp)
     }
 
+    /// Whether the stored token is still good for at least `margin`.
+    pub fn token_valid(&self, token_type: AuthToken, margin: Duration) -> Result<bool> {
+        Ok(token_valid(
+            self.clone().read_expiration(token_type)?,
+            margin,
+        ))
+    }
+
     // Get the expiration date of the desired token
     pub fn read_expiration(self, token_type: AuthToken) -> Result<DateTime<Utc>> {
         let claims = self.read_claims(token_type.clone())?.unwrap_or_default();
@@ -603,7 +997,7 @@ impl TokenRepository {
         if let Some(token) = token {
             let path = self.token_path(token_type);
             trace!("Writing {path}");
-            fs::write(path, token)?;
+            write_private_file(&path, token)?;
         }
         Ok(())
     }
@@ -644,33 +1038,18 @@ impl TokenRepository {
     }
 
     pub fn to_string(&self) -> String {
-        if let Some(claims) = self.read_claims(AuthToken::Id).unwrap_or(None) {
-            let detail: Vec<String> = match (
-                claims.email.as_ref(),
-                claims.org.as_ref(),
-                claims.permissions.as_ref(),
-            ) {
-                (Some(email), Some(org), permissions) => {
-                    vec![
-                        format!("Email: {}", email),
-                        format!("Organization: {}", org),
-                        format!(
-                            "Permissions: {}",
-                            match permissions {
-                                Some(permissions) => permissions.join(", "),
-                                None => "None".to_string(),
-                            }
-                        ),
-                    ]
-                }
-                (Some(email), None, _) => vec![format!("Email: {}", email)],
-                _ => vec![],
-            };
-
-            return detail.join("\n");
-        };
+        match self.read_claims(AuthToken::Id).unwrap_or(None) {
+            Some(claims) => claims.describe().join("\n"),
+            None => "Not logged in".into(),
+        }
+    }
 
-        "Not logged in".into()
+    /// The id token claims as aligned label/value columns, one list entry per line.
+    pub fn to_table(&self) -> String {
+        match self.read_claims(AuthToken::Id).unwrap_or(None) {
+            Some(claims) => format_table(&claims.table_rows()),
+            None => "Not logged in".into(),
+        }
     }
 
     pub fn to_json(&self) -> Result<String, anyhow::Error> {
@@ -681,6 +1060,14 @@ impl TokenRepository {
         Ok(serde_json::to_string_pretty(&claims)?)
     }
 
+    pub fn to_yaml(&self) -> Result<String, anyhow::Error> {
+        let claims = self
+            .read_claims(AuthToken::Id)
+            .context("unable to get claims")?
+            .context("not logged in")?;
+        Ok(serde_yaml::to_string(&claims)?.trim_end().to_string())
+    }
+
     pub async fn scope_str(&mut self) -> Result<String> {
         let existing_scopes: Vec<String> = self
             .read_claims(AuthToken::Access)
@@ -723,6 +1110,242 @@ impl TokenRepository {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_valid_at_margin_boundary() {
+        let now = Utc::now();
+        let margin = Duration::hours(1);
+
+        assert!(valid_at(now + Duration::minutes(61), margin, now));
+        assert!(!valid_at(now + Duration::minutes(60), margin, now));
+        assert!(!valid_at(now + Duration::minutes(59), margin, now));
+        assert!(valid_at(now + Duration::seconds(1), Duration::zero(), now));
+        assert!(!valid_at(now, Duration::zero(), now));
+    }
+
+    /// A repository in a fresh temp dir, removed when the returned guard is dropped.
+    fn temp_token_repository() -> (TempDir, TokenRepository) {
+        let auth_dir = TempDir::new("auth-test");
+        let token_repository = TokenRepository::new(
+            &AuthN {
+                client_id: None,
+                discovery_uri: None,
+                token_preference: None,
+                params: None,
+                apps_uri: None,
+                scopes: None,
+            },
+            &auth_dir,
+        )
+        .unwrap();
+        (auth_dir, token_repository)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_token_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (auth_dir, token_repository) = temp_token_repository();
+
+        token_repository
+            .write_token(AuthToken::Access, Some(&"token".to_string()))
+            .unwrap();
+
+        let mode = |path: &Utf8Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&auth_dir), 0o700);
+        assert_eq!(mode(&auth_dir.join(AuthToken::Access.to_string())), 0o600);
+    }
+
+    #[test]
+    fn test_parse_refresh_margin() {
+        let parse = |minutes: &str| parse_refresh_margin(Some(minutes.to_string()));
+
+        assert_eq!(parse_refresh_margin(None), Duration::minutes(60));
+        assert_eq!(parse(" 5 "), Duration::minutes(5));
+        assert_eq!(parse("0"), Duration::zero());
+        assert_eq!(parse("-5"), Duration::minutes(60));
+        assert_eq!(parse("soon"), Duration::minutes(60));
+    }
+
+    #[test]
+    fn test_should_refresh_custom_margin() {
+        let (_auth_dir, mut token_repository) = temp_token_repository();
+
+        // Unsigned tokens expiring in 30 minutes; only the claims are read
+        let exp = (Utc::now() + Duration::minutes(30)).timestamp();
+        let token = jsonwebtokens::encode(
+            &serde_json::json!({ "alg": "none" }),
+            &serde_json::json!({ "exp": exp }),
+            &jsonwebtokens::Algorithm::new_unsecured().unwrap(),
+        )
+        .unwrap();
+        token_repository
+            .write_token(AuthToken::Id, Some(&token))
+            .unwrap();
+        token_repository
+            .write_token(AuthToken::Access, Some(&token))
+            .unwrap();
+
+        token_repository.refresh_margin = Duration::minutes(60);
+        assert!(token_repository.should_refresh().unwrap());
+
+        token_repository.refresh_margin = Duration::minutes(15);
+        assert!(!token_repository.should_refresh().unwrap());
+    }
+
+    #[test]
+    fn test_stored_token_for_another_audience_is_not_used() {
+        let (auth_dir, mut token_repository) = temp_token_repository();
+
+        let token = |aud: Value| {
+            jsonwebtokens::encode(
+                &serde_json::json!({ "alg": "none" }),
+                &serde_json::json!({ "aud": aud }),
+                &jsonwebtokens::Algorithm::new_unsecured().unwrap(),
+            )
+            .unwrap()
+        };
+        token_repository
+            .write_token(
+                AuthToken::Access,
+                Some(&token(serde_json::json!("https://api.p6m.run/v1/"))),
+            )
+            .unwrap();
+
+        token_repository
+            .with_audience("https://api.example.com/")
+            .unwrap();
+        assert_ne!(token_repository.auth_root(), auth_dir.as_path());
+        assert!(token_repository.auth_root().starts_with(&auth_dir));
+        assert_eq!(
+            token_repository.read_token(AuthToken::Access).unwrap(),
+            None
+        );
+
+        // Even a token that landed in the audience's directory is only used if its aud matches
+        token_repository
+            .write_token(
+                AuthToken::Access,
+                Some(&token(serde_json::json!("https://api.p6m.run/v1/"))),
+            )
+            .unwrap();
+        assert!(!token_repository.audience_matches().unwrap());
+        token_repository
+            .write_token(
+                AuthToken::Access,
+                Some(&token(serde_json::json!([
+                    "https://api.example.com/",
+                    "https://auth.example.com/userinfo"
+                ]))),
+            )
+            .unwrap();
+        assert!(token_repository.audience_matches().unwrap());
+    }
+
+    #[test]
+    fn test_describe_all_claims() {
+        let claims = Claims {
+            email: Some("dev@example.com".to_string()),
+            org: Some("acme".to_string()),
+            roles: Some(vec!["admin".to_string(), "dev".to_string()]),
+            permissions: Some(vec!["products:write".to_string()]),
+            exp: Some(0),
+            ..Default::default()
+        };
+
+        let detail = claims.describe();
+        assert_eq!(
+            detail[..4],
+            [
+                "Email: dev@example.com",
+                "Organization: acme",
+                "Roles: admin, dev",
+                "Permissions: products:write",
+            ]
+        );
+        assert!(
+            detail[4].starts_with("Expires: 1970-01-01")
+                || detail[4].starts_with("Expires: 1969-12-31")
+        );
+    }
+
+    #[test]
+    fn test_format_table() {
+        let claims = Claims {
+            email: Some("dev@example.com".to_string()),
+            roles: Some(vec!["admin".to_string(), "dev".to_string()]),
+            permissions: Some(vec![]),
+            scope: Some("openid email".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            format_table(&claims.table_rows()),
+            [
+                "Email   dev@example.com",
+                "Roles   admin",
+                "        dev",
+                "Scopes  openid",
+                "        email",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_describe_partial_claims() {
+        let claims = Claims {
+            email: Some("dev@example.com".to_string()),
+            roles: Some(vec!["dev".to_string()]),
+            permissions: Some(vec![]),
+            ..Default::default()
+        };
+        assert_eq!(claims.describe(), ["Email: dev@example.com", "Roles: dev"]);
+        assert!(Claims::default().describe().is_empty());
+    }
+
+    fn orgs() -> BTreeMap<String, String> {
+        BTreeMap::from([
+            ("org_1".to_string(), "MyOrg".to_string()),
+            ("org_2".to_string(), "MyOther".to_string()),
+            ("org_3".to_string(), "acme".to_string()),
+        ])
+    }
+
+    #[test]
+    fn test_resolve_organization_exact_and_case_insensitive() {
+        let orgs = orgs();
+        assert_eq!(
+            resolve_organization(Some(&orgs), "org_3").unwrap(),
+            ("org_3".to_string(), "org_3".to_string())
+        );
+        assert_eq!(
+            resolve_organization(Some(&orgs), "myorg").unwrap(),
+            ("org_1".to_string(), "MyOrg".to_string())
+        );
+    }
+
+    #[test]
+    fn test_resolve_organization_prefix() {
+        let orgs = orgs();
+        assert_eq!(
+            resolve_organization(Some(&orgs), "AC").unwrap(),
+            ("org_3".to_string(), "acme".to_string())
+        );
+        let error = resolve_organization(Some(&orgs), "my").unwrap_err();
+        assert!(error.to_string().contains("ambiguous"));
+        assert!(error.to_string().contains("MyOther (org_2)"));
+    }
+
+    #[test]
+    fn test_resolve_organization_missing_lists_available() {
+        let orgs = orgs();
+        let error = resolve_organization(Some(&orgs), "nope").unwrap_err();
+        assert!(error.to_string().contains("acme (org_3)"));
+        assert!(resolve_organization(None, "acme").is_err());
+    }
 
     #[test]
     fn test_empty_array_match() {
@@ -857,6 +1480,69 @@ mod tests {
         assert!(actual.assert(&desired).is_err());
     }
 
+    fn org_map(entries: &[(&str, &str)]) -> Option<BTreeMap<String, String>> {
+        Some(
+            entries
+                .iter()
+                .map(|(id, name)| (id.to_string(), name.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_orgs_membership_match() {
+        let actual = Claims {
+            orgs: org_map(&[("org_1", "acme"), ("org_2", "globex")]),
+            ..Default::default()
+        };
+
+        for desired in [
+            org_map(&[("org_2", "*")]),
+            org_map(&[("*", "acme")]),
+            org_map(&[("*", "*")]),
+            org_map(&[("org_1", "acme"), ("*", "globex")]),
+        ] {
+            let desired = Claims {
+                orgs: desired,
+                ..Default::default()
+            };
+            // Expected: Pass because each desired entry matches one org, ignoring the rest.
+            assert!(actual.assert(&desired).is_ok(), "{}", desired);
+        }
+    }
+
+    #[test]
+    fn test_orgs_membership_mismatch() {
+        let actual = Claims {
+            orgs: org_map(&[("org_1", "acme")]),
+            ..Default::default()
+        };
+
+        for desired in [
+            org_map(&[("org_2", "*")]),
+            org_map(&[("*", "globex")]),
+            org_map(&[("org_1", "globex")]),
+            org_map(&[]),
+        ] {
+            let desired = Claims {
+                orgs: desired,
+                ..Default::default()
+            };
+            assert!(actual.assert(&desired).is_err(), "{}", desired);
+        }
+
+        // Expected: Fail because a wildcard still needs at least one org.
+        let none = Claims {
+            orgs: org_map(&[]),
+            ..Default::default()
+        };
+        let any = Claims {
+            orgs: org_map(&[("*", "*")]),
+            ..Default::default()
+        };
+        assert!(none.assert(&any).is_err());
+    }
+
     #[test]
     fn test_merge_no_change_with_empty_incoming() {
         let mut original = Claims {
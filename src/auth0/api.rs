@@ -1,13 +1,30 @@
+use std::time::Duration;
+
 use anyhow::{anyhow, Context, Result};
+use log::debug;
+use reqwest::header::{HeaderMap, LINK};
 use serde_json::Value;
+use tokio::time::sleep;
 use url::Url;
 
-use super::{types::Apps, App};
+use crate::exit_code;
+
+use super::{
+    types::{Apps, AppsPage},
+    App,
+};
+
+/// Total attempts made for a request that fails transiently (connection errors, 429, 5xx).
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+/// Guards against a server that keeps handing out `next` links.
+const MAX_PAGES: usize = 100;
 
 #[derive(Debug, Clone)]
 pub struct Client {
     base_url: Option<String>,
     token: Option<String>,
+    organization: Option<String>,
     client: Option<reqwest::Client>,
 }
 
@@ -16,6 +33,7 @@ impl Client {
         Self {
             base_url: base_url.clone(),
             token: None,
+            organization: None,
             client: reqwest::Client::builder()
                 .user_agent(format!("p6m-cli/{}", env!("CARGO_PKG_VERSION")))
                 .build()
@@ -33,6 +51,19 @@ impl Client {
         self
     }
 
+    /// Names the organization the token is scoped to, so errors can say which org was denied.
+    pub fn with_organization(mut self, organization: Option<&String>) -> Self {
+        self.organization = organization.cloned();
+        self
+    }
+
+    fn organization_context(&self) -> String {
+        match self.organization.as_ref() {
+            Some(organization) => format!(" for organization {organization}"),
+            None => "".to_string(),
+        }
+    }
+
     async fn authorization(&self) -> Result<String> {
         Ok(format!(
             "Bearer {}",
@@ -40,14 +71,37 @@ impl Client {
         ))
     }
 
+    /// Fetches every app, following a `Link: <...>; rel="next"` header or a `next`
+    /// URL/cursor in the response body until the last page.
     pub async fn apps(&self) -> Result<Apps> {
-        Request::new(self)
+        let mut request = Request::new(self)
             .with_authorization(&self.authorization().await?)
             .with_method(&reqwest::Method::GET)
-            .with_endpoint("apps")?
-            .send::<Apps>()
-            .await?
-            .context("Missing apps")
+            .with_endpoint("apps")?;
+        let mut apps = vec![];
+
+        for page in 1..=MAX_PAGES {
+            let (response, headers) = request
+                .send_with_headers::<AppsPage>()
+                .await?
+                .context("Missing apps")?;
+
+            let next = next_link(&headers).or_else(|| response.next());
+            apps.extend(response.into_apps());
+
+            match next {
+                Some(next) => {
+                    debug!("Fetching apps page {}", page + 1);
+                    request = match Url::parse(&next) {
+                        Ok(url) => request.with_url(url.as_str()),
+                        Err(_) => request.with_query("cursor", &next)?,
+                    };
+                }
+                None => return Ok(apps.into_iter().collect()),
+            }
+        }
+
+        Err(anyhow!("Apps response exceeded {MAX_PAGES} pages"))
     }
 
     pub async fn app(&self, id: &str) -> Result<App> {
@@ -109,12 +163,27 @@ impl Request {
         Ok(self)
     }
 
+    pub fn with_url(mut self, url: &str) -> Self {
+        self.url = Some(url.to_string());
+        self
+    }
+
+    /// Sets `key` to `value` in the query string, replacing any existing value for `key`.
     pub fn with_query(mut self, key: &str, value: &str) -> Result<Self> {
         let url = self.url.as_ref().context("URL not found")?;
 
         let mut url = Url::parse(url).context("Failed to parse URL")?;
 
-        url.query_pairs_mut().append_pair(key, value);
+        let pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .filter(|(k, _)| k != key)
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect();
+
+        url.query_pairs_mut()
+            .clear()
+            .extend_pairs(pairs)
+            .append_pair(key, value);
 
         self.url = Some(url.to_string());
 
@@ -140,6 +209,16 @@ impl Request {
     }
 
     async fn send<T>(&self) -> Result<Option<T>>
+    where
+        T: serde::de::DeserializeOwned + std::fmt::Debug,
+    {
+        Ok(self
+            .send_with_headers::<T>()
+            .await?
+            .map(|(response, _)| response))
+    }
+
+    async fn send_with_headers<T>(&self) -> Result<Option<(T, HeaderMap)>>
     where
         T: serde::de::DeserializeOwned + std::fmt::Debug,
     {
@@ -149,34 +228,67 @@ impl Request {
 
         let url = self.url.as_ref().context("URL not found")?;
 
-        let request_builder = client.request(method.clone(), url);
+        let mut attempt = 1;
+        let response = loop {
+            let request_builder = client.request(method.clone(), url);
 
-        let request_builder = match self.authorization.as_ref() {
-            Some(authorization) => request_builder.header("Authorization", authorization),
-            None => request_builder,
-        };
+            let request_builder = match self.authorization.as_ref() {
+                Some(authorization) => request_builder.header("Authorization", authorization),
+                None => request_builder,
+            };
 
-        let response = match self.payload.as_ref() {
-            Some(payload) => request_builder
-                .json(payload)
-                .send()
-                .await
-                .context(format!("Failed to {} {:?} to {}", method, payload, url))?,
-            None => request_builder
-                .send()
-                .await
-                .context(format!("Failed to {} {}", method, url))?,
+            let request_builder = match self.payload.as_ref() {
+                Some(payload) => request_builder.json(payload),
+                None => request_builder,
+            };
+
+            let result = request_builder.send().await;
+
+            let transient = match &result {
+                Ok(response) => {
+                    response.status().is_server_error()
+                        || response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS
+                }
+                Err(error) => error.is_timeout() || error.is_connect(),
+            };
+
+            if transient && attempt < MAX_ATTEMPTS {
+                debug!("{method} {url} failed transiently (attempt {attempt}/{MAX_ATTEMPTS}); retrying");
+                sleep(RETRY_BACKOFF * attempt).await;
+                attempt += 1;
+                continue;
+            }
+
+            break match self.payload.as_ref() {
+                Some(payload) => {
+                    result.context(format!("Failed to {} {:?} to {}", method, payload, url))?
+                }
+                None => result.context(format!("Failed to {} {}", method, url))?,
+            };
         };
 
         if let Err(error) = response.error_for_status_ref() {
             let status = error.status().context("Missing status")?;
+            let organization = self.client.organization_context();
 
             match (status, self.allow_conflict) {
                 (reqwest::StatusCode::CONFLICT, Some(true)) => {
                     return Ok(None);
                 }
                 (reqwest::StatusCode::UNAUTHORIZED, _) => {
-                    return Err(anyhow!("Unauthorized: Please run `p6m login`"));
+                    return Err(exit_code::not_logged_in(format!(
+                        "Unauthorized{organization}: your token was rejected, most likely because it expired. Please run `p6m login`"
+                    )));
+                }
+                (reqwest::StatusCode::FORBIDDEN, _) => {
+                    return Err(anyhow!(
+                        "Forbidden{organization}: you are logged in, but lack access to {url}. Ask an organization admin for access"
+                    ));
+                }
+                (reqwest::StatusCode::NOT_FOUND, _) => {
+                    return Err(anyhow!(
+                        "Not found{organization}: {url} does not exist or is not visible to you"
+                    ));
                 }
                 _ => {}
             }
@@ -211,6 +323,8 @@ impl Request {
             }
         }
 
+        let headers = response.headers().clone();
+
         let text = response
             .text()
             .await
@@ -219,6 +333,54 @@ impl Request {
         let response: T = serde_json::from_str::<T>(&text)
             .context(format!("Failed to parse response: {}", text))?;
 
-        Ok(Some(response))
+        Ok(Some((response, headers)))
+    }
+}
+
+/// Extracts the `rel="next"` target from an RFC 8288 `Link` header.
+fn next_link(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(LINK)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .find_map(|link| {
+            let mut parts = link.split(';').map(str::trim);
+            let target = parts.next()?.strip_prefix('<')?.strip_suffix('>')?;
+            parts
+                .any(|param| param.replace(' ', "") == "rel=\"next\"" || param == "rel=next")
+                .then(|| target.to_string())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn link(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(LINK, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn next_link_finds_next_among_relations() {
+        let headers = link(
+            r#"<https://example.com/apps?page=1>; rel="prev", <https://example.com/apps?page=3>; rel="next""#,
+        );
+        assert_eq!(
+            next_link(&headers),
+            Some("https://example.com/apps?page=3".to_string())
+        );
+    }
+
+    #[test]
+    fn next_link_is_none_on_last_page() {
+        assert_eq!(
+            next_link(&link(r#"<https://example.com/apps?page=1>; rel="first""#)),
+            None
+        );
+        assert_eq!(next_link(&HeaderMap::new()), None);
     }
 }
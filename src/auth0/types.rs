@@ -1,6 +1,7 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, iter::FromIterator};
 
 use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
 use log::trace;
 use serde::{Deserialize, Serialize};
 use urlencoding::decode;
@@ -9,6 +10,8 @@ use urlencoding::decode;
 // https://azure.github.io/kubelogin/concepts/aks.html#azure-kubernetes-service-aad-server
 const AKS_AAD_CLIENT_ID: &str = "80faf920-1908-4b52-b5ef-a8e7bedfc67a";
 
+const CERTIFICATE_AUTHORITY_ORIGIN: &str = "https://meta.p6m.dev/certificate-authority";
+
 #[derive(Debug, Serialize, Deserialize, strum_macros::Display, Clone)]
 pub enum AuthToken {
     #[strum(to_string = "ACCESS_TOKEN")]
@@ -39,6 +42,12 @@ impl Apps {
     }
 }
 
+impl FromIterator<App> for Apps {
+    fn from_iter<I: IntoIterator<Item = App>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
 impl IntoIterator for Apps {
     type Item = App;
     type IntoIter = std::vec::IntoIter<Self::Item>;
@@ -48,6 +57,34 @@ impl IntoIterator for Apps {
     }
 }
 
+/// A single response from the `apps` endpoint, which is either a bare list of apps
+/// or a page of apps with a `next` URL or cursor.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum AppsPage {
+    List(Vec<App>),
+    Page {
+        apps: Vec<App>,
+        next: Option<String>,
+    },
+}
+
+impl AppsPage {
+    pub fn next(&self) -> Option<String> {
+        match self {
+            AppsPage::List(_) => None,
+            AppsPage::Page { next, .. } => next.clone().filter(|next| !next.is_empty()),
+        }
+    }
+
+    pub fn into_apps(self) -> Vec<App> {
+        match self {
+            AppsPage::List(apps) => apps,
+            AppsPage::Page { apps, .. } => apps,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AuthN {
@@ -77,6 +114,14 @@ impl AuthN {
         self.params.as_ref().and_then(|p| p.get("redirect_uri"))
     }
 
+    /// Requests tokens for a different API audience than the one configured.
+    pub fn with_audience(&mut self, audience: &str) -> &mut Self {
+        self.params
+            .get_or_insert_with(BTreeMap::new)
+            .insert("audience".to_string(), audience.to_string());
+        self
+    }
+
     pub fn apps_uri(&self) -> Option<String> {
         return self.apps_uri.clone();
     }
@@ -160,14 +205,32 @@ impl App {
         self.name.clone()
     }
 
+    /// The `ClaimName` metadata, or else the display name, reduced to alphanumerics separated by single dashes.
+    /// Falls back to the client id when nothing alphanumeric is left.
     pub fn machine_name(&self) -> String {
-        self.metadata
-            .get("ClaimName")
-            .map(|s| s.to_string())
-            .unwrap_or(self.display_name())
-            .chars()
-            .map(|c| if c.is_alphanumeric() { c } else { '-' })
-            .collect()
+        let name = sanitize_name(
+            &self
+                .metadata
+                .get("ClaimName")
+                .map(|s| s.to_string())
+                .unwrap_or(self.display_name()),
+        );
+        match name.is_empty() {
+            true => sanitize_name(&self.client_id),
+            false => name,
+        }
+    }
+
+    /// The name for this app's kube cluster and context: its `KubeName` metadata if set, otherwise
+    /// [App::machine_name] with `strip` removed from its end.
+    pub fn kube_name(&self, strip: &str) -> String {
+        match self.metadata.get("KubeName") {
+            Some(name) => sanitize_name(name),
+            None => {
+                let name = self.machine_name();
+                sanitize_name(name.strip_suffix(strip).unwrap_or(&name))
+            }
+        }
     }
 
     pub fn url(&self) -> String {
@@ -178,24 +241,179 @@ impl App {
         self.org.clone()
     }
 
+    /// Whether the app belongs to the organization, compared case-insensitively.
+    pub fn belongs_to(&self, organization: &str) -> bool {
+        self.org
+            .as_deref()
+            .is_some_and(|org| org.eq_ignore_ascii_case(organization))
+    }
+
+    /// Returns the base64-encoded PEM certificate authority for the cluster, as expected by
+    /// `certificate-authority-data` in a kubeconfig.
+    ///
+    /// The CA is carried in the URL fragment of the `https://meta.p6m.dev/certificate-authority`
+    /// origin, percent-encoded, either already base64-encoded or as a raw PEM.
     pub fn ca(&self) -> Result<String> {
-        let certificate_authority = self
+        let origin = self
             .origins
             .iter()
-            .find(|origin| origin.starts_with("https://meta.p6m.dev/certificate-authority"))
-            .map(|origin| {
-                url::Url::parse(origin)
-                    .context("unalbe to parse url")
-                    .map(|u| Some(u.fragment()?.to_string()))
-                    .context("unable to extract fragment")
-            })
-            .context("unable to find certificate authority")?
-            .context("missing certificate authority")?;
+            .find(|origin| origin.starts_with(CERTIFICATE_AUTHORITY_ORIGIN))
+            .with_context(|| {
+                format!(
+                    "app {} has no {CERTIFICATE_AUTHORITY_ORIGIN} origin",
+                    self.name
+                )
+            })?;
 
-        Ok(
-            decode(certificate_authority.context("missing ca")?.as_str())
-                .context("unable to decode ca")?
-                .to_string(),
-        )
+        let url = url::Url::parse(origin)
+            .with_context(|| format!("unable to parse certificate authority origin {origin}"))?;
+
+        let fragment = url
+            .fragment()
+            .filter(|fragment| !fragment.is_empty())
+            .with_context(|| {
+                format!(
+                    "certificate authority origin for app {} has no #fragment",
+                    self.name
+                )
+            })?;
+
+        let decoded = decode(fragment).with_context(|| {
+            format!(
+                "certificate authority for app {} is not valid percent-encoded UTF-8",
+                self.name
+            )
+        })?;
+        let ca = decoded.trim();
+
+        if ca.starts_with("-----BEGIN") {
+            return Ok(STANDARD.encode(ca));
+        }
+
+        // Base64 may arrive wrapped across lines, which kubeconfig doesn't accept
+        let ca: String = ca.chars().filter(|c| !c.is_whitespace()).collect();
+        STANDARD.decode(&ca).with_context(|| {
+            format!(
+                "certificate authority for app {} is neither base64 nor PEM",
+                self.name
+            )
+        })?;
+
+        Ok(ca)
+    }
+}
+
+fn sanitize_name(name: &str) -> String {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(origins: &[&str]) -> App {
+        App {
+            name: "cluster".into(),
+            org: None,
+            client_id: "client".into(),
+            url: "https://cluster.example.com".into(),
+            origins: origins.iter().map(|origin| origin.to_string()).collect(),
+            scopes: vec![],
+            metadata: BTreeMap::new(),
+            auth_n: None,
+        }
+    }
+
+    #[test]
+    fn belongs_to_matches_org_case_insensitively() {
+        let mut app = app(&[]);
+        assert!(!app.belongs_to("acme"));
+
+        app.org = Some("Acme".into());
+        assert!(app.belongs_to("acme"));
+        assert!(!app.belongs_to("acme-dev"));
+    }
+
+    #[test]
+    fn machine_name_sanitizes() {
+        let machine_name = |name: &str| {
+            let mut app = app(&[]);
+            app.name = name.into();
+            app.machine_name()
+        };
+
+        assert_eq!(machine_name("Acme Prod"), "Acme-Prod");
+        assert_eq!(machine_name("  acme -- prod!  "), "acme-prod");
+        assert_eq!(machine_name("-acme_prod-"), "acme-prod");
+        assert_eq!(machine_name("café prod"), "café-prod");
+        assert_eq!(machine_name("***"), "client");
+        assert_eq!(machine_name(""), "client");
+
+        let mut app = app(&[]);
+        app.metadata.insert("ClaimName".into(), "acme/prod".into());
+        assert_eq!(app.machine_name(), "acme-prod");
+    }
+
+    #[test]
+    fn kube_name_prefers_metadata_then_strips() {
+        let mut app = app(&[]);
+        app.name = "acme prod-auth0".into();
+        assert_eq!(app.kube_name("-auth0"), "acme-prod");
+        assert_eq!(app.kube_name(""), "acme-prod-auth0");
+
+        app.name = "auth0-acme-auth0".into();
+        assert_eq!(app.kube_name("auth0"), "auth0-acme");
+        assert_eq!(app.kube_name("-acme"), "auth0-acme-auth0");
+
+        app.metadata.insert("KubeName".into(), "prod east".into());
+        assert_eq!(app.kube_name("-auth0"), "prod-east");
+    }
+
+    #[test]
+    fn ca_decodes_percent_encoded_base64() {
+        let app = app(&[
+            "https://cluster.example.com",
+            "https://meta.p6m.dev/certificate-authority#TFMwdExTMUNSVWRKVGc%3D",
+        ]);
+        assert_eq!(app.ca().unwrap(), "TFMwdExTMUNSVWRKVGc=");
+    }
+
+    #[test]
+    fn ca_strips_whitespace_from_wrapped_base64() {
+        let app =
+            app(&["https://meta.p6m.dev/certificate-authority#TFMwdExTMU%0ANSVWRK%20VGc%3D%0A"]);
+        assert_eq!(app.ca().unwrap(), "TFMwdExTMUNSVWRKVGc=");
+    }
+
+    #[test]
+    fn ca_encodes_pem() {
+        let app = app(&[
+            "https://meta.p6m.dev/certificate-authority#-----BEGIN%20CERTIFICATE-----%0Aabc%0A-----END%20CERTIFICATE-----",
+        ]);
+        assert_eq!(
+            STANDARD.decode(app.ca().unwrap()).unwrap(),
+            b"-----BEGIN CERTIFICATE-----\nabc\n-----END CERTIFICATE-----"
+        );
+    }
+
+    #[test]
+    fn ca_errors_describe_the_failure() {
+        let message = |app: App| format!("{:#}", app.ca().unwrap_err());
+
+        assert!(message(app(&["https://cluster.example.com"])).contains("has no"));
+        assert!(
+            message(app(&["https://meta.p6m.dev/certificate-authority"])).contains("#fragment")
+        );
+        assert!(
+            message(app(&["https://meta.p6m.dev/certificate-authority#%FF"]))
+                .contains("percent-encoded")
+        );
+        assert!(message(app(&[
+            "https://meta.p6m.dev/certificate-authority#not*base64"
+        ]))
+        .contains("neither base64 nor PEM"));
     }
 }
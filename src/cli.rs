@@ -1,17 +1,31 @@
+use crate::apps;
+use crate::auth;
+use crate::context;
+use crate::exit_code;
+use crate::jwt;
+use crate::kubernetes;
+use crate::logging;
+use crate::login;
 use crate::models::artifact;
+use crate::repositories;
+use crate::sso::{kubeconfig, vcluster};
+use crate::version;
 use crate::whoami;
 use crate::workstation::check::Ecosystem;
 use crate::{AuthN, AuthToken};
+use anyhow::Context;
 use camino::{Utf8Path, Utf8PathBuf};
-use clap::{crate_version, value_parser, Arg, ArgMatches, Command};
+use clap::{value_parser, Arg, ArgGroup, ArgMatches, Command};
 use clap_complete::Shell;
+use log::debug;
+use std::collections::BTreeMap;
 use std::fs::create_dir_all;
 
 pub fn command() -> Command {
     clap::command!()
         .name("") // this string is prepended to -V and --version, resulting in invalid json
         .author("P6m Dev")
-        .version(crate_version!())
+        .version(version::current_version())
         .about("p6m CLI")
         .subcommand(
             Command::new("completions")
@@ -21,6 +35,39 @@ pub fn command() -> Command {
                     Arg::new("generator")
                         .value_parser(value_parser!(Shell)),
                 )
+                .arg(
+                    Arg::new("orgs")
+                        .long("orgs")
+                        .action(clap::ArgAction::SetTrue)
+                        .hide(true)
+                        .help("Print the organization names available to the logged in user, one per line")
+                )
+        )
+        .subcommand(
+            Command::new("version")
+                .about("Display version and build information")
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .help("Output format")
+                        .value_parser(value_parser!(version::Output))
+                        .default_value("default")
+                        .required(false),
+                )
+        )
+        .subcommand(
+            Command::new("self")
+                .about("Manage the p6m CLI itself")
+                .arg_required_else_help(true)
+                .subcommand(
+                    Command::new("update")
+                        .about("Update the p6m CLI to the latest release")
+                )
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Diagnose common problems with authentication, kubeconfig, and workstation setup")
         )
         .subcommand(
             Command::new("workstation")
@@ -35,6 +82,14 @@ pub fn command() -> Command {
                             .action(clap::ArgAction::Append)
                             .help("Ecosystem to check")
                     )
+                    .arg(
+                        Arg::new("exclude")
+                            .long("exclude")
+                            .short('x')
+                            .value_parser(Ecosystem::checkable_parser())
+                            .action(clap::ArgAction::Append)
+                            .help("Ecosystem to skip; checks all others when no ecosystem is given")
+                    )
                 )
                 .subcommand(
                     Command::new("setup")
@@ -59,9 +114,91 @@ pub fn command() -> Command {
                     .value_parser(value_parser!(artifact::StorageProvider))
                     .help("The storage provider to activate for this context.")
             )
+            .arg(
+                Arg::new("all")
+                    .long("all")
+                    .action(clap::ArgAction::SetTrue)
+                    .conflicts_with("organization-name")
+                    .help("Write config for every organization you belong to under ~/.p6m/contexts/<org>, leaving the global files alone")
+            )
+            .arg(
+                Arg::new("verify")
+                    .long("verify")
+                    .action(clap::ArgAction::SetTrue)
+                    .help("After writing the config, check that each registry (npm, Maven, Python) accepts the credentials")
+            )
+            .args_conflicts_with_subcommands(true)
+            .subcommand(Command::new("show")
+                .about("Show the organization and provider of the active context")
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .help("Output format")
+                        .value_parser(value_parser!(context::Output))
+                        .default_value("default")
+                        .required(false),
+                )
+            )
+            .subcommand(Command::new("export")
+                .about("Print registry URLs and credentials as environment variables instead of writing config files")
+                .arg(
+                    Arg::new("organization-name")
+                        .long("org")
+                        .short('o')
+                        .required(false)
+                        .action(clap::ArgAction::Set)
+                        .help("The JV Organization Name")
+                )
+                .arg(
+                    Arg::new("provider")
+                        .long("provider")
+                        .short('p')
+                        .required(false)
+                        .value_parser(value_parser!(artifact::StorageProvider))
+                        .help("The storage provider to export (defaults to the active context's)")
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .help("Print `export KEY='value'` lines for eval, or dotenv `KEY=value` lines")
+                        .value_parser(value_parser!(context::ExportFormat))
+                        .default_value("shell")
+                )
+            )
         )
         .subcommand(Command::new("jwt")
-            .about("Generate JWTs") 
+            .about("Generate and inspect JWTs")
+            .subcommand(Command::new("decode")
+                .about("Print a JWT's header and claims")
+                .arg(
+                    Arg::new("token")
+                        .help("The token, or - to read it from stdin (the default)")
+                )
+                .arg(
+                    Arg::new("verify")
+                        .long("verify")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Verify the signature against the identity provider's keys and check expiry, failing if either is bad")
+                )
+            )
+            .subcommand(Command::new("jwks")
+                .about("Print the identity provider's token signing keys")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .help("Print the JWKS as JSON, or the selected RSA key as a PEM public key")
+                        .value_parser(value_parser!(jwt::jwks::Format))
+                        .default_value("json")
+                )
+                .arg(
+                    Arg::new("kid")
+                        .long("kid")
+                        .help("Only the key with this key id; needed for PEM when there are several keys")
+                )
+            )
             .subcommand(Command::new("insecure")
                 .about("Generates an INSECURE JWT for development")
                 .alias("u")
@@ -77,6 +214,29 @@ pub fn command() -> Command {
                 )
             )
         )
+        .subcommand(Command::new("kube")
+            .about("Inspect your Kubernetes configuration")
+            .subcommand(Command::new("use")
+                .about("Switch the current kube context to a p6m cluster by organization or context name")
+                .arg(
+                    Arg::new("organization")
+                        .required(true)
+                        .help("Organization or context name; case-insensitive, and a unique prefix or substring is enough")
+                )
+            )
+            .subcommand(Command::new("current")
+                .about("Show the current kube context, and whether it is a p6m cluster")
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .help("Output format")
+                        .value_parser(value_parser!(kubernetes::Output))
+                        .default_value("default")
+                        .required(false),
+                )
+            )
+        )
         .subcommand(Command::new("open")
             .about("Open an Organization Resource")
             .arg_required_else_help(true)
@@ -84,6 +244,25 @@ pub fn command() -> Command {
                 Command::new("github")
                     .visible_alias("gh")
                     .about("Opens Github to the corresponding local repository, organization, or enterprise.")
+                    .arg(
+                        Arg::new("prs")
+                            .long("prs")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Open the repository's pull requests")
+                    )
+                    .arg(
+                        Arg::new("issues")
+                            .long("issues")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Open the repository's issues")
+                    )
+                    .arg(
+                        Arg::new("actions")
+                            .long("actions")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Open the repository's GitHub Actions runs")
+                    )
+                    .group(ArgGroup::new("subpage").args(["prs", "issues", "actions"]))
             )
             .subcommand(
                 Command::new("argocd")
@@ -109,7 +288,21 @@ pub fn command() -> Command {
                 .subcommand(
                     Command::new("ide-files")
                         .about("Purges IDE files recursively within one or more projects.")
-                        .arg(Arg::new("dry-run").long("dry-run").action(clap::ArgAction::SetTrue)),
+                        .arg(Arg::new("dry-run").long("dry-run").action(clap::ArgAction::SetTrue))
+                        .arg(
+                            Arg::new("skip-dir")
+                                .long("skip-dir")
+                                .value_name("NAME")
+                                .action(clap::ArgAction::Append)
+                                .default_values(["node_modules", "target", ".git"])
+                                .help("Directory names not to descend into (repeatable; replaces the defaults)"),
+                        )
+                        .arg(
+                            Arg::new("gitignore")
+                                .long("gitignore")
+                                .action(clap::ArgAction::SetTrue)
+                                .help("Also skip directories ignored by git (slower; asks git about each directory)"),
+                        ),
                 )
                 .subcommand(
                     Command::new("maven")
@@ -149,6 +342,22 @@ pub fn command() -> Command {
                         .action(clap::ArgAction::SetTrue)
                         .help("Include repositories that already exist locally")
                 )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .short('f')
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("existing")
+                        .help("With --all or --default-branch-only, also pull repositories that have local changes")
+                )
+                .arg(
+                    Arg::new("default-branch-only")
+                        .long("default-branch-only")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with("branch")
+                        .help("Check out and pull each existing repo's default branch (implies --all)")
+                )
+                .group(ArgGroup::new("existing").args(["all", "default-branch-only"]).multiple(true))
                 .arg(
                     Arg::new("dry-run")
                         .long("dry-run")
@@ -156,6 +365,181 @@ pub fn command() -> Command {
                         .action(clap::ArgAction::SetTrue)
                         .help("Don't actually pull or prune anything")
                 )
+                .arg(
+                    Arg::new("protocol")
+                        .long("protocol")
+                        .help("Protocol used to clone missing repos")
+                        .value_parser(value_parser!(repositories::Protocol))
+                        .default_value("ssh")
+                )
+                .arg(
+                    Arg::new("per-page")
+                        .long("per-page")
+                        .help("Organizations and repos fetched per GitHub API request (max 100)")
+                        .value_parser(value_parser!(u8).range(1..=100))
+                        .default_value("25")
+                )
+                .arg(
+                    Arg::new("concurrency")
+                        .long("concurrency")
+                        .short('j')
+                        .help("Organizations handled at once when no organization is selected")
+                        .value_parser(value_parser!(u8).range(1..=32))
+                        .default_value("4")
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .help("Output format; json prints what happened to each repo, and fails if any repo did")
+                        .value_parser(value_parser!(repositories::Output))
+                        .default_value("default")
+                )
+                .arg(
+                    Arg::new("exclude-org")
+                        .long("exclude-org")
+                        .value_name("ORG")
+                        .action(clap::ArgAction::Append)
+                        .help("Organization to leave out when no organization is selected (repeatable) [default: P6M_EXCLUDED_ORGS, or p6m-dev]")
+                )
+                .arg(
+                    Arg::new("branch")
+                        .long("branch")
+                        .short('b')
+                        .help("Branch to clone, and to check out in existing repos that are pulled; repos without it are skipped")
+                )
+                .arg(
+                    Arg::new("shallow")
+                        .long("shallow")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Clone missing repos with only recent history (see --depth); existing repos are unaffected")
+                )
+                .arg(
+                    Arg::new("depth")
+                        .long("depth")
+                        .value_name("N")
+                        .value_parser(value_parser!(u32).range(1..))
+                        .requires("shallow")
+                        .help("Commits of history in a --shallow clone [default: 1]")
+                )
+            )
+            .subcommand(Command::new("sync")
+                .about("Clone missing repos and fast-forward existing ones, skipping any with local changes")
+                .arg(
+                    Arg::new("organization-name")
+                        .long("org")
+                        .short('o')
+                        .required(false)
+                        .help("The JV Organization Name")
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .short('d')
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Don't actually clone or pull anything")
+                )
+                .arg(
+                    Arg::new("protocol")
+                        .long("protocol")
+                        .help("Protocol used to clone missing repos")
+                        .value_parser(value_parser!(repositories::Protocol))
+                        .default_value("ssh")
+                )
+                .arg(
+                    Arg::new("per-page")
+                        .long("per-page")
+                        .help("Organizations and repos fetched per GitHub API request (max 100)")
+                        .value_parser(value_parser!(u8).range(1..=100))
+                        .default_value("25")
+                )
+                .arg(
+                    Arg::new("concurrency")
+                        .long("concurrency")
+                        .short('j')
+                        .help("Organizations handled at once when no organization is selected")
+                        .value_parser(value_parser!(u8).range(1..=32))
+                        .default_value("4")
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .help("Output format; json prints what happened to each repo, and fails if any repo did")
+                        .value_parser(value_parser!(repositories::Output))
+                        .default_value("default")
+                )
+                .arg(
+                    Arg::new("exclude-org")
+                        .long("exclude-org")
+                        .value_name("ORG")
+                        .action(clap::ArgAction::Append)
+                        .help("Organization to leave out when no organization is selected (repeatable) [default: P6M_EXCLUDED_ORGS, or p6m-dev]")
+                )
+                .arg(
+                    Arg::new("branch")
+                        .long("branch")
+                        .short('b')
+                        .help("Branch to clone, and to check out in existing repos that are pulled; repos without it are skipped")
+                )
+                .arg(
+                    Arg::new("shallow")
+                        .long("shallow")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Clone missing repos with only recent history (see --depth); existing repos are unaffected")
+                )
+                .arg(
+                    Arg::new("depth")
+                        .long("depth")
+                        .value_name("N")
+                        .value_parser(value_parser!(u32).range(1..))
+                        .requires("shallow")
+                        .help("Commits of history in a --shallow clone [default: 1]")
+                )
+            )
+            .subcommand(Command::new("fetch")
+                .about("Fetch upstream changes into existing repos without touching their working trees")
+                .arg(
+                    Arg::new("organization-name")
+                        .long("org")
+                        .short('o')
+                        .required(false)
+                        .help("The JV Organization Name")
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .short('d')
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Don't actually fetch anything")
+                )
+                .arg(
+                    Arg::new("per-page")
+                        .long("per-page")
+                        .help("Organizations and repos fetched per GitHub API request (max 100)")
+                        .value_parser(value_parser!(u8).range(1..=100))
+                        .default_value("25")
+                )
+                .arg(
+                    Arg::new("concurrency")
+                        .long("concurrency")
+                        .short('j')
+                        .help("Organizations handled at once when no organization is selected")
+                        .value_parser(value_parser!(u8).range(1..=32))
+                        .default_value("4")
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .help("Output format; json prints what happened to each repo, and fails if any repo did")
+                        .value_parser(value_parser!(repositories::Output))
+                        .default_value("default")
+                )
+                .arg(
+                    Arg::new("exclude-org")
+                        .long("exclude-org")
+                        .value_name("ORG")
+                        .action(clap::ArgAction::Append)
+                        .help("Organization to leave out when no organization is selected (repeatable) [default: P6M_EXCLUDED_ORGS, or p6m-dev]")
+                )
             )
             .subcommand(
                 Command::new("push")
@@ -181,6 +565,44 @@ pub fn command() -> Command {
                             .action(clap::ArgAction::SetTrue)
                             .help("Don't actually push anything")
                     )
+                    .arg(
+                        Arg::new("message")
+                            .long("message")
+                            .short('m')
+                            .default_value("initial commit")
+                            .help("Commit message for repositories that are initialized by the push")
+                    )
+                    .arg(
+                        Arg::new("branch")
+                            .long("branch")
+                            .short('b')
+                            .help("Branch to create in new repositories and to push [default: main for new repositories, the current branch otherwise]")
+                    )
+                    .arg(
+                        Arg::new("public")
+                            .long("public")
+                            .action(clap::ArgAction::SetTrue)
+                            .conflicts_with("private")
+                            .help("Create public GitHub repositories")
+                    )
+                    .arg(
+                        Arg::new("private")
+                            .long("private")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Create private GitHub repositories (the default)")
+                    )
+                    .arg(
+                        Arg::new("enable-issues")
+                            .long("enable-issues")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Enable GitHub issues on created repositories")
+                    )
+                    .arg(
+                        Arg::new("enable-wiki")
+                            .long("enable-wiki")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Enable the GitHub wiki on created repositories")
+                    )
             )
             .subcommand(
                 Command::new("prune")
@@ -193,6 +615,25 @@ pub fn command() -> Command {
                             .help("The JV Organization Name")
                     )
             )
+            .subcommand(
+                Command::new("list")
+                    .visible_alias("ls")
+                    .about("List the organization's repos cloned under ~/orgs, without contacting GitHub")
+                    .arg(
+                        Arg::new("organization-name")
+                            .long("org")
+                            .short('o')
+                            .required(false)
+                            .help("The JV Organization Name")
+                    )
+                    .arg(
+                        Arg::new("output")
+                            .long("output")
+                            .help("Output format")
+                            .value_parser(value_parser!(repositories::Output))
+                            .default_value("default")
+                    )
+            )
             .subcommand(
                 Command::new("delete")
                     .hide(true)
@@ -216,14 +657,79 @@ pub fn command() -> Command {
         )
         .subcommand(Command::new("sso")
             .about("Configure access to kubernetes clusters via SSO")
+            .arg(
+                Arg::new("only")
+                    .long("only")
+                    .value_name("ORG")
+                    .help("Only add clusters belonging to this organization to the kubeconfig")
+            )
+            .arg(
+                Arg::new("kubeconfig")
+                    .long("kubeconfig")
+                    .value_name("PATH")
+                    .global(true)
+                    .help("Kubeconfig file to update instead of ~/.kube/config (or KUBECONFIG)")
+            )
+            .arg(
+                Arg::new("refresh-apps")
+                    .long("refresh-apps")
+                    .action(clap::ArgAction::SetTrue)
+                    .global(true)
+                    .help("Fetch the list of clusters again instead of using the last few minutes' cached copy")
+            )
+            .arg(
+                Arg::new("machine-name")
+                    .long("machine-name")
+                    .value_name("NAME")
+                    .global(true)
+                    .help("Name the cluster and context NAME instead of deriving it from the app (one cluster only)")
+            )
+            .arg(
+                Arg::new("strip-suffix")
+                    .long("strip-suffix")
+                    .value_name("TEXT")
+                    .default_value("-auth0")
+                    .global(true)
+                    .help("Suffix removed from app names when naming clusters; pass \"\" to keep names as they are")
+            )
+            .arg(
+                Arg::new("vcluster-selector")
+                    .long("vcluster-selector")
+                    .value_name("LABELS")
+                    .default_value(vcluster::DEFAULT_LABEL_SELECTOR)
+                    .global(true)
+                    .help("Label selector for the secrets holding vcluster kubeconfigs on a host cluster")
+            )
             .subcommand(Command::new("aws")
                 .about("Only configure SSO for AWS")
             )
             .subcommand(Command::new("azure")
                 .about("Only configure SSO for Azure")
+                .arg(
+                    Arg::new("subscription")
+                        .long("subscription")
+                        .value_name("ID|NAME")
+                        .action(clap::ArgAction::Append)
+                        .help("Only add clusters from this subscription (repeatable)")
+                )
             )
             .subcommand(Command::new("auth0")
                 .about("Only configure SSO for Auth0")
+                .arg(
+                    Arg::new("only")
+                        .long("only")
+                        .value_name("ORG")
+                        .help("Only add clusters belonging to this organization to the kubeconfig")
+                )
+            )
+            .subcommand(Command::new("vcluster")
+                .about("Add the vclusters running on a host cluster you already have access to")
+                .arg(
+                    Arg::new("context")
+                        .long("context")
+                        .value_name("NAME")
+                        .help("Kube context of the host cluster to look for vclusters on, instead of the current context")
+                )
             )
         )
         .subcommand(Command::new("login")
@@ -242,6 +748,99 @@ pub fn command() -> Command {
                     .action(clap::ArgAction::SetTrue)
                     .help("Refresh access tokens")
             )
+            .arg(
+                Arg::new("scope")
+                    .long("scope")
+                    .required(false)
+                    .action(clap::ArgAction::Append)
+                    .help("Request an additional scope, e.g. products:write (repeatable)")
+            )
+            .arg(
+                Arg::new("audience")
+                    .long("audience")
+                    .value_name("URL")
+                    .value_parser(parse_audience)
+                    .help("API audience to request tokens for, instead of https://api.p6m.run/v1/")
+            )
+            .arg(
+                Arg::new("print-token")
+                    .long("print-token")
+                    .value_name("TOKEN")
+                    .value_parser(value_parser!(login::PrintToken))
+                    .help("After logging in, print only this token to stdout; other output goes to stderr")
+            )
+        )
+        .subcommand(Command::new("apps")
+            .about("Inspect the applications available to you")
+            .subcommand(Command::new("list")
+                .visible_alias("ls")
+                .about("List the Auth0 applications for your organizations")
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .help("Output format")
+                        .value_parser(value_parser!(apps::Output))
+                        .default_value("default")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("organization-name")
+                        .long("org")
+                        .required(false)
+                        .action(clap::ArgAction::Set)
+                        .help("The JV Organization Name")
+                )
+            )
+        )
+        .subcommand(Command::new("auth")
+            .about("Inspect the current tokens")
+            .subcommand(Command::new("assert")
+                .about("Check the current token has the required claims, exiting non-zero if not")
+                .arg(
+                    Arg::new("organization-name")
+                        .long("org")
+                        .required(false)
+                        .action(clap::ArgAction::Set)
+                        .help("Check the token for this JV Organization, which must be one of yours")
+                )
+                .arg(
+                    Arg::new("role")
+                        .long("role")
+                        .action(clap::ArgAction::Append)
+                        .help("Required role (repeatable); together they must be exactly the token's roles, or * for any")
+                )
+                .arg(
+                    Arg::new("permission")
+                        .long("permission")
+                        .action(clap::ArgAction::Append)
+                        .help("Required permission (repeatable); together they must be exactly the token's permissions, or * for any")
+                )
+                .arg(
+                    Arg::new("token")
+                        .long("token")
+                        .help("Token to check")
+                        .value_parser(value_parser!(auth::assert::AssertToken))
+                        .default_value("id")
+                )
+            )
+        )
+        .subcommand(Command::new("token")
+            .about("Print the current access token, refreshing it if needed")
+            .arg(
+                Arg::new("organization-name")
+                    .long("org")
+                    .required(false)
+                    .action(clap::ArgAction::Set)
+                    .help("The JV Organization Name")
+            )
+            .arg(
+                Arg::new("audience")
+                    .long("audience")
+                    .value_name("URL")
+                    .value_parser(parse_audience)
+                    .help("API audience to request tokens for, instead of https://api.p6m.run/v1/")
+            )
         )
         .subcommand(Command::new("whoami")
             .about("Display information about the currently logged in user")
@@ -268,6 +867,34 @@ pub fn command() -> Command {
                     .action(clap::ArgAction::Set)
                     .help("Use an application ID which contains metadata for the authentication flow (meta.p6m.dev/authn-provider)")
             )
+            .arg(
+                Arg::new("refresh-only")
+                    .long("refresh-only")
+                    .action(clap::ArgAction::SetTrue)
+                    .conflicts_with("output")
+                    .help("Refresh tokens if needed without printing anything")
+            )
+            .arg(
+                Arg::new("all-orgs")
+                    .long("all-orgs")
+                    .action(clap::ArgAction::SetTrue)
+                    .conflicts_with_all(["organization-name", "authn-app-id", "refresh-only"])
+                    .help("Report roles and permissions for every organization you belong to")
+            )
+            .arg(
+                Arg::new("userinfo")
+                    .long("userinfo")
+                    .action(clap::ArgAction::SetTrue)
+                    .conflicts_with_all(["all-orgs", "refresh-only"])
+                    .help("Ask the identity provider's userinfo endpoint about you, instead of reading the token's claims")
+            )
+            .arg(
+                Arg::new("scope")
+                    .long("scope")
+                    .required(false)
+                    .action(clap::ArgAction::Append)
+                    .help("Request an additional scope, e.g. products:write (repeatable)")
+            )
         )
         .arg(
             Arg::new("verbosity")
@@ -277,16 +904,136 @@ pub fn command() -> Command {
                 .action(clap::ArgAction::Count)
                 .global(true),
         )
+        .arg(
+            Arg::new("quiet")
+                .help("Only log warnings and errors")
+                .long("quiet")
+                .short('q')
+                .action(clap::ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("log-format")
+                .long("log-format")
+                .help("Log output format")
+                .value_parser(value_parser!(logging::LogFormat))
+                .default_value("text")
+                .global(true),
+        )
+        .arg(
+            Arg::new("yes")
+                .long("yes")
+                .short('y')
+                .visible_alias("assume-yes")
+                .action(clap::ArgAction::SetTrue)
+                .help("Answer yes to confirmation prompts")
+                .global(true),
+        )
+        .arg(
+            Arg::new("page-size")
+                .long("page-size")
+                .value_name("ROWS")
+                .value_parser(value_parser!(u16).range(1..))
+                .help("Number of options shown at once in interactive selection lists")
+                .global(true),
+        )
+        .arg(
+            Arg::new("offline")
+                .long("offline")
+                .action(clap::ArgAction::SetTrue)
+                .help("Use tokens on disk without contacting the network (also P6M_OFFLINE=1)")
+                .global(true),
+        )
+        .arg(
+            Arg::new("config-dir")
+                .long("config")
+                .value_name("DIR")
+                .help("Config directory to use instead of ~/.p6m (also P6M_CONFIG_DIR)")
+                .global(true),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .value_name("NAME")
+                .help("Named profile from ~/.p6m/profiles.yaml to use (also P6M_PROFILE)")
+                .global(true),
+        )
         .arg(
             Arg::new("development")
                 .long("dev")
                 .action(clap::ArgAction::SetTrue)
                 .hide(true)
-                .help("Use the development environment.")
+                .help("Use the development environment; the same as `--profile dev`.")
+                .conflicts_with("profile")
                 .global(true),
             )
 }
 
+/// The subcommands each command's `execute` dispatches, keyed by the command's path.
+const DISPATCH: &[(&str, &[&str])] = &[
+    (
+        "p6m",
+        &[
+            "apps",
+            "auth",
+            "completions",
+            "context",
+            "doctor",
+            "open",
+            "purge",
+            "repositories",
+            "jwt",
+            "kube",
+            "tilt",
+            "self",
+            "sso",
+            "login",
+            "token",
+            "whoami",
+            "version",
+            "workstation",
+        ],
+    ),
+    ("p6m apps", &["list"]),
+    ("p6m auth", &["assert"]),
+    ("p6m context", &["show", "export"]),
+    ("p6m jwt", &["insecure", "jwks", "decode"]),
+    ("p6m kube", &["current", "use"]),
+    ("p6m open", &["argocd", "artifactory", "github"]),
+    ("p6m purge", &["ide-files", "maven"]),
+    (
+        "p6m repositories",
+        &["pull", "sync", "fetch", "push", "prune", "list", "delete"],
+    ),
+    ("p6m self", &["update"]),
+    ("p6m sso", &["auth0", "aws", "azure", "vcluster"]),
+    ("p6m tilt", &["generate"]),
+    ("p6m workstation", &["check", "setup"]),
+];
+
+/// The subcommand given to `command`, for its `execute` to match on. Subcommands missing from `DISPATCH` are rejected
+/// as invalid, so one declared above without a handler fails here rather than falling through silently.
+pub fn subcommand<'a>(
+    command: &str,
+    matches: &'a ArgMatches,
+) -> Result<Option<(&'a str, &'a ArgMatches)>, anyhow::Error> {
+    let (name, subargs) = match matches.subcommand() {
+        Some(subcommand) => subcommand,
+        None => return Ok(None),
+    };
+    let dispatched = DISPATCH
+        .iter()
+        .find(|(path, _)| *path == command)
+        .is_some_and(|(_, names)| names.contains(&name));
+    if dispatched {
+        Ok(Some((name, subargs)))
+    } else {
+        Err(exit_code::usage(format!(
+            "Invalid command: {command} {name}"
+        )))
+    }
+}
+
 #[derive(clap::ValueEnum, Clone, Debug, PartialEq)]
 pub enum Environment {
     Dev,
@@ -302,6 +1049,12 @@ pub struct P6mEnvironment {
     pub kube_dir: Utf8PathBuf,
     pub auth_dir: Utf8PathBuf,
 
+    /// Avoid network calls where possible (`--offline` or `P6M_OFFLINE`)
+    pub offline: bool,
+
+    /// The named profile (`--profile` or `P6M_PROFILE`); `None` is the default production profile
+    pub profile: Option<String>,
+
     // Auth0
     pub auth_n: AuthN,
 }
@@ -309,49 +1062,66 @@ pub struct P6mEnvironment {
 impl P6mEnvironment {
     pub fn init(matches: &ArgMatches) -> Result<Self, anyhow::Error> {
         let dev = matches.get_one::<bool>("development").cloned().unwrap();
+        let offline = matches.get_flag("offline")
+            || std::env::var("P6M_OFFLINE")
+                .map(|value| !matches!(value.as_str(), "" | "0" | "false"))
+                .unwrap_or(false);
+
+        let profile = match dev {
+            true => Some(DEV_PROFILE.to_owned()),
+            false => matches
+                .get_one::<String>("profile")
+                .cloned()
+                .or_else(|| std::env::var("P6M_PROFILE").ok()),
+        }
+        .filter(|profile| !profile.is_empty() && profile != DEFAULT_PROFILE);
+        if let Some(profile) = profile.as_deref() {
+            check_profile_name(profile)?;
+        }
 
         let home_dir = dirs::home_dir()
             .map(Utf8PathBuf::from_path_buf)
             .expect("Valid Home Directory Path")
             .expect("Utf8 Home Directory");
 
-        let config_dir = match dev {
-            true => home_dir.join(".p6m-dev"),
-            false => home_dir.join(".p6m"),
-        };
+        let config_override = matches
+            .get_one::<String>("config-dir")
+            .cloned()
+            .or_else(|| std::env::var("P6M_CONFIG_DIR").ok())
+            .filter(|config_dir| !config_dir.is_empty())
+            .map(Utf8PathBuf::from);
+
+        let profiles_path = config_override
+            .clone()
+            .unwrap_or_else(|| default_config_dir(&home_dir, None))
+            .join(PROFILES_FILE);
 
-        let auth_n = AuthN {
-            client_id: Some("j4jEhWwe2od1eacxuocy0sfmbf7V4H8V".into()),
-            discovery_uri: Some("https://auth.p6m.run/.well-known/openid-configuration".into()),
-            params: Some(
-                vec![("audience".into(), "https://api.p6m.run/v1/".into())]
-                    .into_iter()
-                    .collect(),
-            ),
-            apps_uri: Some("https://auth.p6m.dev/api".into()),
-            scopes: None,
-            token_preference: Some(AuthToken::Id),
+        let config_dir = match config_override {
+            Some(config_dir) => config_dir,
+            None => default_config_dir(&home_dir, profile.as_deref()),
         };
 
-        let environment = match dev {
-            true => {
-                println!("Using development environment");
-                let mut auth_n = auth_n.clone();
-                auth_n.apps_uri = Some("https://auth-dev.p6m.dev/api".into());
-                auth_n.scopes = Some(vec!["urn:auth:dev:true".into()]);
-                Self {
-                    config_dir: config_dir.clone(),
-                    kube_dir: home_dir.join(".kube"),
-                    auth_dir: config_dir.join("auth"),
-                    auth_n,
-                }
-            }
-            false => Self {
-                config_dir: config_dir.clone(),
-                kube_dir: home_dir.join(".kube"),
-                auth_dir: config_dir.join("auth"),
-                auth_n,
-            },
+        let auth_n = profile_auth_n(profile.as_deref(), &profiles_path)?;
+
+        let kube_dir = kubeconfig::path()
+            .parent()
+            .map(|path| path.to_path_buf())
+            .and_then(|path| Utf8PathBuf::from_path_buf(path).ok())
+            .unwrap_or_else(|| home_dir.join(".kube"));
+
+        match profile.as_deref() {
+            Some(DEV_PROFILE) => println!("Using development environment"),
+            Some(profile) => debug!("Using profile {profile} from {config_dir}"),
+            None => {}
+        }
+
+        let environment = Self {
+            auth_dir: config_dir.join("auth"),
+            config_dir,
+            kube_dir,
+            offline,
+            profile,
+            auth_n,
         };
 
         // Ensure this directory exist on behalf of all consumers
@@ -368,3 +1138,383 @@ impl P6mEnvironment {
         self.kube_dir.as_path()
     }
 }
+
+const DEFAULT_PROFILE: &str = "default";
+pub const DEV_PROFILE: &str = "dev";
+const PROFILES_FILE: &str = "profiles.yaml";
+
+/// Profile names become a directory under `~/.p6m/profiles`, so they can't be paths of their own.
+fn check_profile_name(profile: &str) -> Result<(), anyhow::Error> {
+    if profile.contains(['/', '\\']) || profile.contains("..") {
+        return Err(anyhow::Error::msg(format!(
+            "Invalid profile name '{profile}': it can't contain path separators or '..'"
+        )));
+    }
+    Ok(())
+}
+
+/// `~/.p6m` for the default profile, `~/.p6m-dev` for the development environment, and
+/// `~/.p6m/profiles/<name>` for anything else, so tokens from one profile never replace another's.
+fn default_config_dir(home_dir: &Utf8Path, profile: Option<&str>) -> Utf8PathBuf {
+    match profile {
+        None => home_dir.join(".p6m"),
+        Some(DEV_PROFILE) => home_dir.join(".p6m-dev"),
+        Some(profile) => home_dir.join(".p6m").join("profiles").join(profile),
+    }
+}
+
+/// Audiences are API identifiers, which Auth0 requires to be http(s) URLs.
+fn parse_audience(value: &str) -> Result<String, String> {
+    match url::Url::parse(value) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(value.to_string()),
+        Ok(url) => Err(format!(
+            "expected an http(s) URL, not a {} URL",
+            url.scheme()
+        )),
+        Err(err) => Err(format!("not a URL: {err}")),
+    }
+}
+
+fn production_auth_n() -> AuthN {
+    AuthN {
+        client_id: Some("j4jEhWwe2od1eacxuocy0sfmbf7V4H8V".into()),
+        discovery_uri: Some("https://auth.p6m.run/.well-known/openid-configuration".into()),
+        params: Some(
+            vec![("audience".into(), "https://api.p6m.run/v1/".into())]
+                .into_iter()
+                .collect(),
+        ),
+        apps_uri: Some("https://auth.p6m.dev/api".into()),
+        scopes: None,
+        token_preference: Some(AuthToken::Id),
+    }
+}
+
+/// Resolves a profile's `AuthN`, starting from the built in production (or dev) settings and
+/// applying any fields set for the profile in `profiles.yaml`.
+fn profile_auth_n(profile: Option<&str>, profiles_path: &Utf8Path) -> Result<AuthN, anyhow::Error> {
+    let mut auth_n = production_auth_n();
+    if profile == Some(DEV_PROFILE) {
+        auth_n.apps_uri = Some("https://auth-dev.p6m.dev/api".into());
+        auth_n.scopes = Some(vec!["urn:auth:dev:true".into()]);
+    }
+
+    let Some(profile) = profile else {
+        return Ok(auth_n);
+    };
+
+    let mut profiles: BTreeMap<String, AuthN> = match std::fs::read_to_string(profiles_path) {
+        Ok(contents) => serde_yaml::from_str(&contents)
+            .with_context(|| format!("Unable to parse {profiles_path}"))?,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(err) => {
+            return Err(anyhow::Error::new(err).context(format!("Unable to read {profiles_path}")))
+        }
+    };
+
+    match profiles.remove(profile) {
+        Some(overrides) => Ok(AuthN {
+            client_id: overrides.client_id.or(auth_n.client_id),
+            discovery_uri: overrides.discovery_uri.or(auth_n.discovery_uri),
+            token_preference: overrides.token_preference.or(auth_n.token_preference),
+            params: overrides.params.or(auth_n.params),
+            apps_uri: overrides.apps_uri.or(auth_n.apps_uri),
+            scopes: overrides.scopes.or(auth_n.scopes),
+        }),
+        None if profile == DEV_PROFILE => Ok(auth_n),
+        None => Err(anyhow::Error::msg(format!(
+            "Unknown profile '{profile}'. Add it to {profiles_path}"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn init(args: &[&str]) -> (TempDir, P6mEnvironment) {
+        let root = TempDir::new("cli-test");
+        let config_dir = root.join("config");
+
+        let matches = command()
+            .try_get_matches_from(
+                ["p6m", "--config", config_dir.as_str()]
+                    .iter()
+                    .chain(args)
+                    .chain(&["whoami"]),
+            )
+            .unwrap();
+
+        (root, P6mEnvironment::init(&matches).unwrap())
+    }
+
+    /// Args that let `leaf` parse, with placeholders for the ones it requires.
+    fn leaf_args(leaf: &Command) -> Vec<String> {
+        let mut required: Vec<&Arg> = leaf
+            .get_arguments()
+            .filter(|arg| arg.is_required_set())
+            .collect();
+        if required.is_empty() && leaf.is_arg_required_else_help_set() {
+            required.extend(leaf.get_positionals().next());
+        }
+
+        let mut args = vec![];
+        for arg in required {
+            let value = arg
+                .get_possible_values()
+                .first()
+                .map(|value| value.get_name().to_string())
+                .unwrap_or_else(|| "1".to_string());
+            match arg.get_long() {
+                Some(long) => args.push(format!("--{}", long)),
+                None => assert!(arg.is_positional(), "`{}` has no flag", arg.get_id()),
+            }
+            args.push(value);
+        }
+        args
+    }
+
+    /// Parses every leaf command below `path` and checks each level of the matches is one `subcommand` dispatches.
+    fn assert_dispatched(parent: &Command, path: &mut Vec<String>) {
+        if !parent.has_subcommands() {
+            let argv: Vec<String> = path.iter().cloned().chain(leaf_args(parent)).collect();
+            let matches = command()
+                .try_get_matches_from(&argv)
+                .unwrap_or_else(|err| panic!("`{}` doesn't parse: {}", argv.join(" "), err));
+
+            let mut command_path = "p6m".to_string();
+            let mut matches = &matches;
+            while let Some((name, _)) = matches.subcommand() {
+                let (_, subargs) = subcommand(&command_path, matches)
+                    .unwrap_or_else(|err| panic!("`{}` isn't dispatched: {}", argv.join(" "), err))
+                    .unwrap();
+                command_path = format!("{} {}", command_path, name);
+                matches = subargs;
+            }
+            return;
+        }
+
+        for child in parent.get_subcommands() {
+            path.push(child.get_name().to_string());
+            assert_dispatched(child, path);
+            path.pop();
+        }
+    }
+
+    #[test]
+    fn test_command() {
+        command().debug_assert();
+    }
+
+    #[test]
+    fn test_every_subcommand_is_dispatched() {
+        assert_dispatched(&command(), &mut vec!["p6m".to_string()]);
+    }
+
+    #[test]
+    fn test_undispatched_subcommand() {
+        let matches = Command::new("p6m")
+            .subcommand(Command::new("nope"))
+            .try_get_matches_from(["p6m", "nope"])
+            .unwrap();
+        assert_eq!(
+            subcommand("p6m", &matches).unwrap_err().to_string(),
+            "Invalid command: p6m nope"
+        );
+    }
+
+    /// Args the handlers read with `expect`, so they must stay required or defaulted.
+    #[test]
+    fn test_handler_args() {
+        let matches = command()
+            .try_get_matches_from(["p6m", "jwt", "insecure"])
+            .unwrap();
+        let (_, jwt) = matches.subcommand().unwrap();
+        let (_, insecure) = jwt.subcommand().unwrap();
+        assert_eq!(insecure.get_one::<u32>("expire-days"), Some(&1));
+
+        let missing = command()
+            .try_get_matches_from(["p6m", "kube", "use"])
+            .unwrap_err();
+        assert_eq!(
+            missing.kind(),
+            clap::error::ErrorKind::MissingRequiredArgument
+        );
+
+        let matches = command()
+            .try_get_matches_from(["p6m", "repositories", "pull", "--org", "acme", "--all"])
+            .unwrap();
+        let (_, repositories) = matches.subcommand().unwrap();
+        let (name, pull) = repositories.subcommand().unwrap();
+        assert_eq!(name, "pull");
+        assert_eq!(
+            pull.get_one::<String>("organization-name")
+                .map(String::as_str),
+            Some("acme")
+        );
+        assert!(pull.get_flag("all"));
+    }
+
+    #[test]
+    fn test_repositories_pull_force_requires_existing() {
+        let pull = |args: &[&str]| {
+            command().try_get_matches_from(
+                ["p6m", "repositories", "pull", "--org", "acme"]
+                    .iter()
+                    .chain(args),
+            )
+        };
+
+        assert_eq!(
+            pull(&["--force"]).unwrap_err().kind(),
+            clap::error::ErrorKind::MissingRequiredArgument
+        );
+        assert!(pull(&["--force", "--all"]).is_ok());
+        assert!(pull(&["--force", "--default-branch-only"]).is_ok());
+        assert!(pull(&["--all", "--default-branch-only"]).is_ok());
+    }
+
+    #[test]
+    fn test_init_creates_config_dir() {
+        let (root, environment) = init(&[]);
+
+        assert_eq!(environment.config_dir(), root.join("config"));
+        assert!(environment.config_dir().is_dir());
+        assert_eq!(environment.auth_dir, root.join("config").join("auth"));
+        assert_eq!(
+            environment.kube_dir().as_std_path(),
+            kubeconfig::path().parent().unwrap()
+        );
+        assert_eq!(
+            environment.auth_n.apps_uri.as_deref(),
+            Some("https://auth.p6m.dev/api")
+        );
+        assert_eq!(environment.auth_n.scopes, None);
+    }
+
+    #[test]
+    fn test_init_dev() {
+        let (root, environment) = init(&["--dev"]);
+
+        assert_eq!(environment.config_dir(), root.join("config"));
+        assert_eq!(
+            environment.auth_n.apps_uri.as_deref(),
+            Some("https://auth-dev.p6m.dev/api")
+        );
+        assert_eq!(
+            environment.auth_n.scopes,
+            Some(vec!["urn:auth:dev:true".to_string()])
+        );
+        assert_eq!(
+            environment.auth_n.client_id.as_deref(),
+            Some("j4jEhWwe2od1eacxuocy0sfmbf7V4H8V")
+        );
+    }
+
+    #[test]
+    fn test_default_config_dir() {
+        let home_dir = Utf8Path::new("/home/dev");
+
+        assert_eq!(default_config_dir(home_dir, None), "/home/dev/.p6m");
+        assert_eq!(
+            default_config_dir(home_dir, Some("dev")),
+            "/home/dev/.p6m-dev"
+        );
+        assert_eq!(
+            default_config_dir(home_dir, Some("staging")),
+            "/home/dev/.p6m/profiles/staging"
+        );
+    }
+
+    #[test]
+    fn test_profile_auth_n() {
+        let (root, _) = init(&[]);
+        let profiles_path = root.join("config").join(PROFILES_FILE);
+        std::fs::write(
+            &profiles_path,
+            "staging:\n  clientId: staging-client\n  appsUri: https://auth-staging.example.com/api\n",
+        )
+        .unwrap();
+
+        let staging = profile_auth_n(Some("staging"), &profiles_path).unwrap();
+        assert_eq!(staging.client_id.as_deref(), Some("staging-client"));
+        assert_eq!(
+            staging.apps_uri.as_deref(),
+            Some("https://auth-staging.example.com/api")
+        );
+        assert_eq!(staging.discovery_uri, production_auth_n().discovery_uri);
+
+        let dev = profile_auth_n(Some("dev"), &profiles_path).unwrap();
+        assert_eq!(
+            dev.apps_uri.as_deref(),
+            Some("https://auth-dev.p6m.dev/api")
+        );
+
+        assert!(profile_auth_n(Some("unknown"), &profiles_path).is_err());
+    }
+
+    #[test]
+    fn test_check_profile_name() {
+        assert!(check_profile_name("staging").is_ok());
+        assert!(check_profile_name("dev.eu-1").is_ok());
+        assert!(check_profile_name("../x").is_err());
+        assert!(check_profile_name("..").is_err());
+        assert!(check_profile_name("a/b").is_err());
+        assert!(check_profile_name("a\\b").is_err());
+    }
+
+    #[test]
+    fn test_init_profile() {
+        let (root, environment) = init(&["--profile", "dev"]);
+        assert_eq!(environment.profile.as_deref(), Some("dev"));
+        drop(root);
+
+        let (_root, environment) = init(&["--profile", "default"]);
+        assert_eq!(environment.profile, None);
+
+        assert!(command()
+            .try_get_matches_from(["p6m", "--dev", "--profile", "staging", "whoami"])
+            .is_err());
+    }
+
+    #[test]
+    fn test_workstation_check_exclude() {
+        let matches = command()
+            .try_get_matches_from(["p6m", "workstation", "check", "--exclude", "java"])
+            .unwrap();
+        let (_, workstation) = matches.subcommand().unwrap();
+        let (_, check) = workstation.subcommand().unwrap();
+        assert_eq!(
+            check.get_one::<Ecosystem>("exclude"),
+            Some(&Ecosystem::Java)
+        );
+
+        assert!(command()
+            .try_get_matches_from(["p6m", "workstation", "check", "--exclude", "all"])
+            .is_err());
+    }
+
+    #[test]
+    fn test_sso_only_is_limited_to_auth0() {
+        assert!(command()
+            .try_get_matches_from(["p6m", "sso", "--only", "acme"])
+            .is_ok());
+        assert!(command()
+            .try_get_matches_from(["p6m", "sso", "auth0", "--only", "acme"])
+            .is_ok());
+        assert!(command()
+            .try_get_matches_from(["p6m", "sso", "aws", "--only", "acme"])
+            .is_err());
+        assert!(command()
+            .try_get_matches_from(["p6m", "sso", "azure", "--only", "acme"])
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_audience() {
+        assert!(parse_audience("https://api.example.com/v1/").is_ok());
+        assert!(parse_audience("urn:example:api").is_err());
+        assert!(parse_audience("api.example.com").is_err());
+    }
+}
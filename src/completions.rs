@@ -4,9 +4,13 @@ use anyhow::Error;
 use clap::ArgMatches;
 use clap_complete::{generate, Shell};
 
-use crate::cli;
+use crate::{auth::TokenRepository, cli, cli::P6mEnvironment};
+
+pub fn execute(environment: P6mEnvironment, matches: &ArgMatches) -> Result<(), Error> {
+    if matches.get_flag("orgs") {
+        return print_organizations(&environment);
+    }
 
-pub fn execute(matches: &ArgMatches) -> Result<(), Error> {
     if let Some(generator) = matches.get_one::<Shell>("generator") {
         let mut cmd = cli::command();
         eprintln!("Generating completion file for {generator}...");
@@ -17,3 +21,18 @@ pub fn execute(matches: &ArgMatches) -> Result<(), Error> {
 
     Ok(())
 }
+
+/// Prints organization names from the ID token claims, for use by dynamic shell completions.
+///
+/// Completions must never be noisy, so a missing or unreadable token simply prints nothing.
+fn print_organizations(environment: &P6mEnvironment) -> Result<(), Error> {
+    let organizations = TokenRepository::new(&environment.auth_n, &environment.auth_dir)?
+        .organizations()
+        .unwrap_or_default();
+
+    for name in organizations.values() {
+        println!("{name}");
+    }
+
+    Ok(())
+}
@@ -1,22 +1,34 @@
-#[cfg(target_os = "windows")]
-use std::path::PathBuf;
-
-use crate::models::{
-    artifact::StorageProvider,
-    git::{GithubLevel, Organization},
+use crate::{
+    auth::TokenRepository,
+    cli::{self, P6mEnvironment},
+    models::{
+        artifact::StorageProvider,
+        git::{GithubLevel, Organization},
+    },
 };
-use anyhow::Error;
+use anyhow::{Context, Error};
 use base64::{engine, Engine};
-use clap::ArgMatches;
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::{ArgMatches, ValueEnum};
+use log::{debug, warn};
 use minijinja::render;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 use tokio::fs;
 
 macro_rules! read_env_var_only_if {
-    ($active_storage:expr, $storage_provider:expr, $env_var_name:literal) => {
+    ($credentials:expr, $active_storage:expr, $storage_provider:expr, $env_var_name:literal) => {
         if $active_storage == &$storage_provider {
-            std::env::var($env_var_name).map_err(|_| {
-                Error::msg($env_var_name.to_owned() + " environment variable must be set.")
-            })?
+            std::env::var($env_var_name)
+                .ok()
+                .or_else(|| $credentials.values.get($env_var_name).cloned())
+                .ok_or_else(|| {
+                    Error::msg(format!(
+                        "{} must be set as an environment variable or in {}.",
+                        $env_var_name, $credentials.path
+                    ))
+                })?
         } else {
             "".to_owned()
         }
@@ -36,50 +48,643 @@ macro_rules! new_file_with_content {
     };
 }
 
-pub async fn execute(matches: &ArgMatches) -> Result<(), Error> {
+/// The `KEY=VALUE` pairs from the credentials file, and where they were read from.
+struct Credentials {
+    path: Utf8PathBuf,
+    values: BTreeMap<String, String>,
+}
+
+/// Reads `KEY=VALUE` lines from the credentials file in the config dir, if it exists.
+///
+/// Blank lines and `#` comments are ignored, as are an `export ` prefix and quotes around the value.
+async fn load_credentials(config_dir: &Utf8Path) -> Result<Credentials, Error> {
+    let path = config_dir.join("credentials");
+    let values = match fs::read_to_string(&path).await {
+        Ok(contents) => parse_credentials(&contents),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(err) => return Err(Error::new(err).context(format!("Unable to read {path}"))),
+    };
+    Ok(Credentials { path, values })
+}
+
+fn parse_credentials(contents: &str) -> BTreeMap<String, String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| {
+            let key = key.trim();
+            let key = key.strip_prefix("export ").unwrap_or(key).trim();
+            let value = value.trim();
+            let value = ['"', '\'']
+                .iter()
+                .find_map(|quote| {
+                    value
+                        .strip_prefix(*quote)
+                        .and_then(|value| value.strip_suffix(*quote))
+                })
+                .unwrap_or(value);
+            (key.to_string(), value.to_string())
+        })
+        .collect()
+}
+
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq)]
+pub enum Output {
+    Default,
+    Json,
+}
+
+/// How `context export` prints variables.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    /// `export KEY='value'` lines, for `eval "$(p6m context export)"`
+    Shell,
+    /// `KEY=value` lines, for `.env` files and CI variable imports
+    Dotenv,
+}
+
+/// The registry credentials for the active provider, from the environment or the credentials file.
+/// The other provider's fields are left empty.
+struct RegistryCredentials {
+    artifactory_username: String,
+    artifactory_identity_token: String,
+    cloudsmith_username: String,
+    cloudsmith_api_key: String,
+}
+
+impl RegistryCredentials {
+    fn read(credentials: &Credentials, active_storage: &StorageProvider) -> Result<Self, Error> {
+        Ok(Self {
+            artifactory_username: read_env_var_only_if!(
+                credentials,
+                active_storage,
+                StorageProvider::Artifactory,
+                "ARTIFACTORY_USERNAME"
+            ),
+            artifactory_identity_token: read_env_var_only_if!(
+                credentials,
+                active_storage,
+                StorageProvider::Artifactory,
+                "ARTIFACTORY_IDENTITY_TOKEN"
+            ),
+            cloudsmith_username: read_env_var_only_if!(
+                credentials,
+                active_storage,
+                StorageProvider::Cloudsmith,
+                "CLOUDSMITH_USERNAME"
+            ),
+            cloudsmith_api_key: read_env_var_only_if!(
+                credentials,
+                active_storage,
+                StorageProvider::Cloudsmith,
+                "CLOUDSMITH_API_KEY"
+            ),
+        })
+    }
+
+    fn username(&self, active_storage: &StorageProvider) -> &str {
+        match active_storage {
+            StorageProvider::Artifactory => &self.artifactory_username,
+            StorageProvider::Cloudsmith => &self.cloudsmith_username,
+        }
+    }
+
+    fn password(&self, active_storage: &StorageProvider) -> &str {
+        match active_storage {
+            StorageProvider::Artifactory => &self.artifactory_identity_token,
+            StorageProvider::Cloudsmith => &self.cloudsmith_api_key,
+        }
+    }
+}
+
+/// An organization's package registries on a storage provider.
+struct Registries {
+    /// The organization's npm registry, without a scheme (`.npmrc` auth lines are keyed by `//<url>`)
+    npm: String,
+    /// The registry for `@p6m` packages, without a scheme
+    npm_platform: &'static str,
+    maven: String,
+    pypi: String,
+}
+
+impl Registries {
+    fn new(organization_name: &str, active_storage: &StorageProvider) -> Self {
+        match active_storage {
+            StorageProvider::Artifactory => Self {
+                npm: format!(
+                    "p6m.jfrog.io/artifactory/api/npm/{}-npm/",
+                    organization_name
+                ),
+                npm_platform: "p6m.jfrog.io/artifactory/api/npm/p6m-dev-npm/",
+                maven: format!(
+                    "https://p6m.jfrog.io/artifactory/{}-libs-release",
+                    organization_name
+                ),
+                pypi: format!(
+                    "https://p6m.jfrog.io/artifactory/api/pypi/{}-pypi/",
+                    organization_name
+                ),
+            },
+            StorageProvider::Cloudsmith => Self {
+                npm: format!("npm.cloudsmith.io/p6m-dev/{}/", organization_name),
+                npm_platform: "npm.cloudsmith.io/p6m-dev/p6m-run/",
+                maven: format!("https://maven.cloudsmith.io/p6m-dev/{}/", organization_name),
+                pypi: format!(
+                    "https://python.cloudsmith.io/p6m-dev/{}/",
+                    organization_name
+                ),
+            },
+        }
+    }
+}
+
+/// The organization and provider last activated with `p6m context`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextState {
+    pub organization: String,
+    pub provider: StorageProvider,
+}
+
+impl ContextState {
+    fn path(config_dir: &Utf8Path) -> Utf8PathBuf {
+        config_dir.join("context.json")
+    }
+
+    /// Returns [None] if no context has been set yet.
+    pub fn read(config_dir: &Utf8Path) -> Result<Option<Self>, Error> {
+        let path = Self::path(config_dir);
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => Ok(Some(
+                serde_json::from_str(&contents)
+                    .with_context(|| format!("Unable to parse {path}"))?,
+            )),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(Error::new(err).context(format!("Unable to read {path}"))),
+        }
+    }
+
+    async fn write(&self, config_dir: &Utf8Path) -> Result<(), Error> {
+        fs::write(Self::path(config_dir), serde_json::to_string_pretty(self)?).await?;
+        Ok(())
+    }
+}
+
+/// Resolves the organization from `--org`, then the current directory, then the last
+/// context set with `p6m context`.
+pub fn resolve_organization(
+    environment: &P6mEnvironment,
+    organization_name: Option<&String>,
+) -> Result<GithubLevel, Error> {
+    GithubLevel::with_organization(organization_name).or_else(|err| {
+        match ContextState::read(environment.config_dir()).ok().flatten() {
+            Some(state) => {
+                debug!(
+                    "Using organization {} from the active context",
+                    state.organization
+                );
+                Ok(GithubLevel::Organization(Organization::new(
+                    state.organization,
+                )))
+            }
+            None => Err(err),
+        }
+    })
+}
+
+pub async fn execute(environment: P6mEnvironment, matches: &ArgMatches) -> Result<(), Error> {
+    match cli::subcommand("p6m context", matches)? {
+        Some(("show", subargs)) => show(environment, subargs),
+        Some(("export", subargs)) => export(environment, subargs).await,
+        Some((command, _)) => Err(Error::msg(format!(
+            "Unimplemented context command: '{}'",
+            command
+        ))),
+        None => set(environment, matches).await,
+    }
+}
+
+fn show(environment: P6mEnvironment, matches: &ArgMatches) -> Result<(), Error> {
+    let state = ContextState::read(environment.config_dir())?
+        .context("No context has been set. Run `p6m context --org <org>`")?;
+
+    match matches.get_one::<Output>("output") {
+        Some(Output::Json) => println!("{}", serde_json::to_string_pretty(&state)?),
+        None | Some(Output::Default) => {
+            println!("Organization: {}", state.organization);
+            println!(
+                "Provider: {}",
+                state
+                    .provider
+                    .to_possible_value()
+                    .map(|value| value.get_name().to_string())
+                    .unwrap_or_default()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the registry URLs and credentials `p6m context` would write, as environment variables.
+async fn export(environment: P6mEnvironment, matches: &ArgMatches) -> Result<(), Error> {
+    let credentials = load_credentials(environment.config_dir()).await?;
+    let organization =
+        resolve_organization(&environment, matches.get_one::<String>("organization-name"))?
+            .organization()
+            .unwrap();
+
+    // Without --provider, keep the provider of the active context when it's for the same organization
+    let provider = match matches.get_one::<StorageProvider>("provider") {
+        Some(provider) => provider.clone(),
+        None => ContextState::read(environment.config_dir())
+            .ok()
+            .flatten()
+            .filter(|state| state.organization == organization.name())
+            .map(|state| state.provider)
+            .unwrap_or_default(),
+    };
+
+    let format = matches
+        .get_one::<ExportFormat>("format")
+        .copied()
+        .unwrap_or(ExportFormat::Shell);
+    for (key, value) in export_variables(organization.name(), &provider, &credentials)? {
+        println!("{}", format_variable(format, &key, &value));
+    }
+    Ok(())
+}
+
+fn export_variables(
+    organization_name: &str,
+    active_storage: &StorageProvider,
+    credentials: &Credentials,
+) -> Result<Vec<(String, String)>, Error> {
+    let registry_credentials = RegistryCredentials::read(credentials, active_storage)?;
+    let registries = Registries::new(organization_name, active_storage);
+    let provider = active_storage
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default();
+    // Poetry and Cargo read per-repository settings from variables named after the repository
+    let poetry_repository = organization_name.replace('-', "_").to_uppercase() + "_PYPI";
+    let username = registry_credentials.username(active_storage).to_string();
+    let password = registry_credentials.password(active_storage).to_string();
+
+    let mut variables = vec![
+        (
+            "P6M_ORGANIZATION".to_string(),
+            organization_name.to_string(),
+        ),
+        ("P6M_STORAGE_PROVIDER".to_string(), provider),
+        ("P6M_MAVEN_URL".to_string(), registries.maven),
+        (
+            "NPM_CONFIG_REGISTRY".to_string(),
+            format!("https://{}", registries.npm),
+        ),
+        (
+            format!("POETRY_REPOSITORIES_{poetry_repository}_URL"),
+            registries.pypi,
+        ),
+        (
+            format!("POETRY_HTTP_BASIC_{poetry_repository}_USERNAME"),
+            username.clone(),
+        ),
+        (
+            format!("POETRY_HTTP_BASIC_{poetry_repository}_PASSWORD"),
+            password.clone(),
+        ),
+    ];
+    match active_storage {
+        StorageProvider::Artifactory => {
+            variables.push(("ARTIFACTORY_USERNAME".to_string(), username));
+            variables.push(("ARTIFACTORY_IDENTITY_TOKEN".to_string(), password.clone()));
+            variables.push((
+                format!(
+                    "CARGO_REGISTRIES_{}_TOKEN",
+                    organization_name.replace('-', "_").to_uppercase()
+                ),
+                format!("Bearer {password}"),
+            ));
+        }
+        StorageProvider::Cloudsmith => {
+            variables.push(("CLOUDSMITH_USERNAME".to_string(), username));
+            variables.push(("CLOUDSMITH_API_KEY".to_string(), password));
+        }
+    }
+    Ok(variables)
+}
+
+fn format_variable(format: ExportFormat, key: &str, value: &str) -> String {
+    match format {
+        ExportFormat::Shell => format!("export {key}='{}'", value.replace('\'', "'\\''")),
+        ExportFormat::Dotenv
+            if value.contains(|c: char| c.is_whitespace() || "#\"'".contains(c)) =>
+        {
+            format!(
+                "{key}=\"{}\"",
+                value.replace('\\', "\\\\").replace('"', "\\\"")
+            )
+        }
+        ExportFormat::Dotenv => format!("{key}={value}"),
+    }
+}
+
+/// A lightweight authenticated request that succeeds only if a registry accepts the credentials.
+#[derive(Debug, PartialEq)]
+struct RegistryCheck {
+    ecosystem: &'static str,
+    method: reqwest::Method,
+    url: String,
+    authorization: String,
+}
+
+fn registry_checks(
+    organization_name: &str,
+    active_storage: &StorageProvider,
+    registry_credentials: &RegistryCredentials,
+) -> Vec<RegistryCheck> {
+    let registries = Registries::new(organization_name, active_storage);
+    let basic = format!(
+        "Basic {}",
+        engine::general_purpose::STANDARD.encode(format!(
+            "{}:{}",
+            registry_credentials.username(active_storage),
+            registry_credentials.password(active_storage)
+        ))
+    );
+    // Cloudsmith's npm registry only takes the API key as a bearer token, like `.npmrc` does
+    let npm_authorization = match active_storage {
+        StorageProvider::Artifactory => basic.clone(),
+        StorageProvider::Cloudsmith => {
+            format!("Bearer {}", registry_credentials.password(active_storage))
+        }
+    };
+
+    vec![
+        RegistryCheck {
+            ecosystem: "npm",
+            method: reqwest::Method::GET,
+            url: format!("https://{}-/whoami", registries.npm),
+            authorization: npm_authorization,
+        },
+        RegistryCheck {
+            ecosystem: "maven",
+            method: reqwest::Method::HEAD,
+            url: registries.maven,
+            authorization: basic.clone(),
+        },
+        RegistryCheck {
+            ecosystem: "python",
+            method: reqwest::Method::HEAD,
+            url: registries.pypi,
+            authorization: basic,
+        },
+    ]
+}
+
+/// Makes each [RegistryCheck] and prints whether it passed, failing if any didn't.
+async fn verify_registries(
+    organization_name: &str,
+    active_storage: &StorageProvider,
+    credentials: &Credentials,
+) -> Result<(), Error> {
+    let registry_credentials = RegistryCredentials::read(credentials, active_storage)?;
+    let client = reqwest::Client::builder()
+        .user_agent(format!("p6m-cli/{}", env!("CARGO_PKG_VERSION")))
+        .timeout(std::time::Duration::from_secs(15))
+        .build()?;
+
+    let mut failed = vec![];
+    for check in registry_checks(organization_name, active_storage, &registry_credentials) {
+        debug!(
+            "Verifying {} with {} {}",
+            check.ecosystem, check.method, check.url
+        );
+        let result = client
+            .request(check.method, &check.url)
+            .header(reqwest::header::AUTHORIZATION, &check.authorization)
+            .send()
+            .await;
+        match result {
+            Ok(response) if response.status().is_success() => {
+                println!("{organization_name} {}: ok", check.ecosystem)
+            }
+            Ok(response) => {
+                println!(
+                    "{organization_name} {}: failed ({} from {})",
+                    check.ecosystem,
+                    response.status(),
+                    check.url
+                );
+                failed.push(check.ecosystem);
+            }
+            Err(err) => {
+                println!("{organization_name} {}: failed ({err})", check.ecosystem);
+                failed.push(check.ecosystem);
+            }
+        }
+    }
+
+    match failed.is_empty() {
+        true => Ok(()),
+        false => Err(Error::msg(format!(
+            "{organization_name}'s {} registry credentials didn't work; check them in {} or your environment",
+            failed.join(", "),
+            credentials.path
+        ))),
+    }
+}
+
+async fn set(environment: P6mEnvironment, matches: &ArgMatches) -> Result<(), Error> {
+    let credentials = load_credentials(environment.config_dir()).await?;
+    if matches.get_flag("all") {
+        return set_all(&environment, matches, &credentials).await;
+    }
+
     let organization =
-        GithubLevel::with_organization(matches.get_one::<String>("organization-name"))?
+        resolve_organization(&environment, matches.get_one::<String>("organization-name"))?
             .organization()
             .unwrap();
     let provider = matches
         .get_one::<StorageProvider>("provider")
         .cloned()
         .unwrap_or_default();
-    set_context(&organization, &provider).await
+    let state = ContextState {
+        organization: organization.name().to_owned(),
+        provider,
+    };
+
+    // The previous context's credentials are about to be replaced, so make the switch obvious
+    let previous = ContextState::read(environment.config_dir()).unwrap_or_else(|err| {
+        debug!("Ignoring unreadable context state: {err:#}");
+        None
+    });
+    if let Some(message) = switch_message(previous.as_ref(), &state) {
+        warn!("{message}");
+    }
+
+    set_context(
+        &organization,
+        &state.provider,
+        &credentials,
+        &ContextTargets::global()?,
+    )
+    .await?;
+
+    state.write(environment.config_dir()).await?;
+
+    if matches.get_flag("verify") {
+        verify_registries(organization.name(), &state.provider, &credentials).await?;
+    }
+    Ok(())
+}
+
+/// Describes a change of organization or provider from the previous context, if there was one.
+fn switch_message(previous: Option<&ContextState>, next: &ContextState) -> Option<String> {
+    let provider = |state: &ContextState| {
+        state
+            .provider
+            .to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default()
+    };
+
+    match previous {
+        Some(previous) if previous.organization != next.organization => Some(format!(
+            "Switching context from {} to {}; your Maven, npm, Poetry and Cargo credentials will now use {}",
+            previous.organization, next.organization, next.organization
+        )),
+        Some(previous) if previous.provider != next.provider => Some(format!(
+            "Switching {} from {} to {}",
+            next.organization,
+            provider(previous),
+            provider(next)
+        )),
+        _ => None,
+    }
+}
+
+/// Writes every organization in the id token to its own directory, since the global files
+/// (`~/.npmrc`, `~/.m2/settings.xml`, ...) can only point at one organization at a time.
+/// The active context is left alone.
+async fn set_all(
+    environment: &P6mEnvironment,
+    matches: &ArgMatches,
+    credentials: &Credentials,
+) -> Result<(), Error> {
+    let token_repository = TokenRepository::new(&environment.auth_n, &environment.auth_dir)?;
+    let organizations = token_repository
+        .organizations()
+        .context("Unable to read your organizations. Run `p6m login`")?;
+    if organizations.is_empty() {
+        return Err(Error::msg(
+            "Your token doesn't list any organizations. Run `p6m login`",
+        ));
+    }
+
+    let provider = matches
+        .get_one::<StorageProvider>("provider")
+        .cloned()
+        .unwrap_or_default();
+
+    for name in organizations.values() {
+        let targets = ContextTargets::for_organization(environment.config_dir(), name);
+        set_context(
+            &Organization::new(name.clone()),
+            &provider,
+            credentials,
+            &targets,
+        )
+        .await
+        .with_context(|| format!("Unable to configure {name}"))?;
+        println!("{name}: {}", targets.root.display());
+    }
+
+    if matches.get_flag("verify") {
+        let mut failed = vec![];
+        for name in organizations.values() {
+            if let Err(err) = verify_registries(name, &provider, credentials).await {
+                warn!("{err}");
+                failed.push(name.as_str());
+            }
+        }
+        if !failed.is_empty() {
+            return Err(Error::msg(format!(
+                "Registry credentials didn't work for {}",
+                failed.join(", ")
+            )));
+        }
+    }
+
+    println!(
+        "\nYour global ~/.npmrc, ~/.m2/settings.xml, Poetry and Cargo files are unchanged. Use an organization's files with e.g. \
+         `NPM_CONFIG_USERCONFIG=<dir>/.npmrc`, `mvn -s <dir>/.m2/settings.xml` or `POETRY_CONFIG_DIR=<dir>/pypoetry`, \
+         or run `p6m context --org <org>` to make one of them global."
+    );
+    Ok(())
+}
+
+/// Where [set_context] writes each tool's configuration.
+struct ContextTargets {
+    root: PathBuf,
+    m2_dir: PathBuf,
+    npmrc_dir: PathBuf,
+    poetry_config_dir: PathBuf,
+    cargo_config_dir: PathBuf,
+}
+
+impl ContextTargets {
+    /// The files each tool reads by default.
+    fn global() -> Result<Self, Error> {
+        let home_dir =
+            dirs::home_dir().ok_or(Error::msg("Unable to obtain home directory path"))?;
+        Ok(Self {
+            m2_dir: home_dir.join(".m2"),
+            npmrc_dir: home_dir.clone(),
+            poetry_config_dir: poetry_config_dir()?,
+            cargo_config_dir: home_dir.join(".cargo"),
+            root: home_dir,
+        })
+    }
+
+    /// A per-organization copy of the same layout under `<config dir>/contexts/<org>`.
+    fn for_organization(config_dir: &Utf8Path, organization: &str) -> Self {
+        let root = config_dir
+            .join("contexts")
+            .join(organization)
+            .into_std_path_buf();
+        Self {
+            m2_dir: root.join(".m2"),
+            npmrc_dir: root.clone(),
+            poetry_config_dir: root.join("pypoetry"),
+            cargo_config_dir: root.join(".cargo"),
+            root,
+        }
+    }
 }
 
 async fn set_context(
     organization: &Organization,
     active_storage: &StorageProvider,
+    credentials: &Credentials,
+    targets: &ContextTargets,
 ) -> Result<(), Error> {
     let organization_name = organization.name().to_owned();
-    let artifactory_username = read_env_var_only_if!(
-        active_storage,
-        StorageProvider::Artifactory,
-        "ARTIFACTORY_USERNAME"
-    );
-    let artifactory_identity_token = read_env_var_only_if!(
-        active_storage,
-        StorageProvider::Artifactory,
-        "ARTIFACTORY_IDENTITY_TOKEN"
-    );
-    let cloudsmith_username = read_env_var_only_if!(
-        active_storage,
-        StorageProvider::Cloudsmith,
-        "CLOUDSMITH_USERNAME"
-    );
-    let cloudsmith_api_key = read_env_var_only_if!(
-        active_storage,
-        StorageProvider::Cloudsmith,
-        "CLOUDSMITH_API_KEY"
-    );
-
-    let home_dir = dirs::home_dir().ok_or(Error::msg("Unable to obtain home directory path"))?;
+    let registry_credentials = RegistryCredentials::read(credentials, active_storage)?;
+    let registries = Registries::new(&organization_name, active_storage);
+    let RegistryCredentials {
+        artifactory_username,
+        artifactory_identity_token,
+        cloudsmith_username,
+        cloudsmith_api_key,
+    } = &registry_credentials;
 
     // Maven
 
-    let mut m2_dir = home_dir.to_path_buf();
-    m2_dir.push(".m2");
+    let m2_dir = targets.m2_dir.clone();
 
     new_file_with_content!(
         m2_dir,
@@ -97,19 +702,8 @@ async fn set_context(
 
     // NPM
 
-    let registry_url = match active_storage {
-        StorageProvider::Artifactory => format!(
-            "p6m.jfrog.io/artifactory/api/npm/{}-npm/",
-            organization_name
-        ),
-        StorageProvider::Cloudsmith => {
-            format!("npm.cloudsmith.io/p6m-dev/{}/", organization_name)
-        }
-    };
-    let platform_registry_url = match active_storage {
-        StorageProvider::Artifactory => "p6m.jfrog.io/artifactory/api/npm/p6m-dev-npm/",
-        StorageProvider::Cloudsmith => "npm.cloudsmith.io/p6m-dev/p6m-run/",
-    };
+    let registry_url = &registries.npm;
+    let platform_registry_url = registries.npm_platform;
     let auth_config = match active_storage {
         StorageProvider::Artifactory => {
             let b64engine = engine::general_purpose::STANDARD;
@@ -121,53 +715,36 @@ async fn set_context(
         StorageProvider::Cloudsmith => format!("_authToken={}", cloudsmith_api_key),
     };
 
+    let npmrc_dir = targets.npmrc_dir.clone();
+    let npmrc_path = npmrc_dir.join(".npmrc");
+    let existing_npmrc = match fs::read_to_string(&npmrc_path).await {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => {
+            return Err(Error::new(err).context(format!("Unable to read {}", npmrc_path.display())))
+        }
+    };
+
     new_file_with_content!(
-        home_dir,
+        npmrc_dir,
         ".npmrc",
-        render!(
-            include_str!("../resources/npmrc"),
-            registry_url,
-            platform_registry_url,
-            auth_config,
+        merge_npmrc(
+            &existing_npmrc,
+            &render!(
+                include_str!("../resources/npmrc"),
+                registry_url,
+                platform_registry_url,
+                auth_config,
+            )
         )
     );
 
     // Python
 
-    #[cfg(target_os = "windows")]
-    let poetry_config_dir = {
-        let mut config = PathBuf::from(
-            std::env::var("APPDATA")
-                .expect("No APPDATA environment variable. Are you sure you are on Windows?"),
-        );
-        config.push("pypoetry");
-        config
-    };
-    #[cfg(target_os = "macos")]
-    let poetry_config_dir = {
-        let mut config = home_dir.to_path_buf();
-        config.push("Library");
-        config.push("Application Support");
-        config.push("pypoetry");
-        config
-    };
-    #[cfg(all(target_family = "unix", not(target_os = "macos")))]
-    let poetry_config_dir = {
-        let mut config = home_dir.to_path_buf();
-        config.push(".config");
-        config.push("pypoetry");
-        config
-    };
-
-    let username = match active_storage {
-        StorageProvider::Artifactory => artifactory_username.clone(),
-        StorageProvider::Cloudsmith => cloudsmith_username.clone(),
-    };
+    let poetry_config_dir = targets.poetry_config_dir.clone();
 
-    let password = match active_storage {
-        StorageProvider::Artifactory => artifactory_identity_token.clone(),
-        StorageProvider::Cloudsmith => cloudsmith_api_key.clone(),
-    };
+    let username = registry_credentials.username(active_storage);
+    let password = registry_credentials.password(active_storage);
 
     new_file_with_content!(
         poetry_config_dir,
@@ -180,16 +757,7 @@ async fn set_context(
         )
     );
 
-    let alt_publishing_url = match active_storage {
-        StorageProvider::Artifactory => format!(
-            "https://p6m.jfrog.io/artifactory/api/pypi/{}-pypi/",
-            organization_name
-        ),
-        StorageProvider::Cloudsmith => format!(
-            "https://python.cloudsmith.io/p6m-dev/{}/",
-            organization_name
-        ),
-    };
+    let alt_publishing_url = &registries.pypi;
 
     new_file_with_content!(
         poetry_config_dir,
@@ -201,11 +769,7 @@ async fn set_context(
         )
     );
 
-    let cargo_config_dir = {
-        let mut config = home_dir.to_path_buf();
-        config.push(".cargo");
-        config
-    };
+    let cargo_config_dir = targets.cargo_config_dir.clone();
 
     new_file_with_content!(
         cargo_config_dir,
@@ -219,3 +783,233 @@ async fn set_context(
 
     Ok(())
 }
+
+/// Poetry's config directory: `POETRY_CONFIG_DIR` if set, otherwise `pypoetry` in the platform config dir
+/// (`%APPDATA%` on Windows, `~/Library/Application Support` on macOS, `$XDG_CONFIG_HOME` or `~/.config` elsewhere).
+fn poetry_config_dir() -> Result<std::path::PathBuf, Error> {
+    if let Some(dir) = std::env::var_os("POETRY_CONFIG_DIR").filter(|dir| !dir.is_empty()) {
+        return Ok(dir.into());
+    }
+
+    dirs::config_dir()
+        .map(|dir| dir.join("pypoetry"))
+        .ok_or_else(|| Error::msg("Unable to obtain the config directory for Poetry"))
+}
+
+/// Registry hosts whose `.npmrc` auth lines belong to p6m, whichever organization they were written for.
+const NPM_MANAGED_HOSTS: [&str; 2] = ["//p6m.jfrog.io/", "//npm.cloudsmith.io/p6m-dev/"];
+
+/// Replaces the p6m-managed lines of an existing `.npmrc` with `managed`, keeping everything else.
+///
+/// A line is managed if it sets a key that `managed` sets, or configures auth for a p6m registry
+/// (left over from another organization). The new lines go where the first managed line was,
+/// or at the end if there wasn't one.
+fn merge_npmrc(existing: &str, managed: &str) -> String {
+    let key = |line: &str| line.split_once('=').map(|(key, _)| key.trim().to_owned());
+    let managed_keys: Vec<String> = managed.lines().filter_map(key).collect();
+    let is_managed = |line: &str| match key(line) {
+        Some(key) => {
+            managed_keys.contains(&key)
+                || NPM_MANAGED_HOSTS.iter().any(|host| key.starts_with(host))
+        }
+        None => false,
+    };
+
+    let mut lines: Vec<&str> = Vec::new();
+    let mut inserted = false;
+    for line in existing.lines() {
+        if !is_managed(line) {
+            lines.push(line);
+        } else if !inserted {
+            lines.extend(managed.lines());
+            inserted = true;
+        }
+    }
+    if !inserted {
+        lines.extend(managed.lines());
+    }
+
+    let mut merged = lines.join("\n");
+    merged.push('\n');
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_credentials() {
+        let credentials = parse_credentials(
+            "# Artifactory\n\
+             ARTIFACTORY_USERNAME=dev@example.com\n\
+             export ARTIFACTORY_IDENTITY_TOKEN = \"abc=123\"\n\
+             \n\
+             CLOUDSMITH_API_KEY='key'\n\
+             not a credential\n",
+        );
+
+        assert_eq!(
+            credentials,
+            BTreeMap::from([
+                (
+                    "ARTIFACTORY_USERNAME".to_string(),
+                    "dev@example.com".to_string()
+                ),
+                (
+                    "ARTIFACTORY_IDENTITY_TOKEN".to_string(),
+                    "abc=123".to_string()
+                ),
+                ("CLOUDSMITH_API_KEY".to_string(), "key".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_context_targets_for_organization() {
+        let targets = ContextTargets::for_organization(Utf8Path::new("/home/dev/.p6m"), "acme");
+        let root = PathBuf::from("/home/dev/.p6m/contexts/acme");
+
+        assert_eq!(targets.m2_dir, root.join(".m2"));
+        assert_eq!(targets.npmrc_dir, root);
+        assert_eq!(targets.poetry_config_dir, root.join("pypoetry"));
+        assert_eq!(targets.cargo_config_dir, root.join(".cargo"));
+    }
+
+    #[test]
+    fn test_export_variables() {
+        let credentials = Credentials {
+            path: Utf8PathBuf::from("/home/dev/.p6m/credentials"),
+            values: BTreeMap::from([
+                ("CLOUDSMITH_USERNAME".to_string(), "dev".to_string()),
+                ("CLOUDSMITH_API_KEY".to_string(), "key".to_string()),
+            ]),
+        };
+        let variables: BTreeMap<String, String> =
+            export_variables("acme-corp", &StorageProvider::Cloudsmith, &credentials)
+                .unwrap()
+                .into_iter()
+                .collect();
+
+        assert_eq!(variables["P6M_STORAGE_PROVIDER"], "cloudsmith");
+        assert_eq!(
+            variables["NPM_CONFIG_REGISTRY"],
+            "https://npm.cloudsmith.io/p6m-dev/acme-corp/"
+        );
+        assert_eq!(
+            variables["POETRY_HTTP_BASIC_ACME_CORP_PYPI_PASSWORD"],
+            "key"
+        );
+        assert_eq!(variables["CLOUDSMITH_USERNAME"], "dev");
+        assert!(!variables.contains_key("ARTIFACTORY_IDENTITY_TOKEN"));
+    }
+
+    #[test]
+    fn test_missing_credential_names_credentials_path() {
+        let credentials = Credentials {
+            path: Utf8PathBuf::from("/etc/p6m/credentials"),
+            values: BTreeMap::new(),
+        };
+        if std::env::var("ARTIFACTORY_USERNAME").is_err() {
+            let err = RegistryCredentials::read(&credentials, &StorageProvider::Artifactory)
+                .err()
+                .unwrap();
+            assert_eq!(
+                err.to_string(),
+                "ARTIFACTORY_USERNAME must be set as an environment variable or in /etc/p6m/credentials."
+            );
+        }
+    }
+
+    #[test]
+    fn test_registry_checks() {
+        let credentials = RegistryCredentials {
+            artifactory_username: String::new(),
+            artifactory_identity_token: String::new(),
+            cloudsmith_username: "dev".to_string(),
+            cloudsmith_api_key: "key".to_string(),
+        };
+        let checks = registry_checks("acme", &StorageProvider::Cloudsmith, &credentials);
+
+        assert_eq!(
+            checks[0].url,
+            "https://npm.cloudsmith.io/p6m-dev/acme/-/whoami"
+        );
+        assert_eq!(checks[0].authorization, "Bearer key");
+        assert_eq!(checks[1].method, reqwest::Method::HEAD);
+        assert_eq!(checks[1].url, "https://maven.cloudsmith.io/p6m-dev/acme/");
+        assert_eq!(checks[1].authorization, "Basic ZGV2OmtleQ==");
+    }
+
+    #[test]
+    fn test_format_variable() {
+        assert_eq!(
+            format_variable(ExportFormat::Shell, "KEY", "it's"),
+            "export KEY='it'\\''s'"
+        );
+        assert_eq!(
+            format_variable(ExportFormat::Dotenv, "KEY", "a=b"),
+            "KEY=a=b"
+        );
+        assert_eq!(
+            format_variable(ExportFormat::Dotenv, "KEY", "a \"b\""),
+            "KEY=\"a \\\"b\\\"\""
+        );
+    }
+
+    #[test]
+    fn test_switch_message() {
+        let state = |organization: &str, provider: StorageProvider| ContextState {
+            organization: organization.to_string(),
+            provider,
+        };
+        let acme = state("acme", StorageProvider::Artifactory);
+
+        assert_eq!(switch_message(None, &acme), None);
+        assert_eq!(switch_message(Some(&acme), &acme), None);
+        assert!(
+            switch_message(Some(&state("globex", StorageProvider::Artifactory)), &acme)
+                .unwrap()
+                .starts_with("Switching context from globex to acme")
+        );
+        assert_eq!(
+            switch_message(Some(&state("acme", StorageProvider::Cloudsmith)), &acme).as_deref(),
+            Some("Switching acme from cloudsmith to artifactory")
+        );
+    }
+
+    #[test]
+    fn test_merge_npmrc() {
+        let existing = "proxy=http://proxy.example.com:8080\n\
+                        @acme:registry=https://npm.example.com/\n\
+                        registry=https://registry.npmjs.org/\n\
+                        //p6m.jfrog.io/artifactory/api/npm/old-org-npm/:_auth=b2xk\n\
+                        //npm.example.com/:_authToken=secret\n";
+        let managed = "registry=https://p6m.jfrog.io/artifactory/api/npm/new-org-npm/\n\
+                       //p6m.jfrog.io/artifactory/api/npm/new-org-npm/:_auth=bmV3\n";
+
+        let merged = merge_npmrc(existing, managed);
+
+        assert_eq!(
+            merged,
+            "proxy=http://proxy.example.com:8080\n\
+             @acme:registry=https://npm.example.com/\n\
+             registry=https://p6m.jfrog.io/artifactory/api/npm/new-org-npm/\n\
+             //p6m.jfrog.io/artifactory/api/npm/new-org-npm/:_auth=bmV3\n\
+             //npm.example.com/:_authToken=secret\n"
+        );
+        assert_eq!(merge_npmrc(&merged, managed), merged);
+        assert_eq!(merge_npmrc("", managed), managed);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_poetry_config_dir_uses_appdata() {
+        if std::env::var_os("POETRY_CONFIG_DIR").is_some() {
+            return;
+        }
+
+        let appdata = std::path::PathBuf::from(std::env::var_os("APPDATA").unwrap());
+        assert_eq!(poetry_config_dir().unwrap(), appdata.join("pypoetry"));
+    }
+}
@@ -0,0 +1,166 @@
+use crate::{
+    cli::P6mEnvironment,
+    models::git::orgs_root,
+    sso::kubeconfig,
+    workstation::check::{self, Ecosystem, CHECK_ERROR, CHECK_PREFIX, CHECK_SUCCESS, CHECK_WARN},
+};
+use anyhow::Error;
+use clap::ArgMatches;
+use kube::config::Kubeconfig;
+
+/// The `p6m workstation check` ecosystems every p6m workstation needs.
+const DOCTOR_ECOSYSTEMS: [Ecosystem; 2] = [Ecosystem::Core, Ecosystem::Kubernetes];
+
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+struct Report {
+    warnings: usize,
+    failures: usize,
+}
+
+impl Report {
+    fn section(&self, name: &str) {
+        println!("\n{CHECK_PREFIX} Checking {name}");
+    }
+
+    fn record(&mut self, status: Status, message: &str, fix: Option<&str>) {
+        let icon = match status {
+            Status::Pass => CHECK_SUCCESS,
+            Status::Warn => {
+                self.warnings += 1;
+                CHECK_WARN
+            }
+            Status::Fail => {
+                self.failures += 1;
+                CHECK_ERROR
+            }
+        };
+
+        println!("\t{icon} {message}");
+        if let Some(fix) = fix {
+            println!("\t   Fix: {fix}");
+        }
+    }
+
+    /// Prints a `workstation check` report, counting its warnings and errors.
+    fn include(&mut self, output: &str) {
+        print!("{output}");
+        self.warnings += output.matches(CHECK_WARN).count();
+        self.failures += output.matches(CHECK_ERROR).count();
+    }
+}
+
+/// Runs a quick health check across auth, kubeconfig, local repositories, and workstation tools.
+pub async fn execute(environment: P6mEnvironment, _matches: &ArgMatches) -> Result<(), Error> {
+    let mut report = Report {
+        warnings: 0,
+        failures: 0,
+    };
+
+    match check::auth_report(&environment) {
+        Ok(output) => report.include(&output),
+        Err(e) => {
+            report.section("p6m Login");
+            report.record(
+                Status::Fail,
+                &format!("Unable to read {}: {e}", environment.auth_dir),
+                None,
+            );
+        }
+    }
+    check_kubeconfig(&mut report);
+    check_orgs(&mut report);
+    check_tools(&mut report).await;
+
+    println!();
+    match (report.failures, report.warnings) {
+        (0, 0) => {
+            println!("{CHECK_SUCCESS} All checks passed");
+            Ok(())
+        }
+        (0, warnings) => {
+            println!("{CHECK_WARN} Passed with {warnings} warning(s)");
+            Ok(())
+        }
+        (failures, _) => Err(Error::msg(format!("doctor found {failures} problem(s)"))),
+    }
+}
+
+fn check_kubeconfig(report: &mut Report) {
+    report.section("kubeconfig");
+
+    let path = kubeconfig::path();
+    let config = match Kubeconfig::read_from(&path) {
+        Ok(config) => config,
+        Err(_) if !path.exists() => {
+            report.record(
+                Status::Warn,
+                &format!("{} does not exist", path.display()),
+                Some("run `p6m sso`"),
+            );
+            return;
+        }
+        Err(e) => {
+            report.record(
+                Status::Fail,
+                &format!("Unable to read {}: {e}", path.display()),
+                None,
+            );
+            return;
+        }
+    };
+
+    let contexts = config
+        .contexts
+        .iter()
+        .filter(|context| context.name.ends_with(".p6m"))
+        .count();
+
+    match contexts {
+        0 => report.record(
+            Status::Warn,
+            &format!("{} has no p6m contexts", path.display()),
+            Some("run `p6m sso`"),
+        ),
+        contexts => report.record(
+            Status::Pass,
+            &format!("{} has {contexts} p6m context(s)", path.display()),
+            None,
+        ),
+    }
+}
+
+fn check_orgs(report: &mut Report) {
+    report.section("local repositories");
+
+    let root = orgs_root();
+    match root.is_dir() {
+        true => report.record(Status::Pass, &format!("{} exists", root.display()), None),
+        false => report.record(
+            Status::Warn,
+            &format!("{} does not exist", root.display()),
+            Some("run `p6m repos pull`"),
+        ),
+    }
+}
+
+/// The workstation checks for the tools every p6m workstation needs, one ecosystem at a time.
+async fn check_tools(report: &mut Report) {
+    for ecosystem in DOCTOR_ECOSYSTEMS {
+        match check::report(&ecosystem).await {
+            Ok(output) => report.include(&output),
+            Err(e) => {
+                report.section(&format!("{ecosystem} tools"));
+                report.record(
+                    Status::Fail,
+                    &format!("Unable to run the checks: {e}"),
+                    Some("run `p6m workstation check` for details"),
+                )
+            }
+        }
+    }
+}
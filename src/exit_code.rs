@@ -0,0 +1,68 @@
+//! Process exit codes, so scripts can tell failures apart:
+//!
+//! | Code | Meaning                                                                |
+//! |------|------------------------------------------------------------------------|
+//! | 0    | Success                                                                |
+//! | 1    | Any other failure                                                      |
+//! | 2    | Invalid command or arguments (the same code clap uses for usage errors) |
+//! | 3    | Not logged in, or the stored tokens were rejected; run `p6m login`     |
+
+use anyhow::Error;
+use std::fmt;
+
+pub const FAILURE: i32 = 1;
+pub const USAGE: i32 = 2;
+pub const NOT_LOGGED_IN: i32 = 3;
+
+/// An error that carries the exit code the process should end with.
+#[derive(Debug)]
+struct CodedError {
+    code: i32,
+    message: String,
+}
+
+impl fmt::Display for CodedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CodedError {}
+
+pub fn usage(message: impl Into<String>) -> Error {
+    Error::new(CodedError {
+        code: USAGE,
+        message: message.into(),
+    })
+}
+
+pub fn not_logged_in(message: impl Into<String>) -> Error {
+    Error::new(CodedError {
+        code: NOT_LOGGED_IN,
+        message: message.into(),
+    })
+}
+
+/// The exit code for `error`, from the first coded error in its chain.
+pub fn of(error: &Error) -> i32 {
+    error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<CodedError>())
+        .map(|coded| coded.code)
+        .unwrap_or(FAILURE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(of(&Error::msg("boom")), FAILURE);
+        assert_eq!(of(&usage("Invalid command: nope")), USAGE);
+        assert_eq!(
+            of(&not_logged_in("Please run `p6m login`").context("Unable to fetch apps")),
+            NOT_LOGGED_IN
+        );
+    }
+}
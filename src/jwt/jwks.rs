@@ -0,0 +1,210 @@
+use anyhow::{Context, Error, Result};
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use clap::ArgMatches;
+use log::debug;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{auth::OpenIdDiscoveryDocument, AuthN};
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Json,
+    Pem,
+}
+
+/// A JSON Web Key, with only the fields needed to turn RSA keys into PEM.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Jwk {
+    pub kid: Option<String>,
+    pub kty: String,
+    pub n: Option<String>,
+    pub e: Option<String>,
+}
+
+/// Prints the identity provider's signing keys, as published at the discovered `jwks_uri`.
+pub async fn execute(auth_n: &AuthN, args: &ArgMatches) -> Result<()> {
+    let format = args
+        .get_one::<Format>("format")
+        .copied()
+        .unwrap_or(Format::Json);
+    let kid = args.get_one::<String>("kid");
+
+    let jwks = fetch_jwks(auth_n).await?;
+
+    match format {
+        Format::Json => {
+            let output = match kid {
+                Some(kid) => jwks["keys"]
+                    .as_array()
+                    .and_then(|keys| keys.iter().find(|key| key["kid"].as_str() == Some(kid)))
+                    .cloned()
+                    .with_context(|| format!("No key with kid {kid}"))?,
+                None => jwks,
+            };
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+        Format::Pem => print!("{}", select_key(&keys(&jwks)?, kid)?.to_pem()?),
+    }
+
+    Ok(())
+}
+
+pub async fn fetch_jwks(auth_n: &AuthN) -> Result<Value> {
+    let discovery = OpenIdDiscoveryDocument::discover(auth_n).await?;
+    debug!("Fetching JWKS from {}", discovery.jwks_uri);
+    reqwest::get(&discovery.jwks_uri)
+        .await?
+        .error_for_status()?
+        .json::<Value>()
+        .await
+        .with_context(|| format!("Unable to parse JWKS from {}", discovery.jwks_uri))
+}
+
+pub fn keys(jwks: &Value) -> Result<Vec<Jwk>> {
+    serde_json::from_value(jwks["keys"].clone()).context("JWKS has no valid keys")
+}
+
+/// The key with the given `kid`, or the only key if none was asked for.
+pub fn select_key<'a>(keys: &'a [Jwk], kid: Option<&String>) -> Result<&'a Jwk> {
+    match (kid, keys) {
+        (Some(kid), _) => keys
+            .iter()
+            .find(|key| key.kid.as_ref() == Some(kid))
+            .with_context(|| format!("No key with kid {kid}")),
+        (None, [key]) => Ok(key),
+        (None, _) => Err(Error::msg(format!(
+            "The JWKS has {} keys; pick one with --kid: {}",
+            keys.len(),
+            keys.iter()
+                .filter_map(|key| key.kid.clone())
+                .collect::<Vec<String>>()
+                .join(", ")
+        ))),
+    }
+}
+
+impl Jwk {
+    /// Encodes an RSA key as a PEM `PUBLIC KEY` (SubjectPublicKeyInfo).
+    pub fn to_pem(&self) -> Result<String> {
+        if self.kty != "RSA" {
+            return Err(Error::msg(format!(
+                "Only RSA keys can be converted to PEM, not {}",
+                self.kty
+            )));
+        }
+
+        let decode = |value: &Option<String>, name: &str| {
+            URL_SAFE_NO_PAD
+                .decode(
+                    value
+                        .as_deref()
+                        .with_context(|| format!("RSA key missing {name}"))?,
+                )
+                .with_context(|| format!("RSA key has an invalid {name}"))
+        };
+        let n = decode(&self.n, "n")?;
+        let e = decode(&self.e, "e")?;
+
+        let rsa_public_key = der(0x30, &[der_integer(&n), der_integer(&e)].concat());
+        // AlgorithmIdentifier for rsaEncryption (1.2.840.113549.1.1.1) with NULL parameters
+        let algorithm = [
+            0x30, 0x0d, 0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01, 0x05,
+            0x00,
+        ];
+        let bit_string = der(0x03, &[&[0x00], rsa_public_key.as_slice()].concat());
+        let spki = der(0x30, &[&algorithm[..], bit_string.as_slice()].concat());
+
+        let body = STANDARD.encode(spki);
+        let lines: Vec<&str> = body
+            .as_bytes()
+            .chunks(64)
+            .map(|chunk| std::str::from_utf8(chunk).unwrap())
+            .collect();
+        Ok(format!(
+            "-----BEGIN PUBLIC KEY-----\n{}\n-----END PUBLIC KEY-----\n",
+            lines.join("\n")
+        ))
+    }
+}
+
+fn der(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![tag];
+    let len = contents.len();
+    if len < 0x80 {
+        encoded.push(len as u8);
+    } else {
+        let len_bytes: Vec<u8> = len
+            .to_be_bytes()
+            .iter()
+            .copied()
+            .skip_while(|byte| *byte == 0)
+            .collect();
+        encoded.push(0x80 | len_bytes.len() as u8);
+        encoded.extend(len_bytes);
+    }
+    encoded.extend_from_slice(contents);
+    encoded
+}
+
+/// DER INTEGERs are signed, so unsigned big-endian values with the top bit set need a leading zero.
+fn der_integer(value: &[u8]) -> Vec<u8> {
+    let trimmed: Vec<u8> = value
+        .iter()
+        .copied()
+        .skip_while(|byte| *byte == 0)
+        .collect();
+    match trimmed.first() {
+        None => der(0x02, &[0x00]),
+        Some(byte) if byte & 0x80 != 0 => der(0x02, &[&[0x00], trimmed.as_slice()].concat()),
+        Some(_) => der(0x02, &trimmed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_der_integer() {
+        assert_eq!(
+            der_integer(&[0x01, 0x00, 0x01]),
+            vec![0x02, 0x03, 0x01, 0x00, 0x01]
+        );
+        assert_eq!(der_integer(&[0x00, 0x80]), vec![0x02, 0x02, 0x00, 0x80]);
+        assert_eq!(der(0x04, &[0; 200])[..3], [0x04, 0x81, 200]);
+    }
+
+    #[test]
+    fn test_to_pem() {
+        // n is 0xc1 followed by 127 zero bytes: a 1024-bit modulus needing a leading zero
+        let mut n = vec![0xc1];
+        n.extend([0; 127]);
+        let jwk = Jwk {
+            kid: Some("key-1".into()),
+            kty: "RSA".into(),
+            n: Some(URL_SAFE_NO_PAD.encode(&n)),
+            e: Some("AQAB".into()),
+        };
+
+        let pem = jwk.to_pem().unwrap();
+        let body: String = pem
+            .lines()
+            .filter(|line| !line.starts_with("-----"))
+            .collect();
+        let der = STANDARD.decode(body).unwrap();
+
+        assert!(pem.starts_with("-----BEGIN PUBLIC KEY-----\n"));
+        // SEQUENCE of 159 bytes, the standard size for a 1024-bit RSA SubjectPublicKeyInfo
+        assert_eq!(der[..3], [0x30, 0x81, 0x9f]);
+        assert_eq!(der.len(), 162);
+        assert!(der.ends_with(&[0x02, 0x03, 0x01, 0x00, 0x01]));
+
+        let keys = vec![jwk];
+        assert!(select_key(&keys, None).is_ok());
+        assert!(select_key(&keys, Some(&"other".to_string())).is_err());
+    }
+}
@@ -4,11 +4,16 @@ use clap::ArgMatches;
 use jsonwebtokens::{encode, Algorithm, AlgorithmID};
 use serde_json::json;
 
-use crate::cli::P6mEnvironment;
+use crate::cli::{self, P6mEnvironment};
 
-pub async fn execute(_: P6mEnvironment, matches: &ArgMatches) -> Result<()> {
-    match matches.subcommand() {
+mod decode;
+pub mod jwks;
+
+pub async fn execute(environment: P6mEnvironment, matches: &ArgMatches) -> Result<()> {
+    match cli::subcommand("p6m jwt", matches)? {
         Some(("insecure", args)) => generate_jwt(args),
+        Some(("jwks", args)) => jwks::execute(&environment.auth_n, args).await,
+        Some(("decode", args)) => decode::execute(&environment.auth_n, args).await,
         Some((command, _)) => Err(Error::msg(format!(
             "Unimplemented sso command: '{}'",
             command
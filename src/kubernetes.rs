@@ -0,0 +1,338 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Error};
+use clap::ArgMatches;
+use kube::config::Kubeconfig;
+use serde::Serialize;
+
+use crate::auth::TokenRepository;
+use crate::cli::{self, P6mEnvironment};
+use crate::sso::kubeconfig;
+
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq)]
+pub enum Output {
+    Default,
+    Json,
+}
+
+/// The kubeconfig's current context, and the p6m organization behind it if `p6m sso` created it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CurrentContext {
+    context: String,
+    cluster: Option<String>,
+    server: Option<String>,
+    user: Option<String>,
+    p6m: bool,
+    /// The organization's name, or its id when the ID token doesn't list it
+    organization: Option<String>,
+    organization_id: Option<String>,
+    profile: Option<String>,
+}
+
+pub async fn execute(environment: P6mEnvironment, matches: &ArgMatches) -> Result<(), Error> {
+    match cli::subcommand("p6m kube", matches)? {
+        Some(("current", subargs)) => current(&environment, subargs),
+        Some(("use", subargs)) => use_context(&environment, subargs),
+        Some((command, _)) => Err(Error::msg(format!(
+            "Unimplemented kube command: '{}'",
+            command
+        ))),
+        None => Err(Error::msg("No kube command given")),
+    }
+}
+
+/// Shows the context `kubectl` would use, reading `KUBECONFIG` the same way it does.
+fn current(environment: &P6mEnvironment, matches: &ArgMatches) -> Result<(), Error> {
+    let output = matches
+        .try_get_one("output")
+        .unwrap_or(Some(&Output::Default));
+
+    let kubeconfig = Kubeconfig::read().context("Unable to read kubeconfig")?;
+    let current = current_context(&kubeconfig, &organization_names(environment))?;
+
+    match output {
+        Some(Output::Json) => println!("{}", serde_json::to_string_pretty(&current)?),
+        None | Some(Output::Default) => {
+            println!("Context: {}", current.context);
+            if let Some(server) = current.server.as_ref().or(current.cluster.as_ref()) {
+                println!("Cluster: {server}");
+            }
+            match (current.p6m, &current.organization, &current.organization_id) {
+                (true, Some(organization), Some(id)) if organization != id => {
+                    println!("p6m organization: {organization} ({id})")
+                }
+                (true, Some(organization), _) => println!("p6m organization: {organization}"),
+                (true, None, _) => println!("p6m managed"),
+                (false, _, _) => println!("Not managed by p6m"),
+            }
+            if let Some(profile) = current.profile.as_ref() {
+                println!("p6m profile: {profile}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Switches `current-context` in the kubeconfig `p6m sso` writes to the p6m context for an organization or cluster.
+fn use_context(environment: &P6mEnvironment, matches: &ArgMatches) -> Result<(), Error> {
+    let query = matches
+        .get_one::<String>("organization")
+        .expect("Required by clap");
+
+    let path = kubeconfig::path();
+    let mut config = Kubeconfig::read_from(&path)
+        .with_context(|| format!("Unable to read {}", path.display()))?;
+
+    let contexts = p6m_contexts(&config, &organization_names(environment));
+    let name = find_context(&contexts, query)?;
+    config.current_context = Some(name.clone());
+    kubeconfig::write(&path, &config)?;
+
+    println!("Switched to context {name}");
+    Ok(())
+}
+
+/// A p6m context's name and the organization its credentials are for.
+#[derive(Debug, Clone, PartialEq)]
+struct P6mContext {
+    name: String,
+    /// The organization's name, or its id when the ID token doesn't list it
+    organization: Option<String>,
+}
+
+fn p6m_contexts(kubeconfig: &Kubeconfig, names: &BTreeMap<String, String>) -> Vec<P6mContext> {
+    kubeconfig
+        .contexts
+        .iter()
+        .filter_map(|context| {
+            let user = &context.context.as_ref()?.user;
+            let args = p6m_exec_args(kubeconfig, user)?;
+            Some(P6mContext {
+                name: context.name.clone(),
+                organization: flag_value(&args, "--org").map(|id| organization_name(names, &id)),
+            })
+        })
+        .collect()
+}
+
+/// Finds the one context whose organization or name matches `query`: exactly, then
+/// case-insensitively, then by case-insensitive prefix, then anywhere in the name.
+fn find_context(contexts: &[P6mContext], query: &str) -> Result<String, Error> {
+    let wanted = query.to_lowercase();
+    let matchers: [&dyn Fn(&str) -> bool; 4] = [
+        &|value| value == query,
+        &|value| value.to_lowercase() == wanted,
+        &|value| value.to_lowercase().starts_with(&wanted),
+        &|value| value.to_lowercase().contains(&wanted),
+    ];
+
+    for matcher in matchers {
+        let matches: Vec<&P6mContext> = contexts
+            .iter()
+            .filter(|context| {
+                matcher(&context.name) || context.organization.as_deref().is_some_and(matcher)
+            })
+            .collect();
+
+        match matches.as_slice() {
+            [] => continue,
+            [context] => return Ok(context.name.clone()),
+            _ => {
+                return Err(Error::msg(format!(
+                    "{query} matches several p6m contexts: {}",
+                    matches
+                        .iter()
+                        .map(|context| context.name.as_str())
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                )))
+            }
+        }
+    }
+
+    Err(Error::msg(match contexts {
+        [] => "There are no p6m contexts. Run `p6m sso`".to_string(),
+        _ => format!(
+            "No p6m context matches {query}; available: {}",
+            contexts
+                .iter()
+                .map(|context| context.name.as_str())
+                .collect::<Vec<&str>>()
+                .join(", ")
+        ),
+    }))
+}
+
+/// The `p6m whoami` arguments a user's exec plugin runs, if it's one `p6m sso` set up.
+fn p6m_exec_args(kubeconfig: &Kubeconfig, user: &str) -> Option<Vec<String>> {
+    kubeconfig
+        .auth_infos
+        .iter()
+        .find(|auth_info| auth_info.name == user)
+        .and_then(|auth_info| auth_info.auth_info.as_ref())
+        .and_then(|auth_info| auth_info.exec.as_ref())
+        .filter(|exec| exec.command.as_deref() == Some("p6m"))
+        .and_then(|exec| exec.args.clone())
+        .filter(|args| args.first().map(String::as_str) == Some("whoami"))
+}
+
+/// The organization names (by id) on the ID token; `p6m sso` writes ids into the exec args.
+/// Empty when not logged in, so ids are shown as they are.
+fn organization_names(environment: &P6mEnvironment) -> BTreeMap<String, String> {
+    TokenRepository::new(&environment.auth_n, &environment.auth_dir)
+        .and_then(|token_repository| token_repository.organizations())
+        .unwrap_or_default()
+}
+
+fn organization_name(names: &BTreeMap<String, String>, id: &str) -> String {
+    names.get(id).cloned().unwrap_or_else(|| id.to_string())
+}
+
+fn flag_value(args: &[String], flag: &str) -> Option<String> {
+    args.iter()
+        .skip_while(|arg| arg.as_str() != flag)
+        .nth(1)
+        .cloned()
+}
+
+/// A context is p6m's if its user runs `p6m whoami` to get credentials, as `p6m sso` sets up.
+fn current_context(
+    kubeconfig: &Kubeconfig,
+    names: &BTreeMap<String, String>,
+) -> Result<CurrentContext, Error> {
+    let name = kubeconfig
+        .current_context
+        .clone()
+        .filter(|name| !name.is_empty())
+        .context("No current context is set")?;
+    let context = kubeconfig
+        .contexts
+        .iter()
+        .find(|context| context.name == name)
+        .and_then(|context| context.context.clone())
+        .with_context(|| format!("Current context {name} isn't defined in the kubeconfig"))?;
+
+    let server = kubeconfig
+        .clusters
+        .iter()
+        .find(|cluster| cluster.name == context.cluster)
+        .and_then(|cluster| cluster.cluster.as_ref())
+        .and_then(|cluster| cluster.server.clone());
+
+    let args = p6m_exec_args(kubeconfig, &context.user);
+    let arg = |flag: &str| args.as_ref().and_then(|args| flag_value(args, flag));
+    let organization_id = arg("--org");
+
+    Ok(CurrentContext {
+        p6m: args.is_some(),
+        organization: organization_id
+            .as_deref()
+            .map(|id| organization_name(names, id)),
+        organization_id,
+        profile: arg("--profile"),
+        context: name,
+        cluster: Some(context.cluster).filter(|cluster| !cluster.is_empty()),
+        server,
+        user: Some(context.user).filter(|user| !user.is_empty()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_current_context() {
+        let kubeconfig: Kubeconfig = serde_yaml::from_str(
+            r#"
+apiVersion: v1
+kind: Config
+current-context: acme-dev.p6m
+clusters:
+  - name: https://acme-dev.example.com
+    cluster:
+      server: https://acme-dev.example.com
+contexts:
+  - name: acme-dev.p6m
+    context:
+      cluster: https://acme-dev.example.com
+      user: dev@example.com (acme-dev.p6m)
+  - name: kind-local
+    context:
+      cluster: kind-local
+      user: kind-local
+users:
+  - name: dev@example.com (acme-dev.p6m)
+    user:
+      exec:
+        apiVersion: client.authentication.k8s.io/v1beta1
+        command: p6m
+        args: [whoami, --org, org_123, --output, k8s-auth, --profile, staging]
+  - name: kind-local
+    user:
+      token: abc
+"#,
+        )
+        .unwrap();
+
+        let current = current_context(&kubeconfig, &BTreeMap::new()).unwrap();
+        assert!(current.p6m);
+        assert_eq!(current.organization.as_deref(), Some("org_123"));
+        assert_eq!(current.organization_id.as_deref(), Some("org_123"));
+        assert_eq!(current.profile.as_deref(), Some("staging"));
+
+        let names = BTreeMap::from([("org_123".to_string(), "acme".to_string())]);
+        let current = current_context(&kubeconfig, &names).unwrap();
+        assert_eq!(current.organization.as_deref(), Some("acme"));
+        assert_eq!(current.organization_id.as_deref(), Some("org_123"));
+        assert_eq!(current.profile.as_deref(), Some("staging"));
+        assert_eq!(
+            current.server.as_deref(),
+            Some("https://acme-dev.example.com")
+        );
+
+        let local = Kubeconfig {
+            current_context: Some("kind-local".into()),
+            ..kubeconfig
+        };
+        let current = current_context(&local, &names).unwrap();
+        assert!(!current.p6m);
+        assert_eq!(current.organization, None);
+
+        assert_eq!(
+            p6m_contexts(&local, &BTreeMap::new()),
+            vec![P6mContext {
+                name: "acme-dev.p6m".into(),
+                organization: Some("org_123".into()),
+            }]
+        );
+        let contexts = p6m_contexts(&local, &names);
+        assert_eq!(contexts[0].organization.as_deref(), Some("acme"));
+        assert_eq!(find_context(&contexts, "acme").unwrap(), "acme-dev.p6m");
+    }
+
+    #[test]
+    fn test_find_context() {
+        let context = |name: &str, organization: &str| P6mContext {
+            name: name.into(),
+            organization: Some(organization.into()),
+        };
+        let contexts = vec![
+            context("acme-dev.p6m", "acme"),
+            context("acme-prod.p6m", "acme"),
+            context("globex-dev.p6m", "globex"),
+        ];
+
+        assert_eq!(find_context(&contexts, "globex").unwrap(), "globex-dev.p6m");
+        assert_eq!(find_context(&contexts, "GLOB").unwrap(), "globex-dev.p6m");
+        assert_eq!(
+            find_context(&contexts, "acme-prod").unwrap(),
+            "acme-prod.p6m"
+        );
+        assert_eq!(find_context(&contexts, "prod").unwrap(), "acme-prod.p6m");
+        assert!(find_context(&contexts, "acme").is_err());
+        assert!(find_context(&contexts, "initech").is_err());
+    }
+}
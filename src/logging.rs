@@ -1,13 +1,146 @@
+use crate::progress;
+use chrono::Utc;
 use clap::ArgMatches;
+use log::{error, Level, Log, Metadata, Record};
+use std::io::{stderr, Write};
+
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
 
 pub fn init(matches: &ArgMatches) {
-    loggerv::Logger::new()
-        .verbosity(matches.get_count("verbosity") as u64)
-        .level(true)
-        .no_module_path()
-        .add_module_path_filter("p6m")
-        .module_path(false)
-        .base_level(log::Level::Info)
-        .init()
-        .unwrap();
+    let level = level(matches.get_count("verbosity"), matches.get_flag("quiet"));
+
+    let logger: Box<dyn Log> = match matches.get_one::<LogFormat>("log-format") {
+        Some(LogFormat::Json) => Box::new(JsonLogger { level }),
+        None | Some(LogFormat::Text) => Box::new(
+            loggerv::Logger::new()
+                .level(true)
+                .no_module_path()
+                .add_module_path_filter("p6m")
+                .module_path(false)
+                .max_level(level),
+        ),
+    };
+
+    log::set_boxed_logger(Box::new(ProgressAware(logger))).unwrap();
+    log::set_max_level(level.to_level_filter());
+}
+
+/// Logs a command's error with its causes joined on one line, or, with `--log-format json`,
+/// writes it to stderr as a single `{"error": ..., "causes": [...]}` object for scripts to parse.
+pub fn report_error(matches: &ArgMatches, error: &anyhow::Error) {
+    match matches.get_one::<LogFormat>("log-format") {
+        Some(LogFormat::Json) => {
+            progress::suspend(|| {
+                let _ = writeln!(stderr(), "{}", error_json(error));
+            });
+        }
+        None | Some(LogFormat::Text) => error!(
+            "{}",
+            error
+                .chain()
+                .map(|e| e.to_string())
+                .collect::<Vec<String>>()
+                .join(": ")
+        ),
+    }
+}
+
+fn error_json(error: &anyhow::Error) -> serde_json::Value {
+    serde_json::json!({
+        "error": error.to_string(),
+        "causes": error.chain().skip(1).map(|e| e.to_string()).collect::<Vec<String>>(),
+    })
+}
+
+/// Info by default, each -v raises it one level. --quiet drops to warnings and errors,
+/// and wins over a single -v; with -vv it caps logging at debug, and -vvv or more still applies.
+fn level(verbosity: u8, quiet: bool) -> Level {
+    match (verbosity, quiet) {
+        (0 | 1, true) => Level::Warn,
+        (0, false) => Level::Info,
+        (1, false) => Level::Debug,
+        (2, true) => Level::Debug,
+        _ => Level::Trace,
+    }
+}
+
+/// Hides any progress bar while a line is logged.
+struct ProgressAware(Box<dyn Log>);
+
+impl Log for ProgressAware {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        progress::suspend(|| self.0.log(record))
+    }
+
+    fn flush(&self) {
+        self.0.flush()
+    }
+}
+
+/// Emits one JSON object per line on stderr, for pipelines that ingest structured logs.
+struct JsonLogger {
+    level: Level,
+}
+
+impl Log for JsonLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level && metadata.target().starts_with("p6m")
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = serde_json::json!({
+            "timestamp": Utc::now().to_rfc3339(),
+            "level": record.level().to_string(),
+            "target": record.target(),
+            "message": record.args().to_string(),
+        });
+
+        let _ = writeln!(stderr(), "{line}");
+    }
+
+    fn flush(&self) {
+        let _ = stderr().flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level() {
+        assert_eq!(level(0, false), Level::Info);
+        assert_eq!(level(1, false), Level::Debug);
+        assert_eq!(level(2, false), Level::Trace);
+        assert_eq!(level(1, true), Level::Warn);
+        assert_eq!(level(2, true), Level::Debug);
+        assert_eq!(level(3, true), Level::Trace);
+    }
+
+    #[test]
+    fn test_error_json() {
+        let error = anyhow::Error::msg("connection refused")
+            .context("Unable to fetch apps")
+            .context("Please re-run `p6m login`");
+
+        assert_eq!(
+            error_json(&error),
+            serde_json::json!({
+                "error": "Please re-run `p6m login`",
+                "causes": ["Unable to fetch apps", "connection refused"],
+            })
+        );
+    }
 }
@@ -1,10 +1,29 @@
 use crate::{
     auth::{Claims, TokenRepository, TryReason},
     cli::P6mEnvironment,
-    whoami,
+    exit_code, sso, whoami, AuthToken,
 };
 use anyhow::{Context, Error};
 use clap::ArgMatches;
+use std::io::{stdout, Write};
+
+/// Token printed to stdout by `login --print-token`.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum PrintToken {
+    Access,
+    Id,
+    Refresh,
+}
+
+impl From<PrintToken> for AuthToken {
+    fn from(value: PrintToken) -> Self {
+        match value {
+            PrintToken::Access => AuthToken::Access,
+            PrintToken::Id => AuthToken::Id,
+            PrintToken::Refresh => AuthToken::Refresh,
+        }
+    }
+}
 
 pub async fn execute(environment: P6mEnvironment, matches: &ArgMatches) -> Result<(), Error> {
     let organization = matches
@@ -15,7 +34,7 @@ pub async fn execute(environment: P6mEnvironment, matches: &ArgMatches) -> Resul
 
     let mut token_repository = TokenRepository::new(&environment.auth_n, &environment.auth_dir)?;
 
-    token_repository.force();
+    token_repository.force().with_offline(environment.offline);
 
     if let Some(organization) = organization {
         token_repository
@@ -29,6 +48,16 @@ pub async fn execute(environment: P6mEnvironment, matches: &ArgMatches) -> Resul
             );
     }
 
+    if let Ok(Some(audience)) = matches.try_get_one::<String>("audience") {
+        token_repository.with_audience(audience)?;
+    }
+
+    if let Ok(Some(scopes)) = matches.try_get_many::<String>("scope") {
+        scopes.for_each(|scope| {
+            token_repository.with_scope(scope, Claims::default());
+        });
+    }
+
     match refresh {
         Some(true) => token_repository
             .try_refresh(&TryReason::LoginCommand)
@@ -40,6 +69,28 @@ pub async fn execute(environment: P6mEnvironment, matches: &ArgMatches) -> Resul
             .context("Please re-run `p6m login`")?,
     };
 
+    // A new login may see different clusters, so don't let `p6m sso` reuse the old list
+    if let Err(e) = sso::apps_cache::clear(environment.config_dir()) {
+        log::warn!("Unable to clear the cached apps: {e}");
+    }
+
+    // Keep stdout to just the token so scripts can capture it
+    if let Ok(Some(print_token)) = matches.try_get_one::<PrintToken>("print-token") {
+        eprintln!("\nLogged in!\n");
+        eprintln!("{}", token_repository.to_string());
+
+        let token_type = AuthToken::from(*print_token);
+        let token = token_repository
+            .read_token(token_type.clone())
+            .with_context(|| format!("unable to read {token_type}"))?
+            .ok_or_else(|| exit_code::not_logged_in(format!("missing {token_type}")))?;
+
+        let mut stdout = stdout();
+        write!(stdout, "{token}")?;
+        stdout.flush()?;
+        return Ok(());
+    }
+
     println!("\nLogged in!\n");
     whoami::execute(environment, matches).await
 }
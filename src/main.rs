@@ -1,24 +1,34 @@
 extern crate clap;
 
+mod apps;
 mod auth;
 mod auth0;
 mod cli;
 mod completions;
 mod context;
+mod doctor;
+mod exit_code;
 mod jwt;
+mod kubernetes;
 mod logging;
 mod login;
 mod models;
 mod open;
+mod progress;
+mod prompt;
 mod purge;
 mod repositories;
+mod self_update;
 mod sso;
+#[cfg(test)]
+mod test_util;
 mod tilt;
+mod token;
+mod version;
 mod whoami;
 mod workstation;
 
 use cli::P6mEnvironment;
-use log::error;
 
 pub use auth0::*;
 
@@ -29,34 +39,40 @@ async fn main() {
     let environment = match P6mEnvironment::init(&matches) {
         Ok(environment) => environment,
         Err(e) => {
-            error!("{}", e);
-            return;
+            logging::report_error(&matches, &e);
+            std::process::exit(exit_code::of(&e));
         }
     };
 
-    let result = match matches.subcommand() {
-        Some(("completions", subargs)) => completions::execute(subargs),
-        Some(("context", subargs)) => context::execute(subargs).await,
-        Some(("open", subargs)) => open::execute(subargs).await,
+    let result = dispatch(environment, &matches).await;
+
+    if let Err(e) = result {
+        logging::report_error(&matches, &e);
+        std::process::exit(exit_code::of(&e));
+    }
+}
+
+async fn dispatch(environment: P6mEnvironment, matches: &clap::ArgMatches) -> anyhow::Result<()> {
+    match cli::subcommand("p6m", matches)? {
+        Some(("apps", subargs)) => apps::execute(environment, subargs).await,
+        Some(("auth", subargs)) => auth::execute(environment, subargs).await,
+        Some(("completions", subargs)) => completions::execute(environment, subargs),
+        Some(("context", subargs)) => context::execute(environment, subargs).await,
+        Some(("doctor", subargs)) => doctor::execute(environment, subargs).await,
+        Some(("open", subargs)) => open::execute(environment, subargs).await,
         Some(("purge", subargs)) => purge::execute(subargs),
         Some(("repositories", subargs)) => repositories::execute(subargs).await,
         Some(("jwt", subargs)) => jwt::execute(environment, subargs).await,
-        Some(("tilt", subargs)) => tilt::execute(subargs).await,
+        Some(("kube", subargs)) => kubernetes::execute(environment, subargs).await,
+        Some(("tilt", subargs)) => tilt::execute(environment, subargs).await,
+        Some(("self", subargs)) => self_update::execute(subargs).await,
         Some(("sso", subargs)) => sso::execute(environment, subargs).await,
         Some(("login", subargs)) => login::execute(environment, subargs).await,
+        Some(("token", subargs)) => token::execute(environment, subargs).await,
         Some(("whoami", subargs)) => whoami::execute(environment, subargs).await,
-        Some(("workstation", subargs)) => workstation::execute(subargs).await,
-        Some((command, _)) => Err(anyhow::Error::msg(format!("Invalid command: {command}"))),
-        None => Err(anyhow::Error::msg("No command given")),
-    };
-
-    if let Err(e) = result {
-        error!(
-            "{}",
-            e.chain()
-                .map(|e| e.to_string())
-                .collect::<Vec<String>>()
-                .join(": ")
-        );
+        Some(("version", subargs)) => version::execute(subargs),
+        Some(("workstation", subargs)) => workstation::execute(environment, subargs).await,
+        Some((command, _)) => Err(exit_code::usage(format!("Invalid command: {command}"))),
+        None => Err(exit_code::usage("No command given")),
     }
 }
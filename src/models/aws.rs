@@ -25,3 +25,54 @@ pub struct AwsAccountRoleInfo {
 pub struct AwsEksListClustersResponse {
     pub clusters: Vec<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sso_cache_file() {
+        // Shape of ~/.aws/sso/cache/<sha1>.json written by `aws sso login`
+        let config: AwsConfig = serde_json::from_str(
+            r#"{
+                "startUrl": "https://p6m.awsapps.com/start",
+                "region": "us-east-2",
+                "accessToken": "aoaAAAAAGW-token",
+                "expiresAt": "2024-02-09T10:50:47Z",
+                "clientId": "client",
+                "clientSecret": "secret",
+                "registrationExpiresAt": "2024-05-09T10:50:47Z",
+                "refreshToken": "refresh"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.accessToken, "aoaAAAAAGW-token");
+        assert_eq!(config.expiresAt.to_rfc3339(), "2024-02-09T10:50:47+00:00");
+
+        let round_trip: AwsConfig =
+            serde_json::from_str(&serde_json::to_string(&config).unwrap()).unwrap();
+        assert_eq!(round_trip.accessToken, config.accessToken);
+        assert_eq!(round_trip.expiresAt, config.expiresAt);
+    }
+
+    #[test]
+    fn test_sso_cache_file_requires_token_and_expiry() {
+        let result = serde_json::from_str::<AwsConfig>(
+            r#"{"startUrl": "https://p6m.awsapps.com/start", "region": "us-east-2"}"#,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_eks_list_clusters() {
+        let response: AwsEksListClustersResponse =
+            serde_json::from_str(r#"{"clusters": ["p6m-dev", "p6m-prod"], "nextToken": null}"#)
+                .unwrap();
+        assert_eq!(response.clusters, vec!["p6m-dev", "p6m-prod"]);
+
+        let empty: AwsEksListClustersResponse =
+            serde_json::from_str(r#"{"clusters": []}"#).unwrap();
+        assert!(empty.clusters.is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
 use serde::{Deserialize, Serialize};
 use std::fmt::{self, Display, Formatter};
 
@@ -15,6 +16,22 @@ pub struct AzureConfig {
     // pub user: AzureUser,
 }
 
+impl AzureConfig {
+    /// Only subscriptions explicitly marked `Disabled` are skipped; a missing or unrecognized state is treated as usable.
+    pub fn is_disabled(&self) -> bool {
+        self.state == Some(AzureAccountState::Disabled)
+    }
+
+    /// Matches a `--subscription` filter against the subscription id or name, ignoring case.
+    pub fn matches_subscription(&self, subscription: &str) -> bool {
+        self.id.eq_ignore_ascii_case(subscription)
+            || self
+                .name
+                .as_deref()
+                .is_some_and(|name| name.eq_ignore_ascii_case(subscription))
+    }
+}
+
 impl Display for AzureConfig {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         write!(
@@ -30,6 +47,9 @@ impl Display for AzureConfig {
 pub enum AzureAccountState {
     Enabled,
     Disabled,
+    /// Any other state the Azure CLI reports (e.g. `Warned`, `PastDue`), so one odd subscription can't fail the whole list.
+    #[serde(other)]
+    Other,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -42,6 +62,14 @@ pub struct AzureAccessToken {
     pub tokenType: String,
 }
 
+impl AzureAccessToken {
+    /// `expiresOn` is reported in the machine's local time, without an offset.
+    pub fn expires_on(&self) -> Option<DateTime<Local>> {
+        let naive = NaiveDateTime::parse_from_str(&self.expiresOn, "%Y-%m-%d %H:%M:%S%.f").ok()?;
+        Local.from_local_datetime(&naive).earliest()
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[allow(non_snake_case)]
 pub struct AzureAksCluster {
@@ -58,3 +86,154 @@ impl Display for AzureAksCluster {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_list() {
+        // Trimmed `az account list` output; fields the model doesn't keep are ignored
+        let configs: Vec<AzureConfig> = serde_json::from_str(
+            r#"[
+                {
+                    "cloudName": "AzureCloud",
+                    "homeTenantId": "11111111-1111-1111-1111-111111111111",
+                    "id": "22222222-2222-2222-2222-222222222222",
+                    "isDefault": true,
+                    "managedByTenants": [{"tenantId": "33333333-3333-3333-3333-333333333333"}],
+                    "name": "p6m-dev",
+                    "state": "Enabled",
+                    "tenantId": "11111111-1111-1111-1111-111111111111",
+                    "user": {"name": "dev@p6m.dev", "type": "user"}
+                },
+                {"id": "44444444-4444-4444-4444-444444444444"}
+            ]"#,
+        )
+        .unwrap();
+
+        let full = &configs[0];
+        assert_eq!(full.cloud_name.as_deref(), Some("AzureCloud"));
+        assert_eq!(
+            full.home_tenant_id.as_deref(),
+            Some("11111111-1111-1111-1111-111111111111")
+        );
+        assert_eq!(full.id, "22222222-2222-2222-2222-222222222222");
+        assert_eq!(full.is_default, Some(true));
+        assert_eq!(full.name.as_deref(), Some("p6m-dev"));
+        assert_eq!(full.state, Some(AzureAccountState::Enabled));
+        assert_eq!(
+            full.tenant_id.as_deref(),
+            Some("11111111-1111-1111-1111-111111111111")
+        );
+
+        let minimal = &configs[1];
+        assert_eq!(minimal.id, "44444444-4444-4444-4444-444444444444");
+        assert!(minimal.cloud_name.is_none());
+        assert!(minimal.home_tenant_id.is_none());
+        assert!(minimal.is_default.is_none());
+        assert!(minimal.name.is_none());
+        assert!(minimal.state.is_none());
+        assert!(minimal.tenant_id.is_none());
+
+        // Serializing keeps the camelCase names, so the output parses back the same
+        let json = serde_json::to_string(&configs).unwrap();
+        assert!(json.contains("\"homeTenantId\""));
+        let round_trip: Vec<AzureConfig> = serde_json::from_str(&json).unwrap();
+        assert_eq!(round_trip[0].tenant_id, full.tenant_id);
+        assert_eq!(round_trip[0].state, full.state);
+        assert_eq!(round_trip[1].id, minimal.id);
+        assert!(round_trip[1].state.is_none());
+    }
+
+    #[test]
+    fn test_get_access_token() {
+        // `az account get-access-token` output; newer CLIs also report `expires_on` as epoch seconds
+        let token: AzureAccessToken = serde_json::from_str(
+            r#"{
+                "accessToken": "eyJ0eXAi-token",
+                "expiresOn": "2024-02-09 10:50:47.000000",
+                "expires_on": 1707475847,
+                "subscription": "22222222-2222-2222-2222-222222222222",
+                "tenant": "11111111-1111-1111-1111-111111111111",
+                "tokenType": "Bearer"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(token.accessToken, "eyJ0eXAi-token");
+        assert_eq!(token.subscription, "22222222-2222-2222-2222-222222222222");
+        assert_eq!(token.tenant, "11111111-1111-1111-1111-111111111111");
+        assert_eq!(token.tokenType, "Bearer");
+        assert!(token.expires_on().is_some());
+
+        let round_trip: AzureAccessToken =
+            serde_json::from_str(&serde_json::to_string(&token).unwrap()).unwrap();
+        assert_eq!(round_trip.expiresOn, token.expiresOn);
+    }
+
+    #[test]
+    fn test_aks_list() {
+        // Shape produced by the `--query` projection in `get_aks_clusters`
+        let clusters: Vec<AzureAksCluster> = serde_json::from_str(
+            r#"[
+                {"ClusterName": "p6m-dev-aks", "ResourceGroup": "p6m-dev-rg"},
+                {"ClusterName": "p6m-prod-aks", "ResourceGroup": "p6m-prod-rg"}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[1].ClusterName, "p6m-prod-aks");
+        assert_eq!(clusters[1].ResourceGroup, "p6m-prod-rg");
+        assert!(serde_json::from_str::<Vec<AzureAksCluster>>(r#"[{"ClusterName": "x"}]"#).is_err());
+    }
+
+    #[test]
+    fn test_missing_or_unknown_state_is_not_disabled() {
+        let configs: Vec<AzureConfig> = serde_json::from_str(
+            r#"[
+                {"id": "1", "name": "no-state"},
+                {"id": "2", "name": "warned", "state": "Warned"},
+                {"id": "3", "name": "enabled", "state": "Enabled"},
+                {"id": "4", "name": "disabled", "state": "Disabled"}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(configs[0].state, None);
+        assert_eq!(configs[1].state, Some(AzureAccountState::Other));
+        let disabled: Vec<bool> = configs.iter().map(AzureConfig::is_disabled).collect();
+        assert_eq!(disabled, vec![false, false, false, true]);
+    }
+
+    #[test]
+    fn test_matches_subscription() {
+        let config: AzureConfig =
+            serde_json::from_str(r#"{"id": "0000-ABCD", "name": "Production"}"#).unwrap();
+
+        assert!(config.matches_subscription("0000-abcd"));
+        assert!(config.matches_subscription("production"));
+        assert!(!config.matches_subscription("Prod"));
+    }
+
+    #[test]
+    fn test_expires_on() {
+        let mut token = AzureAccessToken {
+            accessToken: "token".to_string(),
+            expiresOn: "2024-02-09 10:50:47.000000".to_string(),
+            subscription: "subscription".to_string(),
+            tenant: "tenant".to_string(),
+            tokenType: "Bearer".to_string(),
+        };
+        let expected =
+            NaiveDateTime::parse_from_str("2024-02-09T10:50:47", "%Y-%m-%dT%H:%M:%S").unwrap();
+        assert_eq!(
+            token.expires_on().map(|at| at.naive_local()),
+            Some(expected)
+        );
+
+        token.expiresOn = "tomorrow".to_string();
+        assert_eq!(token.expires_on(), None);
+    }
+}
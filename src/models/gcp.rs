@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+
+/// A project from `gcloud projects list --format=json`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GcpConfig {
+    pub project_id: String,
+    pub project_number: Option<String>,
+    pub name: Option<String>,
+    pub lifecycle_state: Option<GcpProjectState>,
+}
+
+impl Display for GcpConfig {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "GcpConfig: {}({})",
+            self.name.clone().unwrap_or_default(),
+            self.project_id
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum GcpProjectState {
+    Active,
+    DeleteRequested,
+    #[serde(other)]
+    Other,
+}
+
+/// A cluster from `gcloud container clusters list --format=json`, keeping only what a kubeconfig entry needs.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GcpCluster {
+    pub name: String,
+    /// A region or zone, e.g. `us-central1` or `us-central1-a`.
+    pub location: String,
+    pub endpoint: String,
+    #[serde(default)]
+    pub master_auth: GcpMasterAuth,
+}
+
+impl GcpCluster {
+    /// The base64-encoded cluster CA certificate, if gcloud returned one.
+    pub fn ca_data(&self) -> Option<&str> {
+        self.master_auth.cluster_ca_certificate.as_deref()
+    }
+}
+
+impl Display for GcpCluster {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "GKE Cluster: {}({})", self.name, self.location)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct GcpMasterAuth {
+    pub cluster_ca_certificate: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_clusters() {
+        let clusters: Vec<GcpCluster> = serde_json::from_str(
+            r#"[
+                {
+                    "name": "prod",
+                    "location": "us-central1",
+                    "endpoint": "34.1.2.3",
+                    "masterAuth": {"clusterCaCertificate": "LS0tLS1CRUdJTg=="},
+                    "status": "RUNNING",
+                    "currentMasterVersion": "1.29.1-gke.1589000"
+                },
+                {"name": "dev", "location": "us-east1-b", "endpoint": "34.4.5.6"}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0].endpoint, "34.1.2.3");
+        assert_eq!(clusters[0].ca_data(), Some("LS0tLS1CRUdJTg=="));
+        assert_eq!(clusters[0].to_string(), "GKE Cluster: prod(us-central1)");
+        assert_eq!(clusters[1].ca_data(), None);
+    }
+
+    #[test]
+    fn test_parse_projects() {
+        let projects: Vec<GcpConfig> = serde_json::from_str(
+            r#"[
+                {"projectId": "acme-prod", "projectNumber": "123", "name": "Acme Prod", "lifecycleState": "ACTIVE"},
+                {"projectId": "acme-old", "lifecycleState": "DELETE_IN_PROGRESS"}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(projects[0].lifecycle_state, Some(GcpProjectState::Active));
+        assert_eq!(projects[0].to_string(), "GcpConfig: Acme Prod(acme-prod)");
+        assert_eq!(projects[1].lifecycle_state, Some(GcpProjectState::Other));
+    }
+}
@@ -1,4 +1,7 @@
 pub mod artifact;
 pub mod aws;
 pub mod azure;
+// Groundwork for GCP SSO; nothing reads these models yet.
+#[allow(dead_code)]
+pub mod gcp;
 pub mod git;
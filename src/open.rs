@@ -1,13 +1,17 @@
 use anyhow::Error;
 use clap::ArgMatches;
 
-use crate::models::git::GithubLevel;
+use crate::{
+    cli::{self, P6mEnvironment},
+    context::resolve_organization,
+    models::git::GithubLevel,
+};
 
-pub async fn execute(matches: &ArgMatches) -> Result<(), Error> {
-    match matches.subcommand() {
-        Some(("argocd", subaqrgs)) => argocd_page(subaqrgs).await,
-        Some(("artifactory", subargs)) => artifactory_page(subargs).await,
-        Some(("github", _)) => github_page().await,
+pub async fn execute(environment: P6mEnvironment, matches: &ArgMatches) -> Result<(), Error> {
+    match cli::subcommand("p6m open", matches)? {
+        Some(("argocd", subaqrgs)) => argocd_page(&environment, subaqrgs).await,
+        Some(("artifactory", subargs)) => artifactory_page(&environment, subargs).await,
+        Some(("github", subargs)) => github_page(&environment, subargs).await,
         Some((command, _)) => Err(Error::msg(format!(
             "Unimplemented repos command: '{}'",
             command
@@ -18,18 +22,50 @@ pub async fn execute(matches: &ArgMatches) -> Result<(), Error> {
     Ok(())
 }
 
-async fn github_page() -> Result<(), Error> {
-    let org_path = GithubLevel::current()?;
-    webbrowser::open(&org_path.github_url())?;
+/// Repository pages that `open github` can jump to, by flag.
+const GITHUB_SUBPAGES: [(&str, &str); 3] = [
+    ("prs", "pulls"),
+    ("issues", "issues"),
+    ("actions", "actions"),
+];
+
+async fn github_page(environment: &P6mEnvironment, matches: &ArgMatches) -> Result<(), Error> {
+    // Being inside ~/orgs, even at its top, takes precedence over the remembered context
+    let org_path = match GithubLevel::current_or_remote() {
+        Ok(org_path) => org_path,
+        Err(err) => resolve_organization(environment, None).map_err(|_| err)?,
+    };
+
+    let subpage = GITHUB_SUBPAGES
+        .iter()
+        .find(|(flag, _)| matches.get_flag(flag))
+        .map(|(_, path)| *path);
+
+    webbrowser::open(&github_url(&org_path, subpage)?)?;
     Ok(())
 }
 
-async fn argocd_page(matches: &ArgMatches) -> Result<(), Error> {
-    let organization_name = GithubLevel::with_organization(matches.get_one("organization"))?
-        .organization()
-        .unwrap()
-        .name()
-        .to_string();
+fn github_url(org_path: &GithubLevel, subpage: Option<&str>) -> Result<String, Error> {
+    match (org_path, subpage) {
+        (_, None) => Ok(org_path.github_url()),
+        (GithubLevel::Repository(_), Some(subpage)) => {
+            Ok(format!("{}/{}", org_path.github_url(), subpage))
+        }
+        (_, Some(_)) => Err(Error::msg(
+            "--prs, --issues and --actions only work from within a repository in ~/orgs/",
+        )),
+    }
+}
+
+async fn argocd_page(environment: &P6mEnvironment, matches: &ArgMatches) -> Result<(), Error> {
+    let organization_name = resolve_organization(
+        environment,
+        matches.try_get_one("organization").unwrap_or(None),
+    )?
+    .organization()
+    .unwrap()
+    .name()
+    .to_string();
 
     webbrowser::open(&format!(
         "https://{}-argocd.o11n.p6m.run/applications",
@@ -39,12 +75,15 @@ async fn argocd_page(matches: &ArgMatches) -> Result<(), Error> {
     .map_err(|err| err.into())
 }
 
-async fn artifactory_page(matches: &ArgMatches) -> Result<(), Error> {
-    let organization_name = GithubLevel::with_organization(matches.get_one("organization"))?
-        .organization()
-        .unwrap()
-        .name()
-        .to_string();
+async fn artifactory_page(environment: &P6mEnvironment, matches: &ArgMatches) -> Result<(), Error> {
+    let organization_name = resolve_organization(
+        environment,
+        matches.try_get_one("organization").unwrap_or(None),
+    )?
+    .organization()
+    .unwrap()
+    .name()
+    .to_string();
     webbrowser::open(&format!(
         "https://p6m.jfrog.io/ui/packages?projectKey={}",
         organization_name
@@ -52,3 +91,25 @@ async fn artifactory_page(matches: &ArgMatches) -> Result<(), Error> {
     .map(|_| ())
     .map_err(|err| err.into())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::git::{Organization, Repository};
+
+    #[test]
+    fn test_github_url() {
+        let repository = GithubLevel::Repository(Repository::new("p6m-example", "service"));
+        let organization = GithubLevel::Organization(Organization::new("p6m-example"));
+
+        assert_eq!(
+            github_url(&repository, Some("pulls")).unwrap(),
+            format!("{}/pulls", repository.github_url())
+        );
+        assert_eq!(
+            github_url(&organization, None).unwrap(),
+            organization.github_url()
+        );
+        assert!(github_url(&organization, Some("issues")).is_err());
+    }
+}
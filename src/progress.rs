@@ -0,0 +1,95 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{debug, info, log_enabled, Level};
+use std::{
+    fmt::Display,
+    io::{stderr, IsTerminal},
+    sync::Mutex,
+};
+
+/// The bar currently drawn, so the logger can hide it while writing a line.
+static ACTIVE: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// Reports "n/total" progress through a bulk operation.
+///
+/// Draws a progress bar on an interactive terminal, and falls back to prefixing
+/// log lines with the position when stderr isn't a TTY (e.g. in CI).
+pub struct Progress {
+    bar: Option<ProgressBar>,
+    position: usize,
+    len: usize,
+    quiet: bool,
+}
+
+impl Progress {
+    pub fn new(len: usize) -> Self {
+        let bar = (stderr().is_terminal() && log_enabled!(Level::Info)).then(|| {
+            ProgressBar::new(len as u64).with_style(
+                ProgressStyle::with_template("{bar:30} {pos}/{len} {wide_msg}")
+                    .expect("valid progress template"),
+            )
+        });
+
+        if let (Some(bar), Ok(mut active)) = (bar.as_ref(), ACTIVE.lock()) {
+            *active = Some(bar.clone());
+        }
+
+        Self {
+            bar,
+            position: 0,
+            len,
+            quiet: false,
+        }
+    }
+
+    /// Tracks progress without a bar, logging steps only at debug level, for work that runs alongside
+    /// other progress (which would fight over the one bar).
+    pub fn quiet(len: usize) -> Self {
+        Self {
+            bar: None,
+            position: 0,
+            len,
+            quiet: true,
+        }
+    }
+
+    /// Advances by one, describing what's being done for the new item.
+    pub fn step(&mut self, message: impl Display) {
+        self.position += 1;
+        match self.bar.as_ref() {
+            Some(bar) => {
+                bar.set_message(message.to_string());
+                bar.set_position(self.position as u64);
+            }
+            None if self.quiet => debug!("[{}/{}] {}", self.position, self.len, message),
+            None => info!("[{}/{}] {}", self.position, self.len, message),
+        }
+    }
+
+    /// Advances by one without reporting anything.
+    pub fn skip(&mut self) {
+        self.position += 1;
+        if let Some(bar) = self.bar.as_ref() {
+            bar.set_position(self.position as u64);
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if let Some(bar) = self.bar.take() {
+            bar.finish_and_clear();
+            if let Ok(mut active) = ACTIVE.lock() {
+                *active = None;
+            }
+        }
+    }
+}
+
+/// Runs `f` with any active progress bar hidden, so output doesn't tear it.
+pub fn suspend<F: FnOnce() -> R, R>(f: F) -> R {
+    let bar = ACTIVE.lock().ok().and_then(|active| active.clone());
+    match bar {
+        Some(bar) => bar.suspend(f),
+        None => f(),
+    }
+}
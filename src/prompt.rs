@@ -0,0 +1,41 @@
+use anyhow::Error;
+use clap::ArgMatches;
+use inquire::{Confirm, MultiSelect};
+use log::info;
+use std::fmt::Display;
+
+/// Shown under multi-selects. Checked options stay checked while the filter changes, but → replaces the selection
+/// with just the options currently shown.
+const MULTI_SELECT_HELP: &str =
+    "type to filter, space to toggle, → to select all shown, ← to clear, enter to confirm";
+
+/// Asks for confirmation, unless `--yes` was given, in which case the answer is always yes.
+pub fn confirm(message: &str, default: bool, assume_yes: bool) -> Result<bool, Error> {
+    if assume_yes {
+        info!("{message} Yes (--yes)");
+        return Ok(true);
+    }
+
+    Ok(Confirm::new(message).with_default(default).prompt()?)
+}
+
+/// A multi-select that filters its options by whatever is typed, showing `--page-size` options at once if given,
+/// else `default_page_size`.
+pub fn multi_select<'a, T: Display>(
+    message: &'a str,
+    options: Vec<T>,
+    matches: &ArgMatches,
+    default_page_size: usize,
+) -> MultiSelect<'a, T> {
+    MultiSelect::new(message, options)
+        .with_page_size(page_size(matches, default_page_size))
+        .with_keep_filter(true)
+        .with_help_message(MULTI_SELECT_HELP)
+}
+
+fn page_size(matches: &ArgMatches, default: usize) -> usize {
+    matches
+        .get_one::<u16>("page-size")
+        .map(|size| *size as usize)
+        .unwrap_or(default)
+}
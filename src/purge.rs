@@ -1,12 +1,15 @@
 use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
 
+use crate::cli;
 use clap::ArgMatches;
 use globset::{Glob, GlobSetBuilder};
 use log::{debug, error, info, trace, warn};
 use walkdir::{DirEntry, WalkDir};
 
 pub fn execute(matches: &ArgMatches) -> Result<(), anyhow::Error> {
-    match matches.subcommand() {
+    match cli::subcommand("p6m purge", matches)? {
         Some(("ide-files", subargs)) => purge_ide_files(subargs),
         Some(("maven", subargs)) => purge_maven(subargs),
         Some((command, _)) => error!("Unimplemented purge command: '{}'", command),
@@ -25,11 +28,17 @@ pub fn purge_ide_files(matches: &ArgMatches) {
     ide_files_glob_builder.add(Glob::new("**/.settings").unwrap());
 
     let dry_run = matches.get_flag("dry-run");
+    let skip_dirs: Vec<&String> = matches
+        .get_many::<String>("skip-dir")
+        .unwrap_or_default()
+        .collect();
+    let gitignore = matches.get_flag("gitignore");
 
     if dry_run {
         warn!("Dry Run: No files will be deleted...");
     }
     let ide_files_glob = ide_files_glob_builder.build().unwrap();
+    let mut reclaimed = 0;
 
     let mut it = WalkDir::new(".").follow_links(false).into_iter();
     loop {
@@ -40,8 +49,19 @@ pub fn purge_ide_files(matches: &ArgMatches) {
         };
 
         let path = entry.path();
+        // Checked before matching, so IDE files inside dependencies are left alone
+        let skipped = skip_dirs
+            .iter()
+            .any(|dir| entry.file_name().to_str() == Some(dir.as_str()));
+        if entry.file_type().is_dir() && entry.depth() > 0 && skipped {
+            debug!("Skipping: {}", path.display());
+            it.skip_current_dir();
+            continue;
+        }
+
         if ide_files_glob.is_match(path) {
             info!("Removing {}", path.display());
+            reclaimed += disk_usage(path);
             if !dry_run {
                 if path.is_file() {
                     fs::remove_file(path)
@@ -57,6 +77,13 @@ pub fn purge_ide_files(matches: &ArgMatches) {
             continue;
         }
 
+        // IDE files are often git-ignored themselves, so this only applies to what's left
+        if gitignore && entry.file_type().is_dir() && entry.depth() > 0 && is_git_ignored(path) {
+            debug!("Skipping: {}", path.display());
+            it.skip_current_dir();
+            continue;
+        }
+
         if is_hidden(&entry) {
             if entry.file_type().is_dir() {
                 debug!("Skipping: {}", entry.path().display());
@@ -67,6 +94,11 @@ pub fn purge_ide_files(matches: &ArgMatches) {
 
         trace!("Considering: {}", entry.path().display());
     }
+
+    match dry_run {
+        true => info!("Would reclaim {}", format_size(reclaimed)),
+        false => info!("Reclaimed {}", format_size(reclaimed)),
+    }
 }
 
 fn purge_maven(matches: &ArgMatches) {
@@ -81,8 +113,10 @@ fn purge_maven(matches: &ArgMatches) {
             purge_dir.push(path.replace('.', "/"));
             if purge_dir.exists() {
                 info!("Purging Maven cache directory: {:?}", purge_dir.as_os_str());
+                let reclaimed = disk_usage(purge_dir);
                 fs::remove_dir_all(&purge_dir)
                     .unwrap_or_else(|_| panic!("Error deleting {:?}", purge_dir));
+                info!("Reclaimed {}", format_size(reclaimed));
             } else {
                 warn!("Maven cache directory does not exist: {:?}", purge_dir);
             }
@@ -92,6 +126,51 @@ fn purge_maven(matches: &ArgMatches) {
     }
 }
 
+/// The total size in bytes of the files at or below `path`, not following symlinks.
+fn disk_usage(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Formats a byte count with binary units, e.g. `1.5 MB`.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["bytes", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} {}", bytes, UNITS[0]),
+        _ => format!("{:.1} {}", size, UNITS[unit]),
+    }
+}
+
+/// Asks git whether `path` is ignored by the `.gitignore` of the repository it's in. Paths outside a repository aren't.
+fn is_git_ignored(path: &Path) -> bool {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return false;
+    };
+    Command::new("git")
+        .arg("-C")
+        .arg(parent)
+        .args(["check-ignore", "-q"])
+        .arg(name)
+        // Outside a git repository git complains on stderr; that just means nothing is ignored
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
 fn is_hidden(entry: &DirEntry) -> bool {
     entry
         .file_name()
@@ -99,3 +178,30 @@ fn is_hidden(entry: &DirEntry) -> bool {
         .map(|s| s.starts_with('.') && !s.eq("."))
         .unwrap_or(false)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 bytes");
+        assert_eq!(format_size(1023), "1023 bytes");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(5 * 1024 * 1024 * 1024), "5.0 GB");
+    }
+
+    #[test]
+    fn test_disk_usage() {
+        let temp_dir = TempDir::new("purge");
+        let dir = temp_dir.as_std_path();
+        fs::create_dir_all(dir.join(".idea")).unwrap();
+        fs::write(dir.join(".idea").join("workspace.xml"), [0u8; 100]).unwrap();
+        fs::write(dir.join("app.iml"), [0u8; 20]).unwrap();
+
+        assert_eq!(disk_usage(&dir.join(".idea")), 100);
+        assert_eq!(disk_usage(&dir.join("app.iml")), 20);
+        assert_eq!(disk_usage(dir), 120);
+    }
+}
@@ -1,21 +1,33 @@
 use anyhow::{Context, Error};
+use base64::{engine, Engine};
+use chrono::Utc;
 use clap::ArgMatches;
-use inquire::{Confirm, MultiSelect};
+use futures_util::{stream, StreamExt};
 use log::{error, info, warn};
 use octocrab::models::orgs::Organization;
-use octocrab::{Octocrab, Page};
+use octocrab::{FromResponse, Octocrab, Page};
+use serde::de::DeserializeOwned;
 use serde::Serialize;
+use std::future::Future;
 use std::process::Stdio;
+use std::time::Duration;
 use tokio::fs;
 use tokio::process::Command;
+use tokio::time::sleep;
 
+use crate::cli;
 use crate::models::git::{org_directory, GithubLevel, Repository};
+use crate::progress::Progress;
+use crate::prompt;
 
 pub async fn execute(matches: &ArgMatches) -> Result<(), Error> {
-    match matches.subcommand() {
-        Some(("pull", subargs)) => pull(subargs).await,
+    match cli::subcommand("p6m repositories", matches)? {
+        Some(("pull", subargs)) => pull(subargs, PullMode::Pull).await,
+        Some(("sync", subargs)) => pull(subargs, PullMode::Sync).await,
+        Some(("fetch", subargs)) => pull(subargs, PullMode::Fetch).await,
         Some(("push", subargs)) => push(subargs).await,
         Some(("prune", subargs)) => prune(subargs).await,
+        Some(("list", subargs)) => list(subargs),
         Some(("delete", subargs)) => delete(subargs).await,
         Some((command, _)) => Err(Error::msg(format!(
             "Unimplemented repos command: '{}'",
@@ -27,104 +39,536 @@ pub async fn execute(matches: &ArgMatches) -> Result<(), Error> {
     Ok(())
 }
 
-async fn pull(matches: &ArgMatches) -> Result<(), Error> {
-    let client = create_octocrab()?;
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PullMode {
+    /// Clone missing repos, and with `--all` also pull existing ones
+    Pull,
+    /// Clone missing repos and fast-forward every clean existing one
+    Sync,
+    /// Update remote-tracking refs of existing repos, leaving working trees alone
+    Fetch,
+}
+
+async fn pull(matches: &ArgMatches, mode: PullMode) -> Result<(), Error> {
+    let github = create_github().await?;
 
-    if let Some(org_name) = matches.get_one::<String>("organization-name") {
-        pull_organization(&client, matches, org_name).await?
+    let results = if let Some(org_name) = matches.get_one::<String>("organization-name") {
+        pull_organization(&github, matches, mode, org_name).await?
     } else if let Ok(org_path) = GithubLevel::current() {
         match org_path {
-            GithubLevel::Enterprise => pull_organizations(&client, matches).await?,
+            GithubLevel::Enterprise => pull_organizations(&github, matches, mode).await?,
             GithubLevel::Organization(organization) => {
-                pull_organization(&client, matches, organization.name()).await?
+                pull_organization(&github, matches, mode, organization.name()).await?
             }
             GithubLevel::Repository(repository) => {
-                pull_organization(&client, matches, repository.organization().name()).await?
+                pull_organization(&github, matches, mode, repository.organization().name()).await?
             }
         }
     } else {
-        pull_organizations(&client, matches).await?
+        pull_organizations(&github, matches, mode).await?
+    };
+
+    let output = matches
+        .try_get_one::<Output>("output")
+        .ok()
+        .flatten()
+        .copied()
+        .unwrap_or(Output::Default);
+    if output == Output::Json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
     }
 
+    pull_outcome(&results, output)
+}
+
+/// What a pull did with one repo, as reported by `--output json`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum PullAction {
+    Cloned,
+    Pulled,
+    Fetched,
+    Skipped,
+    Failed,
+}
+
+#[derive(Debug, Serialize)]
+struct PullResult {
+    organization: String,
+    /// `None` when the whole organization failed, e.g. because its repos couldn't be listed
+    repository: Option<String>,
+    action: PullAction,
+    /// Why the repo was skipped or failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+    /// Set under `--dry-run`, where `action` is what would have been done rather than what was
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    dry_run: bool,
+}
+
+impl PullResult {
+    fn new(repository: &Repository, action: PullAction) -> Self {
+        PullResult {
+            organization: repository.organization().name().to_string(),
+            repository: Some(repository.name().to_string()),
+            action,
+            detail: None,
+            dry_run: false,
+        }
+    }
+
+    /// An organization that couldn't be pulled at all, e.g. because its repos couldn't be listed.
+    fn failed_organization(org_name: &str, err: &Error) -> Self {
+        PullResult {
+            organization: org_name.to_string(),
+            repository: None,
+            action: PullAction::Failed,
+            detail: Some(format!("{:#}", err)),
+            dry_run: false,
+        }
+    }
+
+    fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+}
+
+/// Organizations that couldn't be pulled always fail the command. Failed repos only do with `--output json`, since
+/// a pipeline reading the report has no log to notice them in.
+fn pull_outcome(results: &[PullResult], output: Output) -> Result<(), Error> {
+    let failed = |result: &&PullResult| result.action == PullAction::Failed;
+
+    let failed_orgs: Vec<&str> = results
+        .iter()
+        .filter(failed)
+        .filter(|result| result.repository.is_none())
+        .map(|result| result.organization.as_str())
+        .collect();
+    if !failed_orgs.is_empty() {
+        return Err(Error::msg(format!(
+            "Unable to pull {}",
+            failed_orgs.join(", ")
+        )));
+    }
+
+    let failed_repos = results.iter().filter(failed).count();
+    if output == Output::Json && failed_repos > 0 {
+        return Err(Error::msg(format!("{} repos failed", failed_repos)));
+    }
     Ok(())
 }
 
-async fn pull_organizations(client: &Octocrab, matches: &ArgMatches) -> Result<(), Error> {
-    let org_first_page = client.list_orgs().await?;
+/// Longest we'll sleep waiting for the GitHub rate limit to reset before giving up.
+const RATE_LIMIT_MAX_WAIT: Duration = Duration::from_secs(15 * 60);
+const RATE_LIMIT_RETRIES: usize = 3;
 
-    let orgs: Vec<Organization> = client
-        .all_pages(org_first_page)
-        .await?
+/// Lists every repo in an organization, waiting out the rate limit if it's hit midway.
+async fn list_org_repos(
+    client: &Octocrab,
+    org_name: &str,
+    per_page: u8,
+) -> octocrab::Result<Vec<octocrab::models::Repository>> {
+    all_pages(
+        client,
+        format!("/orgs/{org_name}/repos?type=all&per_page={per_page}"),
+    )
+    .await
+}
+
+/// Fetches a listing page by page, so a rate limit hit midway only retries the page it was hit on.
+async fn all_pages<T: DeserializeOwned>(
+    client: &Octocrab,
+    first_page: String,
+) -> octocrab::Result<Vec<T>> {
+    let mut items = vec![];
+    let mut uri = Some(first_page);
+    while let Some(page_uri) = uri {
+        let response = with_rate_limit(|| client._get(page_uri.as_str())).await?;
+        let mut page =
+            Page::<T>::from_response(octocrab::map_github_error(response).await?).await?;
+        items.append(&mut page.take_items());
+        uri = page.next.map(|next| next.to_string());
+    }
+    Ok(items)
+}
+
+/// Sends a GitHub API request, and if the response says the rate limit was hit, waits for it
+/// to reset and sends the request again. The response is returned as is otherwise, errors included.
+async fn with_rate_limit<B, F, Fut>(mut send: F) -> octocrab::Result<http::Response<B>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = octocrab::Result<http::Response<B>>>,
+{
+    let mut retries = 0;
+    loop {
+        let response = send().await?;
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse::<i64>().ok())
+        };
+        let wait = match rate_limit_wait(response.status().as_u16(), header, Utc::now().timestamp())
+        {
+            Some(wait) if retries < RATE_LIMIT_RETRIES => wait,
+            _ => return Ok(response),
+        };
+        if wait > RATE_LIMIT_MAX_WAIT {
+            warn!(
+                "GitHub API rate limit reached; it resets in {} minutes, which is too long to wait",
+                wait.as_secs() / 60
+            );
+            return Ok(response);
+        }
+
+        warn!(
+            "GitHub API rate limit reached; waiting {} seconds for it to reset",
+            wait.as_secs()
+        );
+        sleep(wait).await;
+        retries += 1;
+    }
+}
+
+/// How long to wait before retrying a rate limited response, or [None] if it wasn't rate limited.
+///
+/// The primary limit is spent when `x-ratelimit-remaining` is 0, and resets at `x-ratelimit-reset`.
+/// Secondary limits send `retry-after`, or nothing at all with a 429, for which GitHub asks clients
+/// to wait at least a minute. A 403 without those headers is a permissions problem, not a limit.
+fn rate_limit_wait(
+    status: u16,
+    header: impl Fn(&str) -> Option<i64>,
+    now: i64,
+) -> Option<Duration> {
+    if status != 403 && status != 429 {
+        return None;
+    }
+    let seconds = |seconds: i64| Duration::from_secs(seconds.max(0) as u64);
+
+    if let Some(retry_after) = header("retry-after") {
+        return Some(seconds(retry_after));
+    }
+    match (header("x-ratelimit-remaining"), header("x-ratelimit-reset")) {
+        (Some(0), Some(reset)) => Some(seconds(reset - now + 1)),
+        (Some(0), None) => Some(seconds(60)),
+        _ if status == 429 => Some(seconds(60)),
+        _ => None,
+    }
+}
+
+/// Page size for GitHub listings; GitHub caps it at 100.
+fn per_page(matches: &ArgMatches) -> u8 {
+    matches
+        .try_get_one::<u8>("per-page")
+        .ok()
+        .flatten()
+        .copied()
+        .unwrap_or(25)
+}
+
+/// Pulls up to `--concurrency` organizations at once, reporting each as it finishes.
+/// Prune prompts wait until every organization is done, so they aren't interleaved.
+async fn pull_organizations(
+    github: &Github,
+    matches: &ArgMatches,
+    mode: PullMode,
+) -> Result<Vec<PullResult>, Error> {
+    let client = &github.client;
+    let orgs: Vec<Organization> =
+        all_pages(client, format!("/user/orgs?per_page={}", per_page(matches))).await?;
+    let excluded = excluded_orgs(
+        matches
+            .try_get_many::<String>("exclude-org")
+            .ok()
+            .flatten()
+            .map(|orgs| orgs.cloned().collect()),
+        std::env::var("P6M_EXCLUDED_ORGS").ok(),
+    );
+    let orgs: Vec<Organization> = orgs
         .into_iter()
-        .filter(|org| org.login != "p6m-dev") // Skip p6m-dev
+        .filter(|org| {
+            let skip = excluded
+                .iter()
+                .any(|excluded| excluded.eq_ignore_ascii_case(&org.login));
+            if skip {
+                info!("Skipping excluded organization {}", org.login);
+            }
+            !skip
+        })
         .collect();
 
-    for org in orgs {
-        pull_organization(client, matches, &org.login).await?;
+    let concurrency = matches
+        .try_get_one::<u8>("concurrency")
+        .ok()
+        .flatten()
+        .map(|concurrency| *concurrency as usize)
+        .unwrap_or(1);
+    let concurrent = concurrency > 1;
+
+    // Only one bar can be drawn: the organizations' when they run concurrently, otherwise each organization's repos'
+    let mut progress = match concurrent {
+        true => Progress::new(orgs.len()),
+        false => Progress::quiet(orgs.len()),
+    };
+    let mut results = stream::iter(orgs.iter().map(|org| org.login.clone()))
+        .map(|org_name| async move {
+            let result =
+                pull_organization_repos(github, matches, mode, &org_name, concurrent).await;
+            (org_name, result)
+        })
+        .buffer_unordered(concurrency);
+
+    let mut pulled = vec![];
+    let mut pull_results = vec![];
+    while let Some((org_name, result)) = results.next().await {
+        match result {
+            Ok(org_results) => {
+                progress.step(format!("Finished {}", org_name));
+                pulled.push(org_name);
+                pull_results.extend(org_results);
+            }
+            Err(err) => {
+                progress.step(format!("Failed {}", org_name));
+                error!("Unable to pull {}: {:#}", org_name, err);
+                pull_results.push(PullResult::failed_organization(&org_name, &err));
+            }
+        }
+    }
+    drop(results);
+    drop(progress);
+
+    if prune_requested(matches, mode) {
+        for org_name in &pulled {
+            prune_organization(
+                client,
+                org_name,
+                matches.get_flag("dry-run"),
+                matches.get_flag("yes"),
+                matches,
+            )
+            .await?;
+        }
     }
 
-    Ok(())
+    Ok(pull_results)
+}
+
+/// Organizations left out of enterprise-wide pulls unless `--exclude-org` or `P6M_EXCLUDED_ORGS` says otherwise.
+const DEFAULT_EXCLUDED_ORGS: [&str; 1] = ["p6m-dev"];
+
+/// `--exclude-org` values if given, else the comma-separated `P6M_EXCLUDED_ORGS`, else the defaults.
+/// An empty value excludes nothing.
+fn excluded_orgs(flag: Option<Vec<String>>, env: Option<String>) -> Vec<String> {
+    let orgs: Vec<String> = match (flag, env) {
+        (Some(orgs), _) => orgs,
+        (None, Some(orgs)) => orgs.split(',').map(String::from).collect(),
+        (None, None) => DEFAULT_EXCLUDED_ORGS
+            .iter()
+            .map(|org| org.to_string())
+            .collect(),
+    };
+    orgs.iter()
+        .map(|org| org.trim())
+        .filter(|org| !org.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn prune_requested(matches: &ArgMatches, mode: PullMode) -> bool {
+    mode == PullMode::Pull && matches.get_flag("prune")
 }
 
 async fn pull_organization(
-    client: &Octocrab,
+    github: &Github,
     matches: &ArgMatches,
+    mode: PullMode,
     org_name: &str,
-) -> Result<(), Error> {
+) -> Result<Vec<PullResult>, Error> {
+    let results = match pull_organization_repos(github, matches, mode, org_name, false).await {
+        Ok(results) => results,
+        Err(err) => {
+            error!("Unable to pull {}: {:#}", org_name, err);
+            return Ok(vec![PullResult::failed_organization(org_name, &err)]);
+        }
+    };
+
+    if prune_requested(matches, mode) {
+        prune_organization(
+            &github.client,
+            org_name,
+            matches.get_flag("dry-run"),
+            matches.get_flag("yes"),
+            matches,
+        )
+        .await?;
+    }
+
+    Ok(results)
+}
+
+/// Clones, pulls or fetches one organization's repos. With `quiet`, per-repo progress is only logged at debug level.
+async fn pull_organization_repos(
+    github: &Github,
+    matches: &ArgMatches,
+    mode: PullMode,
+    org_name: &str,
+    quiet: bool,
+) -> Result<Vec<PullResult>, Error> {
+    let client = &github.client;
     let dry_run = matches.get_flag("dry-run");
-    let all = matches.get_flag("all");
-    let prune_flag = matches.get_flag("prune");
+    let default_branch_only = mode == PullMode::Pull && matches.get_flag("default-branch-only");
+    let (all, force) = match mode {
+        PullMode::Pull => (
+            matches.get_flag("all") || default_branch_only,
+            matches.get_flag("force"),
+        ),
+        PullMode::Sync => (true, false),
+        PullMode::Fetch => (false, false),
+    };
+    let protocol = matches
+        .try_get_one::<Protocol>("protocol")
+        .ok()
+        .flatten()
+        .copied()
+        .unwrap_or(Protocol::Ssh);
+    let branch = matches
+        .try_get_one::<String>("branch")
+        .ok()
+        .flatten()
+        .map(String::as_str);
+    let clone_options = CloneOptions {
+        token: &github.token,
+        branch,
+        depth: match matches.try_get_one::<bool>("shallow") {
+            Ok(Some(true)) => Some(
+                matches
+                    .try_get_one::<u32>("depth")
+                    .ok()
+                    .flatten()
+                    .copied()
+                    .unwrap_or(1),
+            ),
+            _ => None,
+        },
+    };
 
     let org_directory = org_directory(org_name);
     fs::create_dir_all(&org_directory).await?;
 
-    let repos_first_page = client
-        .orgs(org_name)
-        .list_repos()
-        .repo_type(octocrab::params::repos::Type::All)
-        .per_page(25)
-        .send()
-        .await?;
+    let repos = list_org_repos(client, org_name, per_page(matches)).await?;
 
-    let repos = client.all_pages(repos_first_page).await?;
+    let mut results = vec![];
+    let mut off_default_branch = vec![];
+    let mut progress = match quiet {
+        true => Progress::quiet(repos.len()),
+        false => Progress::new(repos.len()),
+    };
 
     for repo in &repos {
         let repository = Repository::new(org_name, &repo.name);
 
+        if mode == PullMode::Fetch {
+            if !repository.local_path().exists() {
+                progress.skip();
+                results.push(
+                    PullResult::new(&repository, PullAction::Skipped).with_detail("not cloned"),
+                );
+                continue;
+            }
+
+            progress.step(format!("Fetching {}", repository));
+            if !dry_run {
+                if let Err(err) = run_git(&repository, &["fetch", "--all", "--prune"]).await {
+                    error!("{err}");
+                    results.push(
+                        PullResult::new(&repository, PullAction::Failed)
+                            .with_detail(err.to_string()),
+                    );
+                    continue;
+                }
+            }
+            results.push(PullResult::new(&repository, PullAction::Fetched).with_dry_run(dry_run));
+            continue;
+        }
+
+        let needs_branch = !repository.local_path().exists() || all;
+
+        if let (Some(branch), true) = (branch, needs_branch) {
+            match branch_exists(client, org_name, &repo.name, branch).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    progress.skip();
+                    warn!("Skipping {}: it has no branch {}", repository, branch);
+                    results.push(
+                        PullResult::new(&repository, PullAction::Skipped)
+                            .with_detail(format!("no branch {}", branch)),
+                    );
+                    continue;
+                }
+                Err(err) => {
+                    progress.skip();
+                    let detail = format!("unable to look up branch {}: {}", branch, err);
+                    error!("{}: {}", repository, detail);
+                    results
+                        .push(PullResult::new(&repository, PullAction::Failed).with_detail(detail));
+                    continue;
+                }
+            }
+        }
+
         if !repository.local_path().exists() {
-            info!("Cloning {}", repository);
+            progress.step(format!("Cloning {}", repository));
             if !dry_run {
-                let result = Command::new("git")
-                    .stdout(Stdio::null())
-                    .stderr(Stdio::null())
-                    .arg("-C")
-                    .arg(repository.local_path().parent().unwrap())
-                    .arg("clone")
-                    .arg(repo.ssh_url.as_ref().unwrap())
-                    .arg(repository.local_path())
-                    .status()
-                    .await;
+                if let Err(err) =
+                    clone_repository(repo, &repository, protocol, &clone_options).await
+                {
+                    error!("{err}");
+                    results.push(PullResult::new(&repository, PullAction::Failed).with_detail(err));
+                    continue;
+                }
+            }
+            results.push(PullResult::new(&repository, PullAction::Cloned).with_dry_run(dry_run));
+        } else if all {
+            if !force && is_dirty(&repository).await {
+                progress.skip();
+                warn!("Skipping {}: it has local changes", repository);
+                results.push(
+                    PullResult::new(&repository, PullAction::Skipped).with_detail("local changes"),
+                );
+                continue;
+            }
 
-                match result {
-                    Ok(code) => match code.code() {
-                        Some(code) if code != 0 => {
-                            let cmd = format!(
-                                "git -C {:?} clone {:?} {:?}",
-                                repository.local_path().parent().unwrap(),
-                                &repo.ssh_url.as_ref().unwrap(),
-                                repository.local_path()
-                            );
-                            error!("Error cloning {:?}: Code {}. Try running command directly for more detailed error message. {}", repository.local_path(), code, cmd);
-                        }
-                        _ => {}
-                    },
-                    Err(err) => {
-                        error!("Error cloning {:?}: {}", repository.local_path(), err);
+            let default_branch = repo.default_branch.as_deref();
+            let checkout_branch = branch.or(default_branch.filter(|_| default_branch_only));
+            if let Some(checkout_branch) = checkout_branch {
+                progress.step(format!(
+                    "Checking out {} in {}",
+                    checkout_branch, repository
+                ));
+                if !dry_run {
+                    let checkout = async {
+                        run_git(&repository, &["fetch", "origin", checkout_branch]).await?;
+                        run_git(&repository, &["checkout", checkout_branch]).await
+                    };
+                    if let Err(err) = checkout.await {
+                        error!("{err}");
+                        results.push(
+                            PullResult::new(&repository, PullAction::Failed)
+                                .with_detail(err.to_string()),
+                        );
+                        continue;
                     }
                 }
             }
-        } else if all {
-            info!("Pulling {}", repository);
+
+            progress.step(format!("Pulling {}", repository));
             if !dry_run {
                 let result = Command::new("git")
                     .stdout(Stdio::null())
@@ -132,45 +576,281 @@ async fn pull_organization(
                     .arg("-C")
                     .arg(repository.local_path())
                     .arg("pull")
+                    .args(match mode {
+                        PullMode::Sync => vec!["--ff-only"],
+                        PullMode::Pull | PullMode::Fetch => vec![],
+                    })
                     .status()
                     .await;
-                match result {
+                let failure = match result {
                     Ok(code) => match code.code() {
-                        Some(code) if code != 0 => {
-                            error!("Error pulling {:?}: Code {}", repository.local_path(), code);
-                        }
-                        _ => {}
+                        Some(code) if code != 0 => Some(format!(
+                            "Error pulling {:?}: Code {}",
+                            repository.local_path(),
+                            code
+                        )),
+                        _ => None,
                     },
-                    Err(err) => {
-                        error!("Error pulling {:?}: {}", repository.local_path(), err);
+                    Err(err) => Some(format!(
+                        "Error pulling {:?}: {}",
+                        repository.local_path(),
+                        err
+                    )),
+                };
+                if let Some(failure) = failure {
+                    error!("{failure}");
+                    results.push(
+                        PullResult::new(&repository, PullAction::Failed).with_detail(failure),
+                    );
+                    continue;
+                }
+            }
+            results.push(PullResult::new(&repository, PullAction::Pulled).with_dry_run(dry_run));
+
+            // With --branch, being off the default branch is the point
+            if branch.is_none() {
+                if let (Some(default_branch), Some(current)) =
+                    (default_branch, current_branch(&repository).await)
+                {
+                    if current != default_branch {
+                        off_default_branch.push(format!(
+                            "{} is on {}, not {}",
+                            repository, current, default_branch
+                        ));
                     }
                 }
             }
+        } else {
+            progress.skip();
+            results.push(
+                PullResult::new(&repository, PullAction::Skipped).with_detail("already cloned"),
+            );
+        }
+    }
+
+    drop(progress);
+    if !off_default_branch.is_empty() {
+        warn!(
+            "{} repos in {} aren't on their default branch:\n  {}",
+            off_default_branch.len(),
+            org_name,
+            off_default_branch.join("\n  ")
+        );
+    }
+
+    Ok(results)
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Protocol {
+    /// Clone over SSH, falling back to HTTPS if that fails
+    Ssh,
+    /// Clone over HTTPS, authenticated with GITHUB_TOKEN or the GitHub App
+    Https,
+}
+
+/// Whether the branch exists on GitHub, checked before cloning or checking it out for a clean error.
+/// Only a 404 means it doesn't; any other failure is returned, since the branch may well exist.
+async fn branch_exists(
+    client: &Octocrab,
+    org_name: &str,
+    repo_name: &str,
+    branch: &str,
+) -> octocrab::Result<bool> {
+    let route = format!("/repos/{org_name}/{repo_name}/branches/{branch}");
+    let response = with_rate_limit(|| client._get(route.as_str())).await?;
+    match response.status() {
+        http::StatusCode::NOT_FOUND => Ok(false),
+        _ => octocrab::map_github_error(response).await.map(|_| true),
+    }
+}
+
+/// How missing repos are cloned.
+struct CloneOptions<'a> {
+    /// Authenticates HTTPS clones as the same user or App installation as the API client
+    token: &'a str,
+    /// Branch to check out instead of the default branch
+    branch: Option<&'a str>,
+    /// Commits of history to fetch, for a shallow clone
+    depth: Option<u32>,
+}
+
+async fn clone_repository(
+    repo: &octocrab::models::Repository,
+    repository: &Repository,
+    protocol: Protocol,
+    options: &CloneOptions<'_>,
+) -> Result<(), String> {
+    if protocol == Protocol::Ssh {
+        match repo.ssh_url.as_ref() {
+            Some(ssh_url) => match git_clone(repository, ssh_url, None, options).await {
+                Ok(()) => return Ok(()),
+                Err(err) => {
+                    warn!("{err}");
+                    warn!("Retrying {} over HTTPS", repository);
+                }
+            },
+            None => warn!("{} has no SSH URL; cloning over HTTPS", repository),
         }
     }
 
-    if prune_flag {
-        prune_organization(client, org_name, dry_run).await?;
+    let Some(clone_url) = repo.clone_url.as_ref() else {
+        return Err(format!("Error cloning {}: no HTTPS clone URL", repository));
+    };
+
+    // Pass the token as a header for this command only, so it isn't saved in .git/config
+    let header = format!(
+        "http.https://github.com/.extraheader=AUTHORIZATION: basic {}",
+        engine::general_purpose::STANDARD.encode(format!("x-access-token:{}", options.token))
+    );
+
+    git_clone(repository, clone_url.as_str(), Some(&header), options).await
+}
+
+async fn git_clone(
+    repository: &Repository,
+    url: &str,
+    config: Option<&str>,
+    options: &CloneOptions<'_>,
+) -> Result<(), String> {
+    let parent = repository.local_path().parent().unwrap().to_path_buf();
+
+    let mut command = Command::new("git");
+    command.stdout(Stdio::null()).stderr(Stdio::null());
+    if let Some(config) = config {
+        command.arg("-c").arg(config);
+    }
+
+    command.arg("-C").arg(&parent).arg("clone");
+    if let Some(branch) = options.branch {
+        command.arg("--branch").arg(branch);
+    }
+    if let Some(depth) = options.depth {
+        command.arg("--depth").arg(depth.to_string());
+    }
+
+    let result = command.arg(url).arg(repository.local_path()).status().await;
+
+    match result {
+        Ok(code) => match code.code() {
+            Some(code) if code != 0 => {
+                let cmd = format!(
+                    "git -C {:?} clone {:?} {:?}",
+                    parent,
+                    url,
+                    repository.local_path()
+                );
+                Err(format!("Error cloning {:?}: Code {}. Try running command directly for more detailed error message. {}", repository.local_path(), code, cmd))
+            }
+            _ => Ok(()),
+        },
+        Err(err) => Err(format!(
+            "Error cloning {:?}: {}",
+            repository.local_path(),
+            err
+        )),
+    }
+}
+
+/// Runs a git command in the repository, failing with the step and git's stderr if it exits non-zero.
+async fn run_git(repository: &Repository, args: &[&str]) -> Result<(), Error> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repository.local_path())
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .with_context(|| format!("Unable to run `git {}` in {}", args[0], repository))?;
+
+    if !output.status.success() {
+        return Err(Error::msg(format!(
+            "`git {}` failed in {}: {}",
+            args.join(" "),
+            repository,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
     }
 
     Ok(())
 }
 
+/// A repository is dirty if `git status --porcelain` reports anything, or can't be run at all.
+async fn is_dirty(repository: &Repository) -> bool {
+    match Command::new("git")
+        .arg("-C")
+        .arg(repository.local_path())
+        .arg("status")
+        .arg("--porcelain")
+        .output()
+        .await
+    {
+        Ok(output) => !output.status.success() || !output.stdout.is_empty(),
+        Err(_) => true,
+    }
+}
+
+async fn current_branch(repository: &Repository) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repository.local_path())
+        .arg("rev-parse")
+        .arg("--abbrev-ref")
+        .arg("HEAD")
+        .output()
+        .await
+        .ok()?;
+
+    match output.status.success() {
+        true => Some(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+        false => None,
+    }
+}
+
+/// Branch created by `repos push` when it initializes a repository and no `--branch` is given.
+const DEFAULT_BRANCH: &str = "main";
+
+struct PushOptions {
+    dry_run: bool,
+    message: String,
+    branch: Option<String>,
+    public: bool,
+    has_issues: bool,
+    has_wiki: bool,
+}
+
+impl PushOptions {
+    fn from_matches(matches: &ArgMatches) -> Self {
+        Self {
+            dry_run: matches.get_flag("dry-run"),
+            message: matches
+                .get_one::<String>("message")
+                .cloned()
+                .unwrap_or_else(|| "initial commit".to_string()),
+            branch: matches.get_one::<String>("branch").cloned(),
+            public: matches.get_flag("public"),
+            has_issues: matches.get_flag("enable-issues"),
+            has_wiki: matches.get_flag("enable-wiki"),
+        }
+    }
+}
+
 async fn push(matches: &ArgMatches) -> Result<(), Error> {
-    let dry_run = matches.get_flag("dry-run");
+    let options = PushOptions::from_matches(matches);
+    let assume_yes = matches.get_flag("yes");
     let all = matches.get_flag("all");
     let org_path = GithubLevel::current()?;
+    let octocrab = create_octocrab().await?;
 
     if let Some(repository) = org_path.repository() {
-        let confirmed = Confirm::new(&format!(
-            "Are you sure you want to push {}?",
-            org_path.github_url()
-        ))
-        .with_default(true)
-        .prompt()?;
+        let confirmed = prompt::confirm(
+            &format!("Are you sure you want to push {}?", org_path.github_url()),
+            true,
+            assume_yes,
+        )?;
 
         if confirmed {
-            push_repository(&repository, dry_run).await?;
+            push_repository(&octocrab, &repository, &options).await?;
         }
     } else if let Some(organization) = org_path.organization() {
         let repos = organization
@@ -178,17 +858,18 @@ async fn push(matches: &ArgMatches) -> Result<(), Error> {
             .filter(|repo| all || !repo.has_path(".git"))
             .collect::<Vec<Repository>>();
 
-        if let Ok(selected_repositories) = MultiSelect::new("Repos to push:", repos)
-            .with_page_size(25)
-            .prompt()
+        if let Ok(selected_repositories) =
+            prompt::multi_select("Repos to push:", repos, matches, 25).prompt()
         {
-            let confirmed = Confirm::new("Are you sure you want to push these directories?")
-                .with_default(false)
-                .prompt()?;
+            let confirmed = prompt::confirm(
+                "Are you sure you want to push these directories?",
+                false,
+                assume_yes,
+            )?;
 
             if confirmed {
                 for repository in selected_repositories {
-                    push_repository(&repository, dry_run).await?;
+                    push_repository(&octocrab, &repository, &options).await?;
                 }
             }
         } else {
@@ -201,14 +882,21 @@ async fn push(matches: &ArgMatches) -> Result<(), Error> {
     Ok(())
 }
 
-async fn push_repository(repository: &Repository, dry_run: bool) -> Result<(), Error> {
+async fn push_repository(
+    octocrab: &Octocrab,
+    repository: &Repository,
+    options: &PushOptions,
+) -> Result<(), Error> {
+    let dry_run = options.dry_run;
     info!("Creating {}", repository.org_path().github_url());
 
-    let octocrab = create_octocrab()?;
     let org_path = repository.org_path();
 
     if !dry_run {
-        let create_repository = OrgRepository::from(repository.clone());
+        let create_repository = OrgRepository::from(repository.clone())
+            .with_public(options.public)
+            .with_issues(options.has_issues)
+            .with_wiki(options.has_wiki);
         match octocrab.create_org_repo(&create_repository).await {
             Ok(_) => {}
             Err(_) => warn!(
@@ -218,92 +906,132 @@ async fn push_repository(repository: &Repository, dry_run: bool) -> Result<(), E
         }
     }
 
+    // New repositories push the branch they were created with; existing ones their current branch
+    let mut push_ref = options.branch.clone();
+
     if !repository.has_path(".git") {
         info!("Initializing {}", repository);
+        let branch = push_ref.get_or_insert_with(|| DEFAULT_BRANCH.to_string());
         if !dry_run {
-            Command::new("git")
-                .stdout(Stdio::null())
-                .stderr(Stdio::null())
-                .arg("-C")
-                .arg(&repository.local_path())
-                .arg("init")
-                .status()
-                .await?;
-            Command::new("git")
-                .stdout(Stdio::null())
-                .stderr(Stdio::null())
-                .arg("-C")
-                .arg(&repository.local_path())
-                .arg("add")
-                .arg(".")
-                .status()
-                .await?;
-            Command::new("git")
-                .stdout(Stdio::null())
-                .stderr(Stdio::null())
-                .arg("-C")
-                .arg(&repository.local_path())
-                .arg("commit")
-                .arg("-m")
-                .arg("initial commit")
-                .status()
-                .await?;
-            Command::new("git")
-                .stdout(Stdio::null())
-                .stderr(Stdio::null())
-                .arg("-C")
-                .arg(&repository.local_path())
-                .arg("remote")
-                .arg("add")
-                .arg("origin")
-                .arg(format!(
-                    "git@github.com:{organization}/{repository}.git",
-                    organization = repository.organization().name(),
-                    repository = repository.name()
-                ))
-                .status()
-                .await?;
+            let remote = format!(
+                "git@github.com:{organization}/{repository}.git",
+                organization = repository.organization().name(),
+                repository = repository.name()
+            );
+            run_git(repository, &["init", "--initial-branch", branch.as_str()]).await?;
+            run_git(repository, &["add", "."]).await?;
+            run_git(repository, &["commit", "-m", &options.message]).await?;
+            run_git(repository, &["remote", "add", "origin", &remote]).await?;
         }
     }
     info!("Pushing {}", repository);
     if !dry_run {
-        Command::new("git")
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .arg("-C")
-            .arg(&repository.local_path())
-            .arg("push")
-            .arg("-u")
-            .arg("origin")
-            .arg("HEAD")
-            .status()
-            .await?;
+        run_git(
+            repository,
+            &[
+                "push",
+                "-u",
+                "origin",
+                push_ref.as_deref().unwrap_or("HEAD"),
+            ],
+        )
+        .await?;
     }
 
     Ok(())
 }
 
+/// The organization from `--org`, or else the one the current directory is in.
+fn organization_name(matches: &ArgMatches) -> Result<String, Error> {
+    if let Some(name) = matches.get_one::<String>("organization-name") {
+        return Ok(name.clone());
+    }
+
+    match GithubLevel::current() {
+        Ok(GithubLevel::Organization(org)) => Ok(org.name().to_string()),
+        Ok(GithubLevel::Repository(repo)) => Ok(repo.organization().name().to_string()),
+        Ok(GithubLevel::Enterprise) | Err(_) => Err(Error::msg(
+            "Could not determine organization. Pass --org <name> or run from within ~/orgs/<org>/.",
+        )),
+    }
+}
+
 async fn prune(matches: &ArgMatches) -> Result<(), Error> {
-    let client = create_octocrab()?;
+    let client = create_octocrab().await?;
+    let org_name = organization_name(matches)?;
 
-    let org_name = if let Some(name) = matches.get_one::<String>("organization-name") {
-        name.clone()
-    } else {
-        match GithubLevel::current() {
-            Ok(GithubLevel::Organization(org)) => org.name().to_string(),
-            Ok(GithubLevel::Repository(repo)) => repo.organization().name().to_string(),
-            Ok(GithubLevel::Enterprise) | Err(_) => {
-                return Err(Error::msg(
-                    "Could not determine organization. Pass --org <name> or run from within ~/orgs/<org>/.",
-                ));
+    prune_organization(&client, &org_name, false, matches.get_flag("yes"), matches).await
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Output {
+    Default,
+    Json,
+}
+
+/// A directory under `~/orgs/<org>`, as listed by `repos list`.
+#[derive(Debug, Serialize)]
+struct LocalRepository {
+    name: String,
+    path: String,
+    /// Whether the directory is a git checkout
+    git: bool,
+}
+
+/// Lists the organization's local directories from the filesystem alone, without asking GitHub.
+fn list(matches: &ArgMatches) -> Result<(), Error> {
+    let org_name = organization_name(matches)?;
+    let organization = crate::models::git::Organization::new(org_name.as_str());
+    if !organization.local_path().exists() {
+        return Err(Error::msg(format!(
+            "No local checkout for {}; run `p6m repos pull --org {}`",
+            org_name, org_name
+        )));
+    }
+
+    let repositories: Vec<LocalRepository> = organization
+        .repositories()?
+        .map(|repository| LocalRepository {
+            name: repository.name().to_string(),
+            path: repository.local_path().display().to_string(),
+            git: repository.has_path(".git"),
+        })
+        .collect();
+
+    match matches.get_one::<Output>("output") {
+        Some(Output::Json) => println!("{}", serde_json::to_string_pretty(&repositories)?),
+        None | Some(Output::Default) => {
+            if let Some(listing) = format_local_repositories(&repositories) {
+                println!("{}", listing);
+            } else {
+                info!("No local repos in {}.", org_name);
             }
         }
-    };
+    }
+    Ok(())
+}
 
-    prune_organization(&client, &org_name, false).await
+fn format_local_repositories(repositories: &[LocalRepository]) -> Option<String> {
+    let lines: Vec<String> = repositories
+        .iter()
+        .map(|repository| match repository.git {
+            true => repository.name.clone(),
+            false => format!("{}  (not a git repository)", repository.name),
+        })
+        .collect();
+    match lines.is_empty() {
+        true => None,
+        false => Some(lines.join("\n")),
+    }
 }
 
-async fn prune_organization(client: &Octocrab, org_name: &str, dry_run: bool) -> Result<(), Error> {
+async fn prune_organization(
+    client: &Octocrab,
+    org_name: &str,
+    dry_run: bool,
+    assume_yes: bool,
+    matches: &ArgMatches,
+) -> Result<(), Error> {
     let organization = crate::models::git::Organization::new(org_name);
 
     if !organization.local_path().exists() {
@@ -311,16 +1039,7 @@ async fn prune_organization(client: &Octocrab, org_name: &str, dry_run: bool) ->
         return Ok(());
     }
 
-    let repos_first_page = client
-        .orgs(org_name)
-        .list_repos()
-        .repo_type(octocrab::params::repos::Type::All)
-        .per_page(25)
-        .send()
-        .await?;
-
-    let remote: std::collections::HashSet<String> = client
-        .all_pages(repos_first_page)
+    let remote: std::collections::HashSet<String> = list_org_repos(client, org_name, 25)
         .await?
         .into_iter()
         .map(|r| r.name.to_lowercase())
@@ -343,17 +1062,18 @@ async fn prune_organization(client: &Octocrab, org_name: &str, dry_run: bool) ->
     );
 
     let all_indices: Vec<usize> = (0..stale.len()).collect();
-    let selected = match MultiSelect::new(
-        &format!(
-            "Select repos to delete in {} (Space toggles, Ctrl+A toggles all, Enter to confirm):",
-            org_name
-        ),
-        stale,
-    )
-    .with_default(&all_indices)
-    .with_page_size(25)
-    .prompt()
-    {
+    let selected = match assume_yes {
+        true => Ok(stale),
+        false => prompt::multi_select(
+            &format!("Select repos to delete in {}:", org_name),
+            stale,
+            matches,
+            25,
+        )
+        .with_default(&all_indices)
+        .prompt(),
+    };
+    let selected = match selected {
         Ok(s) => s,
         Err(_) => {
             info!("Aborted; nothing deleted in {}.", org_name);
@@ -366,13 +1086,15 @@ async fn prune_organization(client: &Octocrab, org_name: &str, dry_run: bool) ->
         return Ok(());
     }
 
-    let confirmed = Confirm::new(&format!(
-        "Delete {} selected local repo(s) under ~/orgs/{}/?",
-        selected.len(),
-        org_name
-    ))
-    .with_default(false)
-    .prompt()?;
+    let confirmed = prompt::confirm(
+        &format!(
+            "Delete {} selected local repo(s) under ~/orgs/{}/?",
+            selected.len(),
+            org_name
+        ),
+        false,
+        assume_yes,
+    )?;
 
     if !confirmed {
         info!("Aborted; nothing deleted in {}.", org_name);
@@ -394,30 +1116,29 @@ async fn prune_organization(client: &Octocrab, org_name: &str, dry_run: bool) ->
 
 async fn delete(matches: &ArgMatches) -> Result<(), Error> {
     let dry_run = matches.get_flag("dry-run");
-    let octocrab = create_octocrab()?;
+    let assume_yes = matches.get_flag("yes");
+    let octocrab = create_octocrab().await?;
 
     if dry_run {
         warn!("Dry run mode... nothing will actually be deleted");
     }
 
     if let Ok(org_path) = &GithubLevel::current() {
-        if !(allow_deletes(org_path)) {
-            return Err(Error::msg(
-                "Repositories can only be deleted from 'example' organizations",
-            ));
+        let patterns = delete_allowed_orgs();
+        if !(allow_deletes(org_path, &patterns)) {
+            return Err(Error::msg(format!(
+                "Repositories can only be deleted from organizations matching {}. Set P6M_DELETE_ALLOWED_ORGS to change this",
+                patterns.join(", ")
+            )));
         }
         match org_path {
             GithubLevel::Repository(repository) => {
-                let confirmed = Confirm::new(&format!("Are you sure you want to delete {}?", org_path.github_url()))
-                    .with_default(false)
-                    .prompt()?;
+                let confirmed = prompt::confirm(&format!("Are you sure you want to delete {}?", org_path.github_url()), false, assume_yes)?;
 
                 if confirmed {
                     warn!("Deleting {}", org_path.github_url());
                     if !dry_run {
-                        octocrab.repos(repository.organization().name(), repository.name())
-                            .delete()
-                            .await?;
+                        delete_repository(&octocrab, repository).await?;
                     }
                 }
             }
@@ -425,22 +1146,16 @@ async fn delete(matches: &ArgMatches) -> Result<(), Error> {
                 let repos = organization.repositories()?
                     .collect::<Vec<Repository>>();
 
-                if let Ok(selected_repositories) = MultiSelect::new("Remote repos to delete:", repos)
-                    .with_page_size(20)
+                if let Ok(selected_repositories) = prompt::multi_select("Remote repos to delete:", repos, matches, 20)
                     .prompt() {
-                    let confirmed = Confirm::new("Are you sure you want to delete these remote repositories?")
-                        .with_default(false)
-                        .prompt()?;
+                    let confirmed = prompt::confirm("Are you sure you want to delete these remote repositories?", false, assume_yes)?;
 
                     if confirmed {
                         for repository in selected_repositories {
                             warn!("Deleting {}", repository.org_path().github_url());
                             if !dry_run {
-                                match octocrab.repos(repository.organization().name().to_string(), repository.name().to_string())
-                                    .delete()
-                                    .await {
-                                    Ok(_) => {}
-                                    Err(err) => warn!("{}", err)
+                                if let Err(err) = delete_repository(&octocrab, &repository).await {
+                                    warn!("{}", err)
                                 }
                             }
                         }
@@ -454,39 +1169,222 @@ async fn delete(matches: &ArgMatches) -> Result<(), Error> {
     Ok(())
 }
 
-fn allow_deletes(org_path: &GithubLevel) -> bool {
-    match org_path {
-        GithubLevel::Organization(organization) => {
-            organization.name().contains("example") || organization.name().contains("playstation")
-        }
-        GithubLevel::Repository(repository) => repository.organization().name().contains("example"),
-        _ => false,
+async fn delete_repository(client: &Octocrab, repository: &Repository) -> octocrab::Result<()> {
+    let route = format!(
+        "/repos/{}/{}",
+        repository.organization().name(),
+        repository.name()
+    );
+    let response = with_rate_limit(|| client._delete(route.as_str(), None::<&()>)).await?;
+    octocrab::map_github_error(response).await?;
+    Ok(())
+}
+
+/// Glob patterns for orgs whose remote repos may be deleted, unless overridden by `P6M_DELETE_ALLOWED_ORGS`.
+const DEFAULT_DELETE_ALLOWED_ORGS: [&str; 2] = ["*example*", "*playstation*"];
+
+/// Reads the comma-separated org patterns from `P6M_DELETE_ALLOWED_ORGS`, falling back to the defaults.
+fn delete_allowed_orgs() -> Vec<String> {
+    match std::env::var("P6M_DELETE_ALLOWED_ORGS") {
+        Ok(patterns) => patterns
+            .split(',')
+            .map(str::trim)
+            .filter(|pattern| !pattern.is_empty())
+            .map(String::from)
+            .collect(),
+        Err(_) => DEFAULT_DELETE_ALLOWED_ORGS
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect(),
     }
 }
 
-pub(crate) fn create_octocrab() -> Result<Octocrab, Error> {
-    let token = std::env::var("GITHUB_TOKEN").context(
-        "GITHUB_TOKEN env variable must be set with a classic personal token.\n\n
-            See {DOCS_PREFIX}:",
+fn allow_deletes(org_path: &GithubLevel, patterns: &[String]) -> bool {
+    let org_name = match org_path {
+        GithubLevel::Organization(organization) => organization.name().to_string(),
+        GithubLevel::Repository(repository) => repository.organization().name().to_string(),
+        _ => return false,
+    };
+
+    patterns.iter().any(|pattern| {
+        globset::Glob::new(pattern)
+            .map(|glob| glob.compile_matcher().is_match(&org_name))
+            .unwrap_or_else(|err| {
+                warn!("Ignoring invalid delete pattern {pattern}: {err}");
+                false
+            })
+    })
+}
+
+/// A GitHub API client, and the token it authenticates with, which git uses for HTTPS clones.
+pub(crate) struct Github {
+    pub client: Octocrab,
+    token: String,
+}
+
+pub(crate) async fn create_octocrab() -> Result<Octocrab, Error> {
+    Ok(create_github().await?.client)
+}
+
+/// Authenticates as a GitHub App installation when `GITHUB_APP_ID` is set, otherwise with
+/// `GITHUB_TOKEN` (a classic or fine-grained personal access token).
+pub(crate) async fn create_github() -> Result<Github, Error> {
+    let token = match std::env::var("GITHUB_APP_ID") {
+        Ok(app_id) => create_installation_token(&app_id).await?,
+        Err(_) => {
+            let token = std::env::var("GITHUB_TOKEN").context(
+                "GITHUB_TOKEN env variable must be set with a personal access token (classic or fine-grained), \
+                    or GITHUB_APP_ID and GITHUB_APP_PRIVATE_KEY with a GitHub App",
+            )?;
+            validate_github_token(&token).await?;
+            token
+        }
+    };
+
+    let client = Octocrab::builder().personal_token(token.clone()).build()?;
+    Ok(Github { client, token })
+}
+
+/// An installation access token for the GitHub App, which works for both the API and git over HTTPS.
+/// It's valid for an hour, which is plenty for a single command.
+async fn create_installation_token(app_id: &str) -> Result<String, Error> {
+    let app_id: u64 = app_id
+        .parse()
+        .context("GITHUB_APP_ID must be a numeric App ID")?;
+
+    let key = std::env::var("GITHUB_APP_PRIVATE_KEY").context(
+        "GITHUB_APP_PRIVATE_KEY must be set to the App's private key, or a path to its .pem file",
     )?;
+    let key = match std::path::Path::new(&key).is_file() {
+        true => std::fs::read_to_string(&key)
+            .with_context(|| format!("Unable to read GITHUB_APP_PRIVATE_KEY from {key}"))?,
+        false => key,
+    };
+    let key = jsonwebtoken::EncodingKey::from_rsa_pem(key.as_bytes())
+        .context("GITHUB_APP_PRIVATE_KEY is not a valid RSA private key in PEM format")?;
+
+    let app = Octocrab::builder().app(app_id.into(), key).build()?;
+
+    let installation_id: u64 = match std::env::var("GITHUB_APP_INSTALLATION_ID") {
+        Ok(id) => id
+            .parse()
+            .context("GITHUB_APP_INSTALLATION_ID must be a numeric installation ID")?,
+        Err(_) => {
+            let installations = app
+                .apps()
+                .installations()
+                .send()
+                .await
+                .context("Unable to list GitHub App installations")?
+                .take_items();
+
+            match installations.as_slice() {
+                [installation] => installation.id.0,
+                [] => return Err(Error::msg("The GitHub App is not installed on any organization")),
+                _ => {
+                    return Err(Error::msg(format!(
+                        "The GitHub App has several installations; set GITHUB_APP_INSTALLATION_ID to one of: {}",
+                        installations
+                            .iter()
+                            .map(|installation| format!(
+                                "{} ({})",
+                                installation.id.0, installation.account.login
+                            ))
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    )))
+                }
+            }
+        }
+    };
+
+    let response: serde_json::Value = app
+        .post(
+            format!("/app/installations/{installation_id}/access_tokens"),
+            None::<&()>,
+        )
+        .await
+        .context("Unable to create a GitHub App installation token")?;
+    response
+        .get("token")
+        .and_then(serde_json::Value::as_str)
+        .map(String::from)
+        .context("GitHub didn't return an installation token")
+}
+
+/// Scopes a classic token needs for listing org repos and cloning private ones.
+const REQUIRED_GITHUB_SCOPES: [&str; 2] = ["repo", "read:org"];
+
+/// Fails fast with an actionable message if GitHub rejects the token or it lacks required scopes.
+async fn validate_github_token(token: &str) -> Result<(), Error> {
+    let response = reqwest::Client::builder()
+        .user_agent(format!("p6m-cli/{}", env!("CARGO_PKG_VERSION")))
+        .build()?
+        .get("https://api.github.com/user")
+        .bearer_auth(token)
+        .send()
+        .await
+        .context("Unable to reach GitHub to validate GITHUB_TOKEN")?;
+
+    match response.status() {
+        reqwest::StatusCode::UNAUTHORIZED => {
+            return Err(Error::msg(format!(
+                "GitHub rejected GITHUB_TOKEN; it may be expired or revoked. Generate a new token with the {} scopes",
+                REQUIRED_GITHUB_SCOPES.join(", ")
+            )))
+        }
+        status if !status.is_success() => {
+            return Err(Error::msg(format!(
+                "Unable to validate GITHUB_TOKEN: GitHub responded with {status}"
+            )))
+        }
+        _ => {}
+    }
+
+    // Only classic tokens report their scopes; others are checked by GitHub as they're used
+    let Some(granted) = response
+        .headers()
+        .get("x-oauth-scopes")
+        .and_then(|scopes| scopes.to_str().ok())
+    else {
+        return Ok(());
+    };
 
-    let client = Octocrab::builder().personal_token(token).build()?;
-    Ok(client)
+    let missing = missing_scopes(granted);
+    if !missing.is_empty() {
+        return Err(Error::msg(format!(
+            "GITHUB_TOKEN is missing the {} scope(s). Update the token's scopes in GitHub's Developer Settings",
+            missing.join(", ")
+        )));
+    }
+
+    Ok(())
+}
+
+fn missing_scopes(granted: &str) -> Vec<&'static str> {
+    let granted: Vec<&str> = granted.split(',').map(str::trim).collect();
+
+    REQUIRED_GITHUB_SCOPES
+        .iter()
+        .filter(|required| {
+            !granted.iter().any(|scope| {
+                scope == *required
+                    // broader org scopes imply read:org
+                    || (**required == "read:org" && matches!(*scope, "write:org" | "admin:org"))
+            })
+        })
+        .copied()
+        .collect()
 }
 
 #[async_trait::async_trait]
 trait OctocrabExtensions {
-    async fn list_orgs(&self) -> octocrab::Result<Page<Organization>>;
     // async fn create_repo(&self, org: String, repo: String) -> octocrab::Result<()>;
     async fn create_org_repo(&self, repository: &OrgRepository) -> octocrab::Result<()>;
 }
 
 #[async_trait::async_trait]
 impl OctocrabExtensions for Octocrab {
-    async fn list_orgs(&self) -> octocrab::Result<Page<Organization>> {
-        self.get("/user/orgs", None::<&()>).await
-    }
-
     // async fn create_repo(&self, org: String, repo: String) -> octocrab::Result<()> {
     //     let repository = Repository::new(org.clone(), repo);
 
@@ -524,10 +1422,204 @@ impl From<Repository> for OrgRepository {
         OrgRepository {
             org: value.organization().name().to_string(),
             name: value.name().to_string(),
-            private: false,
+            private: true,
             has_wiki: false,
             has_issues: false,
             visibility: "private".to_string(),
         }
     }
 }
+
+impl OrgRepository {
+    pub fn with_public(mut self, public: bool) -> Self {
+        self.private = !public;
+        self.visibility = match public {
+            true => "public",
+            false => "private",
+        }
+        .to_string();
+        self
+    }
+
+    pub fn with_issues(mut self, has_issues: bool) -> Self {
+        self.has_issues = has_issues;
+        self
+    }
+
+    pub fn with_wiki(mut self, has_wiki: bool) -> Self {
+        self.has_wiki = has_wiki;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limit_wait() {
+        let now = 1_700_000_000;
+        let wait = |status: u16, headers: &[(&str, i64)]| {
+            rate_limit_wait(
+                status,
+                |name| {
+                    headers
+                        .iter()
+                        .find(|(header, _)| *header == name)
+                        .map(|(_, value)| *value)
+                },
+                now,
+            )
+        };
+
+        assert_eq!(
+            wait(
+                403,
+                &[
+                    ("x-ratelimit-remaining", 0),
+                    ("x-ratelimit-reset", now + 30)
+                ]
+            ),
+            Some(Duration::from_secs(31))
+        );
+        assert_eq!(
+            wait(403, &[("retry-after", 5)]),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(wait(429, &[]), Some(Duration::from_secs(60)));
+        // Forbidden for another reason, with requests to spare
+        assert_eq!(wait(403, &[("x-ratelimit-remaining", 4999)]), None);
+        assert_eq!(wait(403, &[]), None);
+        assert_eq!(wait(200, &[("x-ratelimit-remaining", 0)]), None);
+    }
+
+    #[test]
+    fn test_pull_results() {
+        let repository = Repository::new("acme", "widgets");
+        let results = vec![
+            PullResult::new(&repository, PullAction::Pulled),
+            PullResult::new(&repository, PullAction::Skipped).with_detail("local changes"),
+            PullResult::new(&repository, PullAction::Cloned).with_dry_run(true),
+        ];
+
+        assert_eq!(
+            serde_json::to_value(&results).unwrap(),
+            serde_json::json!([
+                {"organization": "acme", "repository": "widgets", "action": "pulled"},
+                {"organization": "acme", "repository": "widgets", "action": "skipped", "detail": "local changes"},
+                {"organization": "acme", "repository": "widgets", "action": "cloned", "dry_run": true},
+            ])
+        );
+        assert!(pull_outcome(&results, Output::Json).is_ok());
+    }
+
+    #[test]
+    fn test_pull_outcome() {
+        let repository = Repository::new("acme", "widgets");
+        let failed_repo =
+            vec![PullResult::new(&repository, PullAction::Failed).with_detail("Code 1")];
+        assert!(pull_outcome(&failed_repo, Output::Default).is_ok());
+        assert_eq!(
+            pull_outcome(&failed_repo, Output::Json)
+                .unwrap_err()
+                .to_string(),
+            "1 repos failed"
+        );
+
+        let failed_org = vec![PullResult::failed_organization(
+            "acme",
+            &Error::msg("rate limited"),
+        )];
+        assert_eq!(
+            pull_outcome(&failed_org, Output::Default)
+                .unwrap_err()
+                .to_string(),
+            "Unable to pull acme"
+        );
+    }
+
+    #[test]
+    fn test_excluded_orgs() {
+        assert_eq!(excluded_orgs(None, None), vec!["p6m-dev"]);
+        assert_eq!(
+            excluded_orgs(None, Some("acme, globex,".to_string())),
+            vec!["acme", "globex"]
+        );
+        assert!(excluded_orgs(None, Some("".to_string())).is_empty());
+        assert_eq!(
+            excluded_orgs(Some(vec!["initech".to_string()]), Some("acme".to_string())),
+            vec!["initech"]
+        );
+        assert!(excluded_orgs(Some(vec!["".to_string()]), None).is_empty());
+    }
+
+    #[test]
+    fn test_format_local_repositories() {
+        let repository = |name: &str, git: bool| LocalRepository {
+            name: name.to_string(),
+            path: format!("/home/dev/orgs/acme/{}", name),
+            git,
+        };
+
+        assert_eq!(format_local_repositories(&[]), None);
+        assert_eq!(
+            format_local_repositories(&[repository("notes", false), repository("widget", true)])
+                .as_deref(),
+            Some("notes  (not a git repository)\nwidget")
+        );
+    }
+
+    #[test]
+    fn test_allow_deletes() {
+        let patterns: Vec<String> = DEFAULT_DELETE_ALLOWED_ORGS
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect();
+        let org =
+            |name: &str| GithubLevel::Organization(crate::models::git::Organization::new(name));
+
+        assert!(allow_deletes(&org("p6m-example"), &patterns));
+        assert!(allow_deletes(&org("playstation-sandbox"), &patterns));
+        assert!(allow_deletes(
+            &GithubLevel::Repository(Repository::new("p6m-example", "service")),
+            &patterns
+        ));
+        assert!(!allow_deletes(&org("acme"), &patterns));
+        assert!(!allow_deletes(&GithubLevel::Enterprise, &patterns));
+        assert!(allow_deletes(&org("acme-sandbox"), &["acme-*".to_string()]));
+        assert!(!allow_deletes(&org("p6m-example"), &[]));
+    }
+
+    #[test]
+    fn test_missing_scopes() {
+        assert!(missing_scopes("repo, read:org, gist").is_empty());
+        assert!(missing_scopes("admin:org,repo").is_empty());
+        assert_eq!(missing_scopes("repo"), ["read:org"]);
+        assert_eq!(missing_scopes(""), ["repo", "read:org"]);
+    }
+
+    #[test]
+    fn test_org_repository_from() {
+        let repository = Repository::new("p6m-example", "service");
+
+        let created = serde_json::to_value(OrgRepository::from(repository.clone())).unwrap();
+        assert_eq!(created["private"], true);
+        assert_eq!(created["visibility"], "private");
+        assert_eq!(created["has_issues"], false);
+        assert_eq!(created["has_wiki"], false);
+
+        let created = serde_json::to_value(
+            OrgRepository::from(repository)
+                .with_public(true)
+                .with_issues(true)
+                .with_wiki(true),
+        )
+        .unwrap();
+        assert_eq!(created["org"], "p6m-example");
+        assert_eq!(created["name"], "service");
+        assert_eq!(created["private"], false);
+        assert_eq!(created["visibility"], "public");
+        assert_eq!(created["has_issues"], true);
+        assert_eq!(created["has_wiki"], true);
+    }
+}
@@ -0,0 +1,246 @@
+use std::{
+    env::{self, consts},
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Error};
+use clap::{crate_version, ArgMatches};
+use log::{debug, info};
+use octocrab::{models::repos::Release, Octocrab};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::cli;
+use crate::prompt;
+
+const RELEASE_OWNER: &str = "p6m-dev";
+const RELEASE_REPO: &str = "p6m-cli";
+
+pub async fn execute(matches: &ArgMatches) -> Result<(), Error> {
+    match cli::subcommand("p6m self", matches)? {
+        Some(("update", subargs)) => update(subargs).await,
+        Some((command, _)) => Err(Error::msg(format!(
+            "Unimplemented self command: '{}'",
+            command
+        ))),
+        None => Err(Error::msg("No self command given")),
+    }
+}
+
+/// Fetches the latest published release of the p6m CLI.
+///
+/// Uses `GITHUB_TOKEN` when present to avoid the anonymous API rate limit.
+pub async fn latest_release() -> octocrab::Result<Release> {
+    let builder = match env::var("GITHUB_TOKEN") {
+        Ok(token) if !token.is_empty() => Octocrab::builder().personal_token(token),
+        _ => Octocrab::builder(),
+    };
+
+    builder
+        .build()?
+        .repos(RELEASE_OWNER, RELEASE_REPO)
+        .releases()
+        .get_latest()
+        .await
+}
+
+async fn update(matches: &ArgMatches) -> Result<(), Error> {
+    let assume_yes = matches.get_flag("yes");
+
+    let release = latest_release()
+        .await
+        .context("Unable to fetch the latest p6m CLI release")?;
+    let latest_version = release.tag_name.clone();
+    let current_version = format!("v{}", crate_version!());
+
+    if !is_newer(&latest_version, crate_version!())
+        .with_context(|| format!("Unable to compare {latest_version} with {current_version}"))?
+    {
+        info!("p6m CLI is already up to date ({current_version}; the latest release is {latest_version})");
+        return Ok(());
+    }
+
+    let archive_name = archive_name(&latest_version);
+    let archive = release
+        .assets
+        .iter()
+        .find(|asset| asset.name == archive_name)
+        .with_context(|| format!("Release {latest_version} has no asset named {archive_name}"))?;
+    let checksum = release
+        .assets
+        .iter()
+        .find(|asset| asset.name == format!("{archive_name}.sha256"));
+
+    if !prompt::confirm(
+        &format!("Update p6m CLI from {current_version} to {latest_version}?"),
+        true,
+        assume_yes,
+    )? {
+        info!("Aborted; p6m CLI was not updated.");
+        return Ok(());
+    }
+
+    info!("Downloading {}", archive.browser_download_url);
+    let bytes = download(archive.browser_download_url.as_str()).await?;
+
+    if bytes.len() as i64 != archive.size {
+        return Err(Error::msg(format!(
+            "Downloaded {} bytes for {archive_name}, but the release lists {} bytes",
+            bytes.len(),
+            archive.size
+        )));
+    }
+
+    match checksum {
+        Some(checksum) => {
+            let expected = parse_checksum(&String::from_utf8_lossy(
+                &download(checksum.browser_download_url.as_str()).await?,
+            ))
+            .with_context(|| format!("Unable to parse checksum from {}", checksum.name))?;
+            let actual = hex::encode(Sha256::digest(&bytes));
+            if !expected.eq_ignore_ascii_case(&actual) {
+                return Err(Error::msg(format!(
+                    "Checksum mismatch for {archive_name}: expected {expected}, found {actual}"
+                )));
+            }
+            debug!("Verified checksum {actual}");
+        }
+        None => debug!("No checksum published for {archive_name}; verified size only"),
+    }
+
+    let work_dir = env::temp_dir().join(format!("p6m-update-{}", Uuid::new_v4()));
+    fs::create_dir_all(&work_dir)?;
+    let result = install(&work_dir, &archive_name, &bytes, &latest_version);
+    let _ = fs::remove_dir_all(&work_dir);
+    result?;
+
+    info!("Updated p6m CLI to {latest_version}");
+    Ok(())
+}
+
+async fn download(url: &str) -> Result<Vec<u8>, Error> {
+    let response = reqwest::Client::builder()
+        .user_agent(format!("p6m-cli/{}", crate_version!()))
+        .build()?
+        .get(url)
+        .send()
+        .await
+        .with_context(|| format!("Failed to GET {url}"))?
+        .error_for_status()?;
+
+    Ok(response.bytes().await?.to_vec())
+}
+
+fn install(work_dir: &Path, archive_name: &str, bytes: &[u8], version: &str) -> Result<(), Error> {
+    let archive_path = work_dir.join(archive_name);
+    fs::write(&archive_path, bytes)?;
+
+    // tar ships with macOS, Linux, and Windows 10+, and understands both .tar.gz and .zip
+    let status = Command::new("tar")
+        .arg("-xf")
+        .arg(&archive_path)
+        .arg("-C")
+        .arg(work_dir)
+        .status()
+        .context("Unable to run tar to extract the release archive")?;
+    if !status.success() {
+        return Err(Error::msg(format!("Unable to extract {archive_name}")));
+    }
+
+    let extracted = work_dir.join(archive_prefix(version)).join(binary_name());
+    if !extracted.exists() {
+        return Err(Error::msg(format!(
+            "{archive_name} does not contain {}",
+            binary_name()
+        )));
+    }
+
+    replace_current_exe(&extracted)
+}
+
+fn replace_current_exe(new_binary: &Path) -> Result<(), Error> {
+    let current = env::current_exe().context("Unable to locate the running p6m binary")?;
+    let current = fs::canonicalize(&current).unwrap_or(current);
+    let directory = current
+        .parent()
+        .context("The running p6m binary has no parent directory")?;
+
+    // Stage next to the current binary so the final rename stays on one filesystem
+    let staged: PathBuf = directory.join(format!(".{}.new", binary_name()));
+    fs::copy(new_binary, &staged)
+        .with_context(|| format!("Unable to write to {}", directory.display()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))?;
+    }
+
+    // Windows refuses to overwrite a running executable, but allows renaming it out of the way
+    #[cfg(windows)]
+    {
+        let previous = directory.join(format!("{}.old", binary_name()));
+        let _ = fs::remove_file(&previous);
+        fs::rename(&current, &previous)?;
+    }
+
+    fs::rename(&staged, &current)
+        .with_context(|| format!("Unable to replace {}", current.display()))?;
+
+    Ok(())
+}
+
+fn archive_prefix(version: &str) -> String {
+    let platform = match consts::OS {
+        "macos" => "macos",
+        "windows" => "windows",
+        _ => "linux",
+    };
+    let arch = match consts::ARCH {
+        "aarch64" => "arm64",
+        arch => arch,
+    };
+    format!("p6m-{version}-{platform}-{arch}")
+}
+
+fn archive_name(version: &str) -> String {
+    match consts::OS {
+        "windows" => format!("{}.zip", archive_prefix(version)),
+        _ => format!("{}.tar.gz", archive_prefix(version)),
+    }
+}
+
+fn binary_name() -> String {
+    format!("p6m{}", consts::EXE_SUFFIX)
+}
+
+/// Whether a release tag like `v1.2.3` is a newer version than `current`, so updating never downgrades.
+pub fn is_newer(latest_tag: &str, current: &str) -> Result<bool, semver::Error> {
+    let latest = semver::Version::parse(latest_tag.strip_prefix('v').unwrap_or(latest_tag))?;
+    let current = semver::Version::parse(current)?;
+    Ok(latest > current)
+}
+
+/// Extracts the hex digest from either `shasum` or `certutil` output.
+fn parse_checksum(contents: &str) -> Option<String> {
+    contents
+        .split_whitespace()
+        .find(|token| token.len() == 64 && token.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(|token| token.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer("v1.2.10", "1.2.9").unwrap());
+        assert!(is_newer("1.3.0", "1.3.0-rc.1").unwrap());
+        assert!(!is_newer("v1.2.3", "1.2.3").unwrap());
+        assert!(!is_newer("v1.2.3", "1.10.0").unwrap());
+        assert!(is_newer("latest", "1.2.3").is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use std::{
+    fs,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Error;
+use camino::{Utf8Path, Utf8PathBuf};
+use log::debug;
+
+use crate::auth0::Apps;
+
+/// How long a cached apps response is reused before `p6m sso` fetches it again.
+pub const TTL: Duration = Duration::from_secs(10 * 60);
+
+fn dir(config_dir: &Utf8Path) -> Utf8PathBuf {
+    config_dir.join("cache").join("apps")
+}
+
+fn path(config_dir: &Utf8Path, organization: Option<&String>) -> Utf8PathBuf {
+    let key = organization.map(String::as_str).unwrap_or("default");
+    dir(config_dir).join(format!("{key}.json"))
+}
+
+/// Returns the cached apps for `organization` if they were written less than `ttl` ago.
+pub fn read(config_dir: &Utf8Path, organization: Option<&String>, ttl: Duration) -> Option<Apps> {
+    let path = path(config_dir, organization);
+    let age = fs::metadata(&path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())?;
+    if age >= ttl {
+        debug!("Apps cache {path} is stale");
+        return None;
+    }
+
+    match serde_json::from_str(&fs::read_to_string(&path).ok()?) {
+        Ok(apps) => Some(apps),
+        Err(e) => {
+            debug!("Ignoring unreadable apps cache {path}: {e}");
+            None
+        }
+    }
+}
+
+pub fn write(
+    config_dir: &Utf8Path,
+    organization: Option<&String>,
+    apps: &Apps,
+) -> Result<(), Error> {
+    let path = path(config_dir, organization);
+    fs::create_dir_all(dir(config_dir))?;
+    fs::write(&path, serde_json::to_string(apps)?)?;
+    Ok(())
+}
+
+/// Drops every cached apps response, e.g. after logging in as someone else.
+pub fn clear(config_dir: &Utf8Path) -> Result<(), Error> {
+    match fs::remove_dir_all(dir(config_dir)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_read_honors_ttl_and_clear() {
+        let config_dir = TempDir::new("apps-cache");
+        let organization = Some("acme".to_string());
+        let apps: Apps = serde_json::from_str("[]").unwrap();
+
+        assert!(read(&config_dir, organization.as_ref(), TTL).is_none());
+
+        write(&config_dir, organization.as_ref(), &apps).unwrap();
+        assert!(read(&config_dir, organization.as_ref(), TTL).is_some());
+        assert!(read(&config_dir, None, TTL).is_none());
+        assert!(read(&config_dir, organization.as_ref(), Duration::ZERO).is_none());
+
+        clear(&config_dir).unwrap();
+        assert!(read(&config_dir, organization.as_ref(), TTL).is_none());
+        clear(&config_dir).unwrap();
+    }
+}
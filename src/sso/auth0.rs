@@ -1,4 +1,4 @@
-use std::{collections::HashMap, fs, path::PathBuf};
+use std::{collections::HashMap, path::Path};
 
 use anyhow::{Context, Error};
 use kube::config::{
@@ -10,13 +10,30 @@ use log::{debug, info, warn};
 use crate::{
     auth::{TokenRepository, TryReason},
     auth0,
-    cli::P6mEnvironment,
+    cli::{P6mEnvironment, DEV_PROFILE},
     App, AuthToken,
 };
 
+use super::{apps_cache, kubeconfig};
+
+/// How kube cluster and context names are derived from apps.
+pub struct KubeNaming {
+    /// Replaces the derived name; only valid when a single cluster is being configured.
+    pub machine_name: Option<String>,
+    /// Removed from app names; empty to keep them as they are.
+    pub strip_suffix: String,
+}
+
+/// Adds a kubeconfig context for every Kubernetes app, or with `only` just those of one organization.
+///
+/// The apps response is cached briefly per organization; `refresh_apps` fetches it regardless.
 pub async fn configure_auth0(
     environment: &P6mEnvironment,
     organization: Option<&String>,
+    only: Option<&String>,
+    kubeconfig_path: &Path,
+    refresh_apps: bool,
+    naming: &KubeNaming,
 ) -> Result<(), Error> {
     let mut token_repository = TokenRepository::new(&environment.auth_n, &environment.auth_dir)?;
 
@@ -41,18 +58,56 @@ pub async fn configure_auth0(
         .email
         .context("missing email")?;
 
-    let client = auth0::Client::new(&token_repository.auth_n.apps_uri()).with_token(id_token);
+    let client = auth0::Client::new(&token_repository.auth_n.apps_uri())
+        .with_token(id_token)
+        .with_organization(organization);
+
+    let cached = match refresh_apps {
+        true => None,
+        false => apps_cache::read(environment.config_dir(), organization, apps_cache::TTL),
+    };
+    let apps = match cached {
+        Some(apps) => {
+            debug!("auth0: using cached apps");
+            apps
+        }
+        None => {
+            let apps = client.apps().await.context("Unable to fetch apps")?;
+            if let Err(e) = apps_cache::write(environment.config_dir(), organization, &apps) {
+                debug!("auth0: unable to cache apps: {e}");
+            }
+            apps
+        }
+    };
 
-    let apps = client.apps().await.context("Unable to fetch apps")?;
+    let kube_apps: Vec<App> = apps
+        .contain_scope("login:kubernetes")
+        .into_iter()
+        .filter(|app| only.is_none_or(|only| app.belongs_to(only)))
+        .collect();
 
-    let kube_apps = apps.contain_scope("login:kubernetes");
+    if let (Some(only), true) = (only, kube_apps.is_empty()) {
+        warn!("auth0: no Kubernetes clusters found for organization {only}");
+    }
 
-    for app in kube_apps.clone() {
-        let (kubeconfig, name) = generate_kubeconfig(&app, &email)
-            .await
-            .context("unable to generate kubeconfig")?;
+    if naming.machine_name.is_some() && kube_apps.len() > 1 {
+        return Err(Error::msg(format!(
+            "--machine-name names a single cluster, but {} were found; narrow them down with --only",
+            kube_apps.len()
+        )));
+    }
 
-        match merge_kubeconfig(kubeconfig, &name).await {
+    for app in kube_apps {
+        let machine_name = naming
+            .machine_name
+            .clone()
+            .unwrap_or_else(|| app.kube_name(&naming.strip_suffix));
+        let (kubeconfig, name) =
+            generate_kubeconfig(&app, &machine_name, &email, environment.profile.as_ref())
+                .await
+                .context("unable to generate kubeconfig")?;
+
+        match merge_kubeconfig(kubeconfig, &name, kubeconfig_path).await {
             Ok(update_res) => {
                 info!("auth0: update-kubectx: {}", update_res);
             }
@@ -65,8 +120,17 @@ pub async fn configure_auth0(
     Ok(())
 }
 
-async fn generate_kubeconfig(app: &App, email: &String) -> Result<(Kubeconfig, String), Error> {
-    let cluster_name = format!("{}.p6m", app.machine_name().replace("-auth0", ""));
+async fn generate_kubeconfig(
+    app: &App,
+    machine_name: &str,
+    email: &String,
+    profile: Option<&String>,
+) -> Result<(Kubeconfig, String), Error> {
+    // Dev contexts were named `<cluster>.p6m` before profiles existed; keep that so they're updated in place
+    let cluster_name = match profile.filter(|profile| profile.as_str() != DEV_PROFILE) {
+        Some(profile) => format!("{}.{}.p6m", machine_name, profile),
+        None => format!("{}.p6m", machine_name),
+    };
     let url = app.url();
     let org = app.org().context("missing org")?;
     let ca = app.ca().context("Missing certificate authority")?;
@@ -102,6 +166,11 @@ async fn generate_kubeconfig(app: &App, email: &String) -> Result<(Kubeconfig, S
         "k8s-auth".into(),
     ];
 
+    if let Some(profile) = profile {
+        command.push("--profile".into());
+        command.push(profile.clone());
+    }
+
     let env: Vec<HashMap<String, String>> = vec![];
 
     let user_name = match app.auth_n {
@@ -158,21 +227,18 @@ async fn generate_kubeconfig(app: &App, email: &String) -> Result<(Kubeconfig, S
     Ok((kubeconfig, cluster_name))
 }
 
-async fn merge_kubeconfig(kubeconfig: Kubeconfig, name: &String) -> Result<String, Error> {
-    let path = dirs::home_dir()
-        .map(|path| path.join(".kube").join("config"))
-        .unwrap_or_else(|| PathBuf::from(".kube").join("config"));
-
-    let existing = Kubeconfig::read_from(path.clone().as_path()).unwrap_or(Kubeconfig::default());
+async fn merge_kubeconfig(
+    kubeconfig: Kubeconfig,
+    name: &String,
+    path: &Path,
+) -> Result<String, Error> {
+    let existing = Kubeconfig::read_from(path).unwrap_or(Kubeconfig::default());
 
     let kubeconfig = kubeconfig
         .merge(existing)
         .context("unable to merge configs")?;
 
-    let yaml =
-        serde_yaml::to_string(&kubeconfig).context("unable to convert kubeconfig to yaml")?;
-
-    fs::write(path.clone(), yaml).context("unable to write kubeconfig")?;
+    kubeconfig::write(path, &kubeconfig)?;
 
     Ok(format!("Updated context {} in {}", name, path.to_string_lossy(),).to_string())
 }
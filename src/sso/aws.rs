@@ -1,6 +1,7 @@
 use crate::{
     models::aws::{AwsAccountInfo, AwsAccountRoleInfo, AwsConfig, AwsEksListClustersResponse},
-    sso::vcluster::update_vcluster_kubecfgs,
+    progress::Progress,
+    sso::{cloud_cli::CloudCli, vcluster::update_vcluster_kubecfgs},
 };
 use anyhow::Error;
 use aws_sdk_eks::config::Region;
@@ -14,6 +15,7 @@ use std::{
     env,
     fs::{self, File},
     io::Write,
+    path::{Path, PathBuf},
     process::Command,
 };
 
@@ -35,14 +37,16 @@ const AWS_ROLE_HIERARCHY: [&str; 4] = [
     AWS_DEVELOPER,
 ];
 
-pub async fn configure_aws() -> Result<(), Error> {
+pub async fn configure_aws(kubeconfig_path: &Path, vcluster_selector: &str) -> Result<(), Error> {
+    CloudCli::Aws.check_installed()?;
+
     // Create the initial aws config file with the P6m SSO session. This covers the use case where the
     // user is configuring this for the first time and there is no SSO config at all for downstream calls.
-    let mut aws_dir = dirs::home_dir()
-        .ok_or("Failed to get home directory")
-        .expect("Unable to get home directory");
-    aws_dir.push(".aws");
-    let aws_config_file_path = aws_dir.join("config");
+    let aws_config_file_path = match env::var_os("AWS_CONFIG_FILE").filter(|path| !path.is_empty())
+    {
+        Some(path) => PathBuf::from(path),
+        None => aws_dir()?.join("config"),
+    };
 
     // Check to make sure AWS_* is not set
     // TODO this can probably be removed if the aws_config below is built manually.
@@ -71,7 +75,10 @@ pub async fn configure_aws() -> Result<(), Error> {
     let mut account_role_vector: Vec<AwsAccountRoleInfo> = Vec::new();
     let mut vcluster_vector: Vec<KubeConfigOptions> = Vec::new();
 
+    let mut progress = Progress::new(account_vector.len());
+
     for account in account_vector.iter() {
+        progress.step(format!("aws: sso: {}", account.account_slug));
         match find_account_role(
             sso_client.clone(),
             access_token.clone(),
@@ -94,6 +101,8 @@ pub async fn configure_aws() -> Result<(), Error> {
         }
     }
 
+    drop(progress);
+
     // Write to ~/.aws/config again, this time with all the JV profiles
     let content = render!(
         include_str!("../../resources/aws_config"),
@@ -103,14 +112,19 @@ pub async fn configure_aws() -> Result<(), Error> {
         .expect("Unable to overwrite ~/.aws/config");
 
     // Find clusters and update kubeconfig for each JV
+    let mut progress = Progress::new(account_role_vector.len());
+
     for account in account_role_vector.iter() {
         let res = cmd_list_clusters(account.account_slug.clone());
-        info!("aws: list-clusters: {}", account.account_slug.clone());
+        progress.step(format!("aws: list-clusters: {}", account.account_slug));
         match res {
             Ok(list_clusters_res) => {
                 list_clusters_res.clusters.iter().for_each(|cluster| {
-                    let update_res =
-                        cmd_update_kubecfg(account.account_slug.clone(), cluster.to_string());
+                    let update_res = cmd_update_kubecfg(
+                        account.account_slug.clone(),
+                        cluster.to_string(),
+                        kubeconfig_path,
+                    );
 
                     match update_res.as_ref() {
                         Ok(update_res) => {
@@ -139,8 +153,10 @@ pub async fn configure_aws() -> Result<(), Error> {
         }
     }
 
+    drop(progress);
+
     for options in vcluster_vector.iter() {
-        match update_vcluster_kubecfgs(options).await {
+        match update_vcluster_kubecfgs(options, vcluster_selector, kubeconfig_path).await {
             Err(err) => {
                 log::warn!("aws: unable to update vcluster kubeconfigs: {}", err);
             }
@@ -163,12 +179,7 @@ pub async fn configure_aws() -> Result<(), Error> {
 // See https://github.com/aws/aws-cli/issues/5057 for details.
 fn find_aws_access_token(sso_profile_name: &str) -> Result<String, Error> {
     // Find AWS SSO cache dir
-    let mut aws_cache_dir = dirs::home_dir()
-        .ok_or("Failed to get home directory")
-        .expect("Unable to get home directory");
-    aws_cache_dir.push(".aws");
-    aws_cache_dir.push("sso");
-    aws_cache_dir.push("cache");
+    let aws_cache_dir = aws_dir()?.join("sso").join("cache");
 
     // SHA1 hash of the profile
     let mut hasher = Sha1::new();
@@ -276,10 +287,17 @@ async fn find_account_role(
     }
 }
 
+// The AWS CLI keeps its files in ~/.aws on every platform, which is %USERPROFILE%\.aws on Windows
+fn aws_dir() -> Result<PathBuf, Error> {
+    dirs::home_dir()
+        .map(|home| home.join(".aws"))
+        .ok_or_else(|| Error::msg("Unable to get home directory"))
+}
+
 // Create or replace a file with the specified content, and create the directory structure if it is missing
 fn create_or_replace_file(filename: Option<&str>, content: &str) -> Result<(), Error> {
     if let Some(file_path) = filename {
-        if let Some(parent_dir) = std::path::Path::new(file_path).parent() {
+        if let Some(parent_dir) = Path::new(file_path).parent() {
             fs::create_dir_all(parent_dir)?;
         }
 
@@ -313,12 +331,7 @@ fn cmd_list_clusters(profile: String) -> Result<AwsEksListClustersResponse, Erro
 
     let output = match cmd.output() {
         Ok(o) => o,
-        Err(err) => {
-            return Err(Error::msg(format!(
-                "unable to run 'aws eks list-clusters': {}",
-                err
-            )));
-        }
+        Err(err) => return Err(CloudCli::Aws.run_error("aws eks list-clusters", err)),
     };
 
     let stdout = String::from_utf8_lossy(&output.stdout);
@@ -347,7 +360,11 @@ fn cmd_list_clusters(profile: String) -> Result<AwsEksListClustersResponse, Erro
     }
 }
 
-fn cmd_update_kubecfg(profile: String, cluster: String) -> Result<String, Error> {
+fn cmd_update_kubecfg(
+    profile: String,
+    cluster: String,
+    kubeconfig_path: &Path,
+) -> Result<String, Error> {
     let mut cmd = Command::new("aws");
     cmd.args(&[
         "eks",
@@ -357,6 +374,7 @@ fn cmd_update_kubecfg(profile: String, cluster: String) -> Result<String, Error>
         "--alias",
         cluster.clone().as_str(),
     ]);
+    cmd.arg("--kubeconfig").arg(kubeconfig_path);
     cmd.env("AWS_PROFILE", profile.clone());
 
     log::debug!("executing `{:?}`", cmd);
@@ -364,12 +382,10 @@ fn cmd_update_kubecfg(profile: String, cluster: String) -> Result<String, Error>
     let output = match cmd.output() {
         Ok(o) => o,
         Err(err) => {
-            log::warn!(
-                "unable to run 'aws eks update-kubeconfig --name {}': {}",
-                profile.clone(),
-                err
-            );
-            return Err(Error::msg("command error"));
+            return Err(CloudCli::Aws.run_error(
+                &format!("aws eks update-kubeconfig --name {}", cluster),
+                err,
+            ))
         }
     };
 
@@ -385,9 +401,14 @@ fn cmd_update_kubecfg(profile: String, cluster: String) -> Result<String, Error>
     }
 }
 
+#[cfg(windows)]
+const UNSET_COMMAND: &str = "Remove-Item Env:";
+#[cfg(not(windows))]
+const UNSET_COMMAND: &str = "unset ";
+
 fn check_env_unset(env_var: &str) -> Result<(), Error> {
     match env::var(env_var) {
-        Ok(_) => return Err(Error::msg(format!("{} must be unset for this to work correctly. Try this in your current terminal session:\n\n\tunset {}\n", env_var, env_var))),
+        Ok(_) => return Err(Error::msg(format!("{} must be unset for this to work correctly. Try this in your current terminal session:\n\n\t{}{}\n", env_var, UNSET_COMMAND, env_var))),
         Err(_) => return Ok(()),
     }
 }
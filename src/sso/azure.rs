@@ -1,16 +1,49 @@
-use crate::models::azure::{self, AzureAccessToken, AzureAksCluster, AzureConfig};
+use crate::models::azure::{AzureAccessToken, AzureAksCluster, AzureConfig};
+use crate::sso::cloud_cli::CloudCli;
 use anyhow::Error;
-use log::{error, info, warn};
-use std::process::Command;
+use chrono::{Duration, Local};
+use log::{debug, error, info, warn};
+use std::{path::Path, process::Command};
+
+/// The Azure CLI is installed as a batch script on Windows, which `Command` won't find without its extension.
+#[cfg(windows)]
+pub(super) const AZ: &str = "az.cmd";
+#[cfg(not(windows))]
+pub(super) const AZ: &str = "az";
+
+/// How close to expiry an Azure access token gets before we suggest logging in again.
+const EXPIRY_WARNING_MINUTES: i64 = 10;
+
+pub async fn configure_azure(
+    kubeconfig_path: &Path,
+    subscriptions: &[String],
+) -> Result<(), Error> {
+    CloudCli::Azure.check_installed()?;
 
-pub async fn configure_azure() -> Result<(), Error> {
     let azure_configs = find_azure_accounts().unwrap_or(vec![]);
     if azure_configs.is_empty() {
         warn!("No Azure accounts found, make sure that you have run \n\n\taz login\nand have access to at least one Azure account.");
         return Ok(());
     }
+    let azure_configs: Vec<AzureConfig> = azure_configs
+        .into_iter()
+        .filter(|config| {
+            subscriptions.is_empty()
+                || subscriptions
+                    .iter()
+                    .any(|subscription| config.matches_subscription(subscription))
+        })
+        .collect();
+    if azure_configs.is_empty() {
+        warn!(
+            "No Azure subscriptions match {}; `az account list --all` shows the ones available.",
+            subscriptions.join(", ")
+        );
+        return Ok(());
+    }
     for azure_config in azure_configs {
-        if azure_config.state == Some(azure::AzureAccountState::Disabled) {
+        if azure_config.is_disabled() {
+            debug!("Skipping disabled subscription {}", azure_config);
             continue;
         }
         match find_azure_access_token(azure_config.clone()) {
@@ -41,7 +74,7 @@ pub async fn configure_azure() -> Result<(), Error> {
         };
         for cluster in aks_clusters {
             info!("aks: update-kubectx: {}", &cluster.ClusterName);
-            match update_kubeconfig(azure_config.clone(), cluster.clone()) {
+            match update_kubeconfig(azure_config.clone(), cluster.clone(), kubeconfig_path) {
                 Ok(_) => {}
                 Err(err) => {
                     error!(
@@ -57,13 +90,13 @@ pub async fn configure_azure() -> Result<(), Error> {
 }
 
 fn find_azure_accounts() -> Result<Vec<AzureConfig>, Error> {
-    let mut cmd: Command = Command::new("az");
+    let mut cmd: Command = Command::new(AZ);
     cmd.args(&["account", "list", "--all"]);
 
     log::debug!("executing `{:?}`", cmd);
     let output = match cmd.output() {
         Ok(o) => o,
-        Err(err) => return Err(Error::msg(format!("unable to run 'az aks list': {}", err))),
+        Err(err) => return Err(CloudCli::Azure.run_error("az account list", err)),
     };
 
     let stdout = String::from_utf8_lossy(&output.stdout);
@@ -91,7 +124,7 @@ fn find_azure_accounts() -> Result<Vec<AzureConfig>, Error> {
 }
 
 fn find_azure_access_token(azure_config: AzureConfig) -> Result<(), Error> {
-    let mut cmd: Command = Command::new("az");
+    let mut cmd: Command = Command::new(AZ);
     cmd.args(&[
         "account",
         "get-access-token",
@@ -102,12 +135,7 @@ fn find_azure_access_token(azure_config: AzureConfig) -> Result<(), Error> {
     log::debug!("executing `{:?}`", cmd);
     let output = match cmd.output() {
         Ok(o) => o,
-        Err(err) => {
-            return Err(Error::msg(format!(
-                "unable to run 'az get-access-token': {}",
-                err
-            )))
-        }
+        Err(err) => return Err(CloudCli::Azure.run_error("az account get-access-token", err)),
     };
 
     let stdout = String::from_utf8_lossy(&output.stdout);
@@ -125,18 +153,38 @@ fn find_azure_access_token(azure_config: AzureConfig) -> Result<(), Error> {
         return Err(Error::msg("Command terminated by signal"));
     }
 
-    let _token: AzureAccessToken = match serde_json::from_str(&stdout) {
+    let token: AzureAccessToken = match serde_json::from_str(&stdout) {
         Ok(token) => token,
         Err(_) => {
             warn!("invalid json: {}", &stdout);
             return Err(Error::msg("invalid json"));
         }
     };
+
+    match token.expires_on() {
+        Some(expires_on) => {
+            let remaining = expires_on - Local::now();
+            if remaining < Duration::zero() {
+                return Err(Error::msg(format!(
+                    "access token expired at {}, try logging in?\n\n\taz login\n",
+                    token.expiresOn
+                )));
+            }
+            if remaining < Duration::minutes(EXPIRY_WARNING_MINUTES) {
+                warn!(
+                    "Azure access token for {} expires in {} minutes, run `az login` to refresh it",
+                    &azure_config.name.clone().unwrap_or_default(),
+                    remaining.num_minutes()
+                );
+            }
+        }
+        None => debug!("unable to parse Azure token expiry '{}'", token.expiresOn),
+    }
     Ok(())
 }
 
 fn get_aks_clusters(azure_config: AzureConfig) -> Result<Vec<AzureAksCluster>, Error> {
-    let mut cmd: Command = Command::new("az");
+    let mut cmd: Command = Command::new(AZ);
     cmd.args(&[
         "aks",
         "list",
@@ -149,7 +197,7 @@ fn get_aks_clusters(azure_config: AzureConfig) -> Result<Vec<AzureAksCluster>, E
     log::debug!("executing `{:?}`", cmd);
     let output = match cmd.output() {
         Ok(o) => o,
-        Err(err) => return Err(Error::msg(format!("unable to run 'az aks list': {}", err))),
+        Err(err) => return Err(CloudCli::Azure.run_error("az aks list", err)),
     };
 
     let stdout = String::from_utf8_lossy(&output.stdout);
@@ -177,8 +225,12 @@ fn get_aks_clusters(azure_config: AzureConfig) -> Result<Vec<AzureAksCluster>, E
     Ok(clusters)
 }
 
-fn update_kubeconfig(azure_config: AzureConfig, cluster: AzureAksCluster) -> Result<(), Error> {
-    let mut cmd: Command = Command::new("az");
+fn update_kubeconfig(
+    azure_config: AzureConfig,
+    cluster: AzureAksCluster,
+    kubeconfig_path: &Path,
+) -> Result<(), Error> {
+    let mut cmd: Command = Command::new(AZ);
     cmd.args(&[
         "aks",
         "get-credentials",
@@ -192,16 +244,12 @@ fn update_kubeconfig(azure_config: AzureConfig, cluster: AzureAksCluster) -> Res
         &azure_config.id,
         "--overwrite-existing",
     ]);
+    cmd.arg("--file").arg(kubeconfig_path);
 
     log::debug!("executing `{:?}`", cmd);
     let output = match cmd.output() {
         Ok(o) => o,
-        Err(err) => {
-            return Err(Error::msg(format!(
-                "unable to run 'az aks get-credentials': {}",
-                err
-            )))
-        }
+        Err(err) => return Err(CloudCli::Azure.run_error("az aks get-credentials", err)),
     };
 
     let stderr = String::from_utf8_lossy(&output.stderr);
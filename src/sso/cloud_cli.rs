@@ -0,0 +1,94 @@
+use crate::workstation::check::print_see_link;
+use anyhow::Error;
+use std::io::{self, ErrorKind};
+use std::process::{Command, Stdio};
+
+/// The cloud provider CLIs that `p6m sso` shells out to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CloudCli {
+    Aws,
+    Azure,
+}
+
+impl CloudCli {
+    fn name(self) -> &'static str {
+        match self {
+            CloudCli::Aws => "AWS CLI",
+            CloudCli::Azure => "Azure CLI",
+        }
+    }
+
+    fn program(self) -> &'static str {
+        match self {
+            CloudCli::Aws => "aws",
+            CloudCli::Azure => super::azure::AZ,
+        }
+    }
+
+    fn subcommand(self) -> &'static str {
+        match self {
+            CloudCli::Aws => "aws",
+            CloudCli::Azure => "azure",
+        }
+    }
+
+    fn install_url(self) -> &'static str {
+        match self {
+            CloudCli::Aws => {
+                "https://docs.aws.amazon.com/cli/latest/userguide/getting-started-install.html"
+            }
+            CloudCli::Azure => "https://learn.microsoft.com/en-us/cli/azure/install-azure-cli",
+        }
+    }
+
+    /// Fails with install instructions unless the CLI can be started, so a first run stops before changing anything.
+    pub fn check_installed(self) -> Result<(), Error> {
+        Command::new(self.program())
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|_| ())
+            .map_err(|err| self.run_error(&format!("{} --version", self.program()), err))
+    }
+
+    /// The error for a `command` that couldn't be started. A missing binary is the usual first-run
+    /// failure, so it gets install instructions rather than the bare io error.
+    pub fn run_error(self, command: &str, err: io::Error) -> Error {
+        if err.kind() != ErrorKind::NotFound {
+            return Error::msg(format!("unable to run '{}': {}", command, err));
+        }
+
+        let mut message = format!(
+            "the {} is required for `p6m sso {}`, but was not found on the PATH",
+            self.name(),
+            self.subcommand()
+        );
+        // Writing to a String can't fail
+        let _ = print_see_link(&mut message, self.install_url());
+        Error::msg(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_error() {
+        let missing = CloudCli::Azure
+            .run_error("az account list", io::Error::from(ErrorKind::NotFound))
+            .to_string();
+        assert!(missing.contains("Azure CLI is required for `p6m sso azure`"));
+        assert!(missing.contains("See: https://learn.microsoft.com/"));
+
+        let denied = CloudCli::Aws
+            .run_error(
+                "aws eks list-clusters",
+                io::Error::from(ErrorKind::PermissionDenied),
+            )
+            .to_string();
+        assert!(denied.starts_with("unable to run 'aws eks list-clusters': "));
+        assert!(!denied.contains("See:"));
+    }
+}
@@ -0,0 +1,110 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Error};
+use kube::config::Kubeconfig;
+use log::debug;
+
+/// Resolves the kubeconfig file that p6m should read and update.
+///
+/// Honors `KUBECONFIG` (which may be a list of paths) by choosing the first writable entry,
+/// falling back to `~/.kube/config` when it is unset or nothing in it is writable.
+pub fn path() -> PathBuf {
+    if let Some(paths) = env::var_os("KUBECONFIG") {
+        let candidates: Vec<PathBuf> = env::split_paths(&paths)
+            .filter(|path| !path.as_os_str().is_empty())
+            .collect();
+
+        if let Some(path) = candidates.iter().find(|path| is_writable(path)) {
+            debug!("Using kubeconfig {} from KUBECONFIG", path.display());
+            return path.clone();
+        }
+
+        debug!("No writable path in KUBECONFIG; falling back to the default kubeconfig");
+    }
+
+    default_path()
+}
+
+fn default_path() -> PathBuf {
+    dirs::home_dir()
+        .map(|path| path.join(".kube").join("config"))
+        .unwrap_or_else(|| PathBuf::from(".kube").join("config"))
+}
+
+/// A path is writable if it exists and isn't read-only, or if it can be created.
+fn is_writable(path: &Path) -> bool {
+    match fs::metadata(path) {
+        Ok(metadata) => metadata.is_file() && !metadata.permissions().readonly(),
+        Err(_) => match path.parent() {
+            Some(parent) if parent.as_os_str().is_empty() => true,
+            Some(parent) => fs::metadata(parent)
+                .map(|metadata| metadata.is_dir() && !metadata.permissions().readonly())
+                .unwrap_or(true),
+            None => false,
+        },
+    }
+}
+
+/// Writes the kubeconfig to a temporary file next to `path` and renames it into place, so
+/// `kubectl` never sees a partially written config.
+///
+/// The temporary file is read back first; if it isn't a loadable kubeconfig, it's discarded and
+/// the existing file is left as it was.
+pub fn write(path: &Path, kubeconfig: &Kubeconfig) -> Result<(), Error> {
+    let yaml = serde_yaml::to_string(kubeconfig).context("unable to convert kubeconfig to yaml")?;
+
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(format!(".{}.tmp", uuid::Uuid::new_v4()));
+    let temp_path = path.with_file_name(temp_name);
+
+    fs::write(&temp_path, yaml)
+        .with_context(|| format!("unable to write {}", temp_path.display()))?;
+
+    let result = Kubeconfig::read_from(&temp_path)
+        .map_err(|err| {
+            Error::new(err).context(format!(
+                "the updated kubeconfig couldn't be loaded, so {} was left unchanged",
+                path.display()
+            ))
+        })
+        .and_then(|_| {
+            fs::rename(&temp_path, path).map_err(|err| {
+                Error::new(err).context(format!("unable to replace {}", path.display()))
+            })
+        });
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_write() {
+        let temp_dir = TempDir::new("kubeconfig-test");
+        let dir = temp_dir.as_std_path();
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config");
+
+        let kubeconfig = Kubeconfig {
+            current_context: Some("acme.p6m".into()),
+            ..Default::default()
+        };
+        write(&path, &kubeconfig).unwrap();
+        assert_eq!(
+            Kubeconfig::read_from(&path).unwrap().current_context,
+            kubeconfig.current_context
+        );
+
+        // Only the config itself is left behind
+        assert_eq!(fs::read_dir(dir).unwrap().count(), 1);
+    }
+}
@@ -1,17 +1,21 @@
+pub mod apps_cache;
 pub mod auth0;
 pub mod aws;
 pub mod azure;
+mod cloud_cli;
+pub mod kubeconfig;
 pub mod vcluster;
 
 use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Error};
-use auth0::configure_auth0;
+use auth0::{configure_auth0, KubeNaming};
 use aws::configure_aws;
 use azure::configure_azure;
 use clap::ArgMatches;
 
-use crate::cli::P6mEnvironment;
+use crate::cli::{self, P6mEnvironment};
 
 pub async fn execute(environment: P6mEnvironment, matches: &ArgMatches) -> Result<(), Error> {
     create_dir_all(environment.kube_dir())?;
@@ -19,18 +23,77 @@ pub async fn execute(environment: P6mEnvironment, matches: &ArgMatches) -> Resul
     let organization = matches
         .try_get_one::<String>("organization-name")
         .unwrap_or(None);
+    let only = matches.try_get_one::<String>("only").unwrap_or(None);
+    let refresh_apps = matches.get_flag("refresh-apps");
+    let naming = KubeNaming {
+        machine_name: matches.get_one::<String>("machine-name").cloned(),
+        strip_suffix: matches
+            .get_one::<String>("strip-suffix")
+            .cloned()
+            .unwrap_or_default(),
+    };
+    let kubeconfig_path = match matches.try_get_one::<String>("kubeconfig").unwrap_or(None) {
+        Some(path) => {
+            let path = PathBuf::from(path);
+            if let Some(parent) = path
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+            {
+                create_dir_all(parent)?;
+            }
+            path
+        }
+        None => kubeconfig::path(),
+    };
 
-    match matches.subcommand() {
-        Some(("auth0", _)) => configure_auth0(&environment, organization)
+    let vcluster_selector = matches
+        .get_one::<String>("vcluster-selector")
+        .map(String::as_str)
+        .unwrap_or(vcluster::DEFAULT_LABEL_SELECTOR);
+
+    match cli::subcommand("p6m sso", matches)? {
+        Some(("auth0", matches)) => configure_auth0(
+            &environment,
+            organization,
+            matches.get_one::<String>("only").or(only),
+            &kubeconfig_path,
+            refresh_apps,
+            &naming,
+        )
+        .await
+        .context("Unable to SSO using Auth0"),
+        Some(("aws", _)) => configure_aws(&kubeconfig_path, vcluster_selector).await,
+        Some(("azure", matches)) => {
+            let subscriptions: Vec<String> = matches
+                .get_many::<String>("subscription")
+                .unwrap_or_default()
+                .cloned()
+                .collect();
+            configure_azure(&kubeconfig_path, &subscriptions).await
+        }
+        Some(("vcluster", matches)) => {
+            vcluster::configure_vcluster(
+                matches.get_one::<String>("context"),
+                vcluster_selector,
+                &kubeconfig_path,
+            )
             .await
-            .context("Unable to SSO using Auth0"),
-        Some(("aws", _)) => configure_aws().await,
-        Some(("azure", _)) => configure_azure().await,
+        }
         Some((command, _)) => Err(Error::msg(format!(
             "Unimplemented sso command: '{}'",
             command
         ))),
-        None => configure_sso(&environment, organization).await,
+        None => {
+            configure_sso(
+                &environment,
+                organization,
+                only,
+                &kubeconfig_path,
+                refresh_apps,
+                &naming,
+            )
+            .await
+        }
     }?;
 
     Ok(())
@@ -39,9 +102,21 @@ pub async fn execute(environment: P6mEnvironment, matches: &ArgMatches) -> Resul
 async fn configure_sso(
     environment: &P6mEnvironment,
     organization: Option<&String>,
+    only: Option<&String>,
+    kubeconfig_path: &Path,
+    refresh_apps: bool,
+    naming: &KubeNaming,
 ) -> Result<(), Error> {
-    configure_auth0(environment, organization).await?;
-    // configure_aws().await?;
-    // configure_azure().await?;
+    configure_auth0(
+        environment,
+        organization,
+        only,
+        kubeconfig_path,
+        refresh_apps,
+        naming,
+    )
+    .await?;
+    // configure_aws(kubeconfig_path, vcluster::DEFAULT_LABEL_SELECTOR).await?;
+    // configure_azure(kubeconfig_path, &[]).await?;
     Ok(())
 }
@@ -1,9 +1,4 @@
-use std::{
-    collections::BTreeMap,
-    convert::TryFrom,
-    fs,
-    path::{Path, PathBuf},
-};
+use std::{collections::BTreeMap, convert::TryFrom, path::Path};
 
 use anyhow::{Context, Error};
 use k8s_openapi::api::core::v1::Secret;
@@ -14,8 +9,60 @@ use kube::{
 };
 use log::info;
 
-pub async fn update_vcluster_kubecfgs(options: &KubeConfigOptions) -> Result<(), Error> {
-    let config = create_config(options)
+use super::kubeconfig;
+
+/// Labels the organization controller puts on the secrets holding each vcluster's kubeconfig.
+pub const DEFAULT_LABEL_SELECTOR: &str =
+    "p6m.dev/component=kubeconfig,meta.p6m.dev/controller=organization-controller-vcluster";
+
+/// Adds the vclusters on the host cluster behind `context`, or the current context, in the kubeconfig at `path`.
+pub async fn configure_vcluster(
+    context: Option<&String>,
+    label_selector: &str,
+    path: &Path,
+) -> Result<(), Error> {
+    if let Some(context) = context {
+        let kubeconfig = Kubeconfig::read_from(path)
+            .with_context(|| format!("unable to read {}", path.to_string_lossy()))?;
+        check_context(&kubeconfig, context)?;
+    }
+
+    let options = KubeConfigOptions {
+        context: context.cloned(),
+        ..Default::default()
+    };
+    update_vcluster_kubecfgs(&options, label_selector, path).await
+}
+
+fn check_context(kubeconfig: &Kubeconfig, context: &str) -> Result<(), Error> {
+    if kubeconfig.contexts.iter().any(|c| c.name == context) {
+        return Ok(());
+    }
+
+    let available: Vec<&str> = kubeconfig
+        .contexts
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect();
+    Err(anyhow::anyhow!(
+        "no context named '{}' in the kubeconfig; available contexts: {}",
+        context,
+        if available.is_empty() {
+            "none".to_string()
+        } else {
+            available.join(", ")
+        }
+    ))
+}
+
+/// Adds the vclusters found on the cluster selected by `options` in the kubeconfig at `path`, using the
+/// kubeconfig secrets matching `label_selector`.
+pub async fn update_vcluster_kubecfgs(
+    options: &KubeConfigOptions,
+    label_selector: &str,
+    path: &Path,
+) -> Result<(), Error> {
+    let config = create_config(options, path)
         .await
         .context("could not create kube config")?;
 
@@ -25,13 +72,8 @@ pub async fn update_vcluster_kubecfgs(options: &KubeConfigOptions) -> Result<(),
 
     let secret_api: kube::Api<Secret> = kube::Api::all(client.clone());
 
-    for secret in secret_api
-        .list(&ListParams::default().labels(
-            "p6m.dev/component=kubeconfig,meta.p6m.dev/controller=organization-controller-vcluster",
-        ))
-        .await?
-    {
-        match update_kubeconfig(&secret).await {
+    for secret in secret_api.list(&list_params(label_selector)).await? {
+        match update_kubeconfig(&secret, path).await {
             Ok(update_res) => info!("vcluster: update-kubectx: {}", update_res),
             Err(err) => log::warn!("vcluster: unable to update kubeconfig: {}", err),
         }
@@ -40,8 +82,13 @@ pub async fn update_vcluster_kubecfgs(options: &KubeConfigOptions) -> Result<(),
     Ok(())
 }
 
-async fn create_config(options: &KubeConfigOptions) -> Result<Config, Error> {
-    match Config::from_kubeconfig(options).await {
+fn list_params(label_selector: &str) -> ListParams {
+    ListParams::default().labels(label_selector)
+}
+
+async fn create_config(options: &KubeConfigOptions, path: &Path) -> Result<Config, Error> {
+    let kubeconfig = Kubeconfig::read_from(path)?;
+    match Config::from_custom_kubeconfig(kubeconfig, options).await {
         Ok(config) => Ok(config),
         Err(err) => {
             log::warn!("vcluster: unable to create config: {}", err);
@@ -54,12 +101,8 @@ async fn create_client(config: &Config) -> Result<Client, Error> {
     kube::Client::try_from(config.clone()).context("could not create client")
 }
 
-async fn update_kubeconfig(secret: &Secret) -> Result<String, Error> {
-    let path = dirs::home_dir()
-        .map(|path| path.join(".kube").join("config"))
-        .unwrap_or_else(|| PathBuf::from(".kube").join("config"));
-
-    let kubeconfig = Kubeconfig::read_from(path.as_path()).unwrap_or(Kubeconfig::default());
+async fn update_kubeconfig(secret: &Secret, path: &Path) -> Result<String, Error> {
+    let kubeconfig = Kubeconfig::read_from(path).unwrap_or(Kubeconfig::default());
 
     let config = String::from_utf8(
         secret
@@ -91,7 +134,7 @@ async fn update_kubeconfig(secret: &Secret) -> Result<String, Error> {
         .merge(new_kubeconfig)
         .context("unable to merge configs")?;
 
-    save_kubeconfig(&kubeconfig, path.as_path())
+    save_kubeconfig(&kubeconfig, path)
         .await
         .context("unable to save kube config")?;
 
@@ -164,8 +207,55 @@ fn uniqueify_kubeconfig(kubeconfig: &mut Kubeconfig) -> Result<String, Error> {
 }
 
 async fn save_kubeconfig(kubeconfig: &Kubeconfig, path: &Path) -> Result<(), Error> {
-    let yaml = serde_yaml::to_string(kubeconfig).context("unable to convert kubeconfig to yaml")?;
-    fs::write(path, yaml).context("unable to write kubeconfig")?;
+    kubeconfig::write(path, kubeconfig)
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_params() {
+        assert_eq!(
+            list_params(DEFAULT_LABEL_SELECTOR)
+                .label_selector
+                .as_deref(),
+            Some(DEFAULT_LABEL_SELECTOR)
+        );
+        assert_eq!(
+            list_params("app=vcluster").label_selector.as_deref(),
+            Some("app=vcluster")
+        );
+    }
+
+    #[test]
+    fn test_check_context() {
+        let kubeconfig = Kubeconfig::from_yaml(
+            r#"
+apiVersion: v1
+kind: Config
+contexts:
+  - name: host-dev
+    context:
+      cluster: host-dev
+      user: host-dev
+  - name: host-prod
+    context:
+      cluster: host-prod
+      user: host-prod
+"#,
+        )
+        .unwrap();
+
+        assert!(check_context(&kubeconfig, "host-prod").is_ok());
+        let err = check_context(&kubeconfig, "host-stage").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no context named 'host-stage' in the kubeconfig; available contexts: host-dev, host-prod"
+        );
+        assert!(check_context(&Kubeconfig::default(), "host-dev")
+            .unwrap_err()
+            .to_string()
+            .ends_with("available contexts: none"));
+    }
 }
@@ -0,0 +1,36 @@
+//! Helpers shared by the unit tests.
+
+use camino::Utf8PathBuf;
+use std::ops::Deref;
+use std::path::Path;
+
+/// A unique path under the system temp dir, removed with everything in it when dropped, even if the test panics
+/// first. The directory itself isn't created, so tests can check code that creates it.
+pub struct TempDir(Utf8PathBuf);
+
+impl TempDir {
+    pub fn new(prefix: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("p6m-{}-{}", prefix, uuid::Uuid::new_v4()));
+        TempDir(Utf8PathBuf::from_path_buf(path).expect("temp dir isn't UTF-8"))
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Utf8PathBuf;
+
+    fn deref(&self) -> &Utf8PathBuf {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        self.0.as_std_path()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -1,15 +1,16 @@
 use anyhow::Error;
 use clap::ArgMatches;
-use inquire::MultiSelect;
 use log::{info, warn};
 use minijinja::render;
 
-use crate::models::git::GithubLevel;
+use crate::cli::{self, P6mEnvironment};
+use crate::context::resolve_organization;
 use crate::models::git::Repository;
+use crate::prompt;
 
-pub async fn execute(matches: &ArgMatches) -> Result<(), Error> {
-    match matches.subcommand() {
-        Some(("generate", subargs)) => generate(subargs).await,
+pub async fn execute(environment: P6mEnvironment, matches: &ArgMatches) -> Result<(), Error> {
+    match cli::subcommand("p6m tilt", matches)? {
+        Some(("generate", subargs)) => generate(&environment, subargs).await,
         Some((command, _)) => Err(Error::msg(format!(
             "Unimplemented tilt command: '{}'",
             command
@@ -20,8 +21,8 @@ pub async fn execute(matches: &ArgMatches) -> Result<(), Error> {
     Ok(())
 }
 
-async fn generate(_matches: &ArgMatches) -> Result<(), Error> {
-    let org_path = GithubLevel::current()?;
+async fn generate(environment: &P6mEnvironment, matches: &ArgMatches) -> Result<(), Error> {
+    let org_path = resolve_organization(environment, None)?;
 
     if let Some(organization) = org_path.organization() {
         let repositories = organization
@@ -30,9 +31,7 @@ async fn generate(_matches: &ArgMatches) -> Result<(), Error> {
             .collect::<Vec<Repository>>();
 
         if let Ok(selected_repositories) =
-            MultiSelect::new("Applications to include:", repositories)
-                .with_page_size(25)
-                .prompt()
+            prompt::multi_select("Applications to include:", repositories, matches, 25).prompt()
         {
             let applications = selected_repositories
                 .iter()
@@ -0,0 +1,49 @@
+use crate::{
+    auth::{Claims, TokenRepository, TryReason},
+    cli::P6mEnvironment,
+    exit_code, AuthToken,
+};
+use anyhow::{Context, Error};
+use clap::ArgMatches;
+use std::io::{stdout, Write};
+
+/// Prints the current access token, refreshing it first if needed, for use as `$(p6m token)`.
+pub async fn execute(environment: P6mEnvironment, matches: &ArgMatches) -> Result<(), Error> {
+    let organization = matches
+        .try_get_one::<String>("organization-name")
+        .unwrap_or(None);
+
+    let mut token_repository = TokenRepository::new(&environment.auth_n, &environment.auth_dir)?;
+
+    if let Some(organization) = organization {
+        token_repository
+            .with_organization(organization)
+            .context("Unknown organization")?
+            .with_scope(
+                "roles",
+                Claims {
+                    roles: Some(vec!["*".into()]), // ["*"] is a special case to allow any
+                    ..Default::default()
+                },
+            );
+    }
+
+    if let Ok(Some(audience)) = matches.try_get_one::<String>("audience") {
+        token_repository.with_audience(audience)?;
+    }
+
+    token_repository
+        .try_refresh_or_login(&TryReason::TokenCommand)
+        .await?;
+
+    let token = token_repository
+        .read_token(AuthToken::Access)
+        .context("unable to read access token")?
+        .ok_or_else(|| exit_code::not_logged_in("missing access token. Please run `p6m login`"))?;
+
+    let mut stdout = stdout();
+    write!(stdout, "{token}")?;
+    stdout.flush()?;
+
+    Ok(())
+}
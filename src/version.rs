@@ -0,0 +1,69 @@
+use std::fmt::{self, Display, Formatter};
+
+use anyhow::Error;
+use clap::{crate_version, ArgMatches};
+use serde::Serialize;
+
+include!(concat!(env!("OUT_DIR"), "/version_constants.rs"));
+
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq)]
+pub enum Output {
+    Default,
+    Json,
+}
+
+/// Describes the exact build of the running binary.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionData {
+    pub version: String,
+    pub commit_hash: String,
+    pub is_dirty: bool,
+}
+
+impl VersionData {
+    fn short_hash(&self) -> &str {
+        self.commit_hash
+            .get(..7)
+            .unwrap_or(self.commit_hash.as_str())
+    }
+}
+
+impl Display for VersionData {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.commit_hash.is_empty() {
+            return write!(f, "{}", self.version);
+        }
+
+        write!(f, "{} ({}", self.version, self.short_hash())?;
+        if self.is_dirty {
+            write!(f, "-dirty")?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl From<VersionData> for clap::builder::Str {
+    fn from(value: VersionData) -> Self {
+        value.to_string().into()
+    }
+}
+
+pub fn current_version() -> VersionData {
+    VersionData {
+        version: crate_version!().to_string(),
+        commit_hash: GIT_COMMIT_HASH.to_string(),
+        is_dirty: GIT_IS_DIRTY,
+    }
+}
+
+pub fn execute(matches: &ArgMatches) -> Result<(), Error> {
+    let version = current_version();
+
+    match matches.get_one::<Output>("output") {
+        Some(Output::Json) => println!("{}", serde_json::to_string_pretty(&version)?),
+        None | Some(Output::Default) => println!("{version}"),
+    }
+
+    Ok(())
+}
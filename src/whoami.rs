@@ -1,17 +1,18 @@
-use crate::auth::{Claims, TokenRepository, TryReason};
+use crate::auth::{Claims, OpenIdDiscoveryDocument, TokenRepository, TryReason, UserInfo};
 use crate::cli::P6mEnvironment;
 use crate::AuthToken;
 use anyhow::{Context, Error};
 use chrono::{DateTime, Utc};
 use clap::ArgMatches;
-use log::debug;
 use serde::{Deserialize, Serialize};
 use std::env;
 
 #[derive(clap::ValueEnum, Clone, Debug, PartialEq)]
 pub enum Output {
     Default,
+    Table,
     Json,
+    Yaml,
     K8sAuth,
     AccessToken,
     IdToken,
@@ -70,7 +71,11 @@ pub async fn execute(environment: P6mEnvironment, matches: &ArgMatches) -> Resul
         .try_get_one::<String>("authn-app-id")
         .unwrap_or(None);
 
+    // kuberlr only needs any credential to resolve the server version
+    let resolving_version = env::var("KUBERLR_RESOLVING_VERSION").is_ok();
+
     let mut token_repository = TokenRepository::new(&environment.auth_n, &environment.auth_dir)?;
+    token_repository.with_offline(environment.offline || resolving_version);
 
     if let Some(organization) = organization {
         if output == Some(&Output::K8sAuth) {
@@ -95,30 +100,36 @@ pub async fn execute(environment: P6mEnvironment, matches: &ArgMatches) -> Resul
             );
     }
 
-    match token_repository
-        .try_refresh(&TryReason::WhoAmICommand)
-        .await
-        .map_err(|e| {
-            debug!("Unable to refresh: {}", e);
-            e
-        })
-        .ok()
-    {
-        Some(token_repository) => token_repository,
-        None => {
-            // TODO
-            debug!("Unable to refresh, trying to login");
-            token_repository
-                .force()
-                .try_login(&TryReason::WhoAmICommand)
-                .await?
-        }
-    };
+    if let Ok(Some(scopes)) = matches.try_get_many::<String>("scope") {
+        scopes.for_each(|scope| {
+            token_repository.with_scope(scope, Claims::default());
+        });
+    }
+
+    if let Ok(Some(true)) = matches.try_get_one::<bool>("refresh-only") {
+        token_repository
+            .try_refresh(&TryReason::WhoAmICommand)
+            .await
+            .context("Please re-run `p6m login`")?;
+        return Ok(());
+    }
+
+    token_repository
+        .try_refresh_or_login(&TryReason::WhoAmICommand)
+        .await?;
+
+    if let Ok(Some(true)) = matches.try_get_one::<bool>("all-orgs") {
+        return all_orgs(&environment, &token_repository, output).await;
+    }
+
+    if let Ok(Some(true)) = matches.try_get_one::<bool>("userinfo") {
+        return user_info(&token_repository, output).await;
+    }
 
     match (output, authn_app_id) {
         (Some(Output::K8sAuth), Some(authn_app_id)) => {
             // Skip re-authenticating if kuberlr is resolving the version
-            if !env::var("KUBERLR_RESOLVING_VERSION").is_ok() {
+            if !resolving_version {
                 token_repository
                     .with_authn_app_id(authn_app_id)
                     .await
@@ -138,6 +149,8 @@ pub async fn execute(environment: P6mEnvironment, matches: &ArgMatches) -> Resul
                 )
                 .await?,
             Some(Output::Json) => token_repository.to_json()?,
+            Some(Output::Yaml) => token_repository.to_yaml()?,
+            Some(Output::Table) => token_repository.to_table(),
             Some(Output::IdToken) => token_repository
                 .clone()
                 .read_token(AuthToken::Id)
@@ -155,6 +168,126 @@ pub async fn execute(environment: P6mEnvironment, matches: &ArgMatches) -> Resul
     Ok(())
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OrgAccess {
+    id: String,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    roles: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    permissions: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Obtains an organization-scoped token for each organization on the ID token, reporting the
+/// roles and permissions it grants, or why it couldn't be obtained.
+async fn all_orgs(
+    environment: &P6mEnvironment,
+    token_repository: &TokenRepository,
+    output: Option<&Output>,
+) -> Result<(), Error> {
+    let mut access = vec![];
+
+    for (id, name) in token_repository.organizations()? {
+        let mut org_repository = TokenRepository::new(&environment.auth_n, &environment.auth_dir)?;
+        org_repository.with_offline(environment.offline);
+
+        let claims = async {
+            org_repository.with_organization(&id)?.with_scope(
+                "roles",
+                Claims {
+                    roles: Some(vec!["*".into()]), // ["*"] is a special case to allow any
+                    ..Default::default()
+                },
+            );
+            org_repository
+                .try_refresh(&TryReason::WhoAmICommand)
+                .await?;
+            org_repository
+                .read_claims(AuthToken::Id)?
+                .context("missing claims on the ID Token")
+        }
+        .await;
+
+        access.push(match claims {
+            Ok(claims) => OrgAccess {
+                id,
+                name,
+                roles: claims.roles,
+                permissions: claims.permissions,
+                error: None,
+            },
+            Err(e) => OrgAccess {
+                id,
+                name,
+                roles: None,
+                permissions: None,
+                error: Some(format!("{e:#}")),
+            },
+        });
+    }
+
+    match output {
+        Some(Output::Json) => println!("{}", serde_json::to_string_pretty(&access)?),
+        Some(Output::Yaml) => print!("{}", serde_yaml::to_string(&access)?),
+        None | Some(Output::Default) | Some(Output::Table) => {
+            let list = |values: &Option<Vec<String>>| match values {
+                Some(values) if !values.is_empty() => values.join(","),
+                _ => "-".to_owned(),
+            };
+            for org in access {
+                match org.error {
+                    Some(error) => println!("{}\t{}\terror: {}", org.name, org.id, error),
+                    None => println!(
+                        "{}\t{}\troles: {}\tpermissions: {}",
+                        org.name,
+                        org.id,
+                        list(&org.roles),
+                        list(&org.permissions)
+                    ),
+                }
+            }
+        }
+        Some(output) => {
+            return Err(Error::msg(format!(
+                "--all-orgs doesn't support --output {output:?}"
+            )))
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints what the identity provider's userinfo endpoint reports for the access token.
+async fn user_info(
+    token_repository: &TokenRepository,
+    output: Option<&Output>,
+) -> Result<(), Error> {
+    let access_token = token_repository
+        .read_token(AuthToken::Access)
+        .context("unable to read access token")?
+        .context("missing access token")?;
+    let discovery = OpenIdDiscoveryDocument::discover(&token_repository.auth_n)
+        .await
+        .context("Unable to discover the userinfo endpoint")?;
+    let user_info = UserInfo::request(&discovery.userinfo_endpoint, &access_token).await?;
+
+    match output {
+        Some(Output::Json) => println!("{}", user_info.to_json()?),
+        Some(Output::Yaml) => print!("{}", serde_yaml::to_string(&user_info)?),
+        None | Some(Output::Default) | Some(Output::Table) => println!("{user_info}"),
+        Some(output) => {
+            return Err(Error::msg(format!(
+                "--userinfo doesn't support --output {output:?}"
+            )))
+        }
+    }
+
+    Ok(())
+}
+
 async fn k8s_auth(
     token_repository: &TokenRepository,
     _organization: &String,
@@ -1,33 +1,33 @@
 use crate::workstation::check::common::*;
-use clap::ArgMatches;
 use dirs::home_dir;
+use std::fmt::Write;
 use std::process::Command;
 
-pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
-    check_archetect_binary(args)?;
-    check_archetect_config(args)?;
-    Ok(())
-}
+pub const CHECKS: &[Check] = &[check_archetect_binary, check_archetect_config];
 
-fn check_archetect_binary(_args: &ArgMatches) -> anyhow::Result<()> {
+fn check_archetect_binary(out: &mut String) -> anyhow::Result<()> {
     perform_check(
+        out,
         "Archetect",
         Command::new("archetect").arg("--version"),
         "core/archetect/#installation",
     )
 }
 
-fn check_archetect_config(_args: &ArgMatches) -> anyhow::Result<()> {
-    println!("\n{CHECK_PREFIX} Checking Archetect Configuration");
+fn check_archetect_config(out: &mut String) -> anyhow::Result<()> {
+    writeln!(out, "\n{CHECK_PREFIX} Checking Archetect Configuration")?;
     if !home_dir()
         .expect("Home Directory Required")
         .join(".archetect/etc/archetect.yaml")
         .exists()
     {
-        println!("\t{CHECK_ERROR} Archetect is not configured correctly for your environment.");
-        print_see_also("core/archetect/#configuration");
+        writeln!(
+            out,
+            "\t{CHECK_ERROR} Archetect is not configured correctly for your environment."
+        )?;
+        print_see_also(out, "core/archetect/#configuration")?;
     } else {
-        println!("\t{CHECK_SUCCESS} Archetect Configured");
+        writeln!(out, "\t{CHECK_SUCCESS} Archetect Configured")?;
     }
     Ok(())
 }
@@ -0,0 +1,54 @@
+use crate::workstation::check::common::*;
+use crate::{
+    auth::{token_valid, TokenRepository},
+    cli::P6mEnvironment,
+    AuthToken,
+};
+use chrono::Duration;
+use std::fmt::Write;
+
+/// Reads the stored tokens without refreshing them; a check shouldn't prompt for a login.
+pub fn check_auth(environment: &P6mEnvironment, out: &mut String) -> anyhow::Result<()> {
+    writeln!(out, "\n{CHECK_PREFIX} Checking p6m Login")?;
+
+    let token_repository = TokenRepository::new(&environment.auth_n, &environment.auth_dir)?;
+    if !token_repository.is_logged_in() {
+        writeln!(
+            out,
+            "\t{CHECK_ERROR} Not logged in. Run `p6m login` before using organization commands."
+        )?;
+        return Ok(());
+    }
+
+    match token_repository.clone().read_expiration(AuthToken::Id) {
+        Ok(expiration) if token_valid(expiration, Duration::zero()) => {
+            writeln!(
+                out,
+                "\t{CHECK_SUCCESS} Logged in; tokens valid until {expiration}"
+            )?;
+        }
+        Ok(expiration) => {
+            writeln!(out, "\t{CHECK_WARN} Logged in, but tokens expired at {expiration}. They will be refreshed the next time they're needed, or run `p6m login`.")?;
+        }
+        Err(_) => {
+            writeln!(
+                out,
+                "\t{CHECK_WARN} Logged in, but the token expiry couldn't be read. Run `p6m login`."
+            )?;
+        }
+    }
+
+    match token_repository.organizations() {
+        Ok(organizations) if !organizations.is_empty() => writeln!(
+            out,
+            "\t{CHECK_SUCCESS} Member of {} organization(s)",
+            organizations.len()
+        )?,
+        _ => writeln!(
+            out,
+            "\t{CHECK_WARN} Your token lists no organizations. Ask an organization admin for access, then run `p6m login`."
+        )?,
+    }
+
+    Ok(())
+}
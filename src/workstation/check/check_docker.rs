@@ -1,16 +1,49 @@
 use crate::workstation::check::common::*;
-use clap::ArgMatches;
+use std::fmt::Write;
 use std::process::Command;
 
-pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
-    check_docker(args)?;
-    Ok(())
-}
+pub const CHECKS: &[Check] = &[check_docker, check_docker_daemon];
 
-fn check_docker(_args: &ArgMatches) -> anyhow::Result<()> {
+fn check_docker(out: &mut String) -> anyhow::Result<()> {
     perform_check(
+        out,
         "Docker",
         Command::new("docker").arg("--version"),
         "core/docker/",
     )
 }
+
+/// The CLI being installed doesn't mean the daemon (or Docker Desktop) is running.
+fn check_docker_daemon(out: &mut String) -> anyhow::Result<()> {
+    writeln!(out, "\n{CHECK_PREFIX} Checking Docker Daemon")?;
+
+    match Command::new("docker")
+        .args(["info", "--format", "{{.ServerVersion}}"])
+        .output()
+    {
+        Ok(output) if output.status.success() => {
+            let version = String::from_utf8_lossy(&output.stdout);
+            writeln!(
+                out,
+                "\t{CHECK_SUCCESS} Docker daemon is running (server {})",
+                version.trim()
+            )?;
+        }
+        Ok(_) => {
+            writeln!(
+                out,
+                "\t{CHECK_WARN} Docker is installed, but the daemon is not reachable. Is Docker running?"
+            )?;
+            print_see_also(out, "core/docker/")?;
+        }
+        // The Docker check already reports the missing CLI
+        Err(_error) => {
+            writeln!(
+                out,
+                "\t{CHECK_WARN} Skipped, since the docker CLI was not found"
+            )?;
+        }
+    }
+
+    Ok(())
+}
@@ -1,12 +1,13 @@
 use crate::workstation::check::common::*;
-use clap::ArgMatches;
 use std::process::Command;
 
-pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
-    check_dotnet_binary(args)?;
-    Ok(())
-}
+pub const CHECKS: &[Check] = &[check_dotnet_binary];
 
-pub fn check_dotnet_binary(_args: &ArgMatches) -> anyhow::Result<()> {
-    perform_check("dotnet", Command::new("dotnet").arg("--version"), "dotnet/")
+pub fn check_dotnet_binary(out: &mut String) -> anyhow::Result<()> {
+    perform_check(
+        out,
+        "dotnet",
+        Command::new("dotnet").arg("--version"),
+        "dotnet/",
+    )
 }
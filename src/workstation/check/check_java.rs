@@ -1,34 +1,42 @@
 use crate::workstation::check::common::*;
-use clap::ArgMatches;
 use dirs::home_dir;
+use std::fmt::Write;
 use std::process::Command;
 
-pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
-    check_java(args)?;
-    check_maven_binary(args)?;
-    check_maven_settings(args)?;
-    Ok(())
-}
+pub const CHECKS: &[Check] = &[check_java, check_maven_binary, check_maven_settings];
 
-pub fn check_java(_args: &ArgMatches) -> anyhow::Result<()> {
-    perform_check("Java", Command::new("java").arg("--version"), "java/#java")
+pub fn check_java(out: &mut String) -> anyhow::Result<()> {
+    perform_check(
+        out,
+        "Java",
+        Command::new("java").arg("--version"),
+        "java/#java",
+    )
 }
 
-pub fn check_maven_binary(_args: &ArgMatches) -> anyhow::Result<()> {
-    perform_check("Maven", Command::new("mvn").arg("--version"), "java/#maven")
+pub fn check_maven_binary(out: &mut String) -> anyhow::Result<()> {
+    perform_check(
+        out,
+        "Maven",
+        Command::new("mvn").arg("--version"),
+        "java/#maven",
+    )
 }
 
-pub fn check_maven_settings(_args: &ArgMatches) -> anyhow::Result<()> {
-    println!("\n{CHECK_PREFIX} Checking Maven Configuration");
+pub fn check_maven_settings(out: &mut String) -> anyhow::Result<()> {
+    writeln!(out, "\n{CHECK_PREFIX} Checking Maven Configuration")?;
     if !home_dir()
         .expect("Home Directory Required")
         .join(".m2/settings.xml")
         .exists()
     {
-        println!("\t{CHECK_ERROR} Maven is not configured correctly for your environment.");
-        print_see_also("java/#maven");
+        writeln!(
+            out,
+            "\t{CHECK_ERROR} Maven is not configured correctly for your environment."
+        )?;
+        print_see_also(out, "java/#maven")?;
     } else {
-        println!("\t{CHECK_SUCCESS} Maven Configured");
+        writeln!(out, "\t{CHECK_SUCCESS} Maven Configured")?;
     }
     Ok(())
 }
@@ -1,23 +1,20 @@
 use crate::workstation::check::common::*;
-use clap::ArgMatches;
 use std::process::Command;
 
-pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
-    check_node(args)?;
-    check_npm(args)?;
-    Ok(())
-}
+pub const CHECKS: &[Check] = &[check_node, check_npm];
 
-fn check_node(_args: &ArgMatches) -> anyhow::Result<()> {
+fn check_node(out: &mut String) -> anyhow::Result<()> {
     perform_check(
+        out,
         "NodeJS",
         Command::new("node").arg("--version"),
         "javascript/#nodejs",
     )
 }
 
-fn check_npm(_args: &ArgMatches) -> anyhow::Result<()> {
+fn check_npm(out: &mut String) -> anyhow::Result<()> {
     perform_check(
+        out,
         "NPM",
         Command::new("npm").arg("--version"),
         "javascript/#npm",
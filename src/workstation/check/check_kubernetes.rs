@@ -1,32 +1,29 @@
 use crate::workstation::check::common::*;
-use clap::ArgMatches;
 use std::process::Command;
 
-pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
-    check_kubectl(args)?;
-    check_tilt(args)?;
-    check_k9s(args)?;
-    Ok(())
-}
+pub const CHECKS: &[Check] = &[check_kubectl, check_tilt, check_k9s];
 
-fn check_kubectl(_args: &ArgMatches) -> anyhow::Result<()> {
+fn check_kubectl(out: &mut String) -> anyhow::Result<()> {
     perform_check(
+        out,
         "kubectl",
         Command::new("kubectl").arg("version").arg("--client=true"),
         "core/kubernetes/#kubectl",
     )
 }
 
-fn check_tilt(_args: &ArgMatches) -> anyhow::Result<()> {
+fn check_tilt(out: &mut String) -> anyhow::Result<()> {
     perform_check(
+        out,
         "Tilt",
         Command::new("tilt").arg("version"),
         "core/kubernetes/#tilt",
     )
 }
 
-fn check_k9s(_args: &ArgMatches) -> anyhow::Result<()> {
+fn check_k9s(out: &mut String) -> anyhow::Result<()> {
     perform_check(
+        out,
         "k9s",
         Command::new("k9s").arg("version"),
         "core/kubernetes/#k9s",
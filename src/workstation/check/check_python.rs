@@ -1,21 +1,22 @@
 use crate::workstation::check::common::*;
-use clap::ArgMatches;
 use std::process::Command;
 
-pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
-    check_python(args)?;
-    check_pip(args)?;
-    Ok(())
-}
+pub const CHECKS: &[Check] = &[check_python, check_pip];
 
-fn check_python(_args: &ArgMatches) -> anyhow::Result<()> {
+fn check_python(out: &mut String) -> anyhow::Result<()> {
     perform_check(
+        out,
         "Python",
         Command::new("python3").arg("--version"),
         "python/#python",
     )
 }
 
-fn check_pip(_args: &ArgMatches) -> anyhow::Result<()> {
-    perform_check("PIP", Command::new("pip3").arg("--version"), "python/#pip")
+fn check_pip(out: &mut String) -> anyhow::Result<()> {
+    perform_check(
+        out,
+        "PIP",
+        Command::new("pip3").arg("--version"),
+        "python/#pip",
+    )
 }
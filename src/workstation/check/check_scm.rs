@@ -1,37 +1,38 @@
 use crate::workstation::check::common::*;
-use clap::ArgMatches;
+use std::fmt::Write;
 use std::process::Command;
 
-pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
-    check_git_installed(args)?;
-    check_git_author(args)?;
+pub const CHECKS: &[Check] = &[check_git_installed, check_git_author];
 
-    Ok(())
-}
-
-pub fn check_git_installed(_args: &ArgMatches) -> anyhow::Result<()> {
-    perform_check("Git", Command::new("git").arg("--version"), "core/scm/#git")
+pub fn check_git_installed(out: &mut String) -> anyhow::Result<()> {
+    perform_check(
+        out,
+        "Git",
+        Command::new("git").arg("--version"),
+        "core/scm/#git",
+    )
 }
 
-pub fn check_git_author(_args: &ArgMatches) -> anyhow::Result<()> {
-    println!("\n{CHECK_PREFIX} Checking Git User Name and Email");
+pub fn check_git_author(out: &mut String) -> anyhow::Result<()> {
+    writeln!(out, "\n{CHECK_PREFIX} Checking Git User Name and Email")?;
     if let Ok(config) = git2::Config::open_default() {
         let name = config.get_string("user.name");
         let email = config.get_string("user.email");
 
         if let (Ok(name), Ok(email)) = (name, email) {
             if !name.is_empty() && !email.is_empty() {
-                println!("\t{CHECK_SUCCESS} {} <{}>", name, email);
+                writeln!(out, "\t{CHECK_SUCCESS} {} <{}>", name, email)?;
             }
         } else {
-            println!(
+            writeln!(
+                out,
                 "\t{CHECK_ERROR} Git User Name or Email is empty.  Archetypes may use your Git\n\
             User Name and Email to answer questions about code authorship."
-            );
+            )?;
 
-            println!("\n\tExecute the following command to configure git:");
-            println!("\n\tgit config --global user.name \"<your name>\"");
-            println!("\tgit config --global user.email \"<your email>\"");
+            writeln!(out, "\n\tExecute the following command to configure git:")?;
+            writeln!(out, "\n\tgit config --global user.name \"<your name>\"")?;
+            writeln!(out, "\tgit config --global user.email \"<your email>\"")?;
         }
     }
 
@@ -1,30 +1,39 @@
+use crate::self_update::{is_newer, latest_release};
 use crate::workstation::check::common::*;
 use clap::{crate_version, ArgMatches};
-use log::error;
-use octocrab::Octocrab;
+use log::debug;
+use std::time::Duration;
+
+const LATEST_RELEASE_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub async fn execute(_args: &ArgMatches) -> anyhow::Result<()> {
     println!("\n{CHECK_PREFIX} Checking p6m CLI Version");
-    let octocrab = Octocrab::builder().build()?;
-    match octocrab
-        .repos("p6m-dev", "p6m-cli")
-        .releases()
-        .get_latest()
-        .await
-    {
-        Ok(release) => {
+    let current_version = format!("v{}", crate_version!());
+    match tokio::time::timeout(LATEST_RELEASE_TIMEOUT, latest_release()).await {
+        Ok(Ok(release)) => {
             let latest_version = release.tag_name;
-            let current_version = format!("v{}", crate_version!());
-            if latest_version == current_version {
-                println!("\t{CHECK_SUCCESS} {latest_version}");
+            if let Ok(false) = is_newer(&latest_version, crate_version!()) {
+                println!("\t{CHECK_SUCCESS} {current_version}");
             } else {
                 println!("\t{CHECK_WARN} The current version of the p6m CLI is {current_version}, but {latest_version} is available.");
-                print_see_also("core/p6m-cli");
+                println!("\n\t   Run `p6m self update` to upgrade.");
+                let mut see_also = String::new();
+                print_see_also(&mut see_also, "core/p6m-cli")?;
+                print!("{see_also}");
             }
         }
-        Err(error) => {
-            error!("Failure checking p6m-cli version: {error}");
+        Ok(Err(error)) => {
+            debug!("Failure checking p6m-cli version: {error}");
+            print_version_unknown(&current_version);
+        }
+        Err(_) => {
+            debug!("Timed out checking p6m-cli version after {LATEST_RELEASE_TIMEOUT:?}");
+            print_version_unknown(&current_version);
         }
     }
     Ok(())
 }
+
+fn print_version_unknown(current_version: &str) {
+    println!("\t{CHECK_WARN} {current_version} (couldn't check the latest version; are you offline or rate limited?)");
+}
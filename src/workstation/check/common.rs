@@ -1,5 +1,6 @@
-use clap::builder::PossibleValue;
+use clap::builder::{PossibleValue, PossibleValuesParser, TypedValueParser};
 use clap::ValueEnum;
+use std::fmt::{self, Write};
 use std::io::{BufRead, Lines};
 use std::process::Command;
 use strum_macros::{Display, EnumIter};
@@ -10,49 +11,60 @@ pub const CHECK_ERROR: &str = "🔴";
 pub const CHECK_WARN: &str = "🟡";
 pub const DOCS_PREFIX: &str = "https://developer.p6m.dev/docs/workstation";
 
-pub fn print_see_also(path: &str) {
-    println!("\n\t   See: {DOCS_PREFIX}/{path}");
+/// A side-effect free check that writes its findings to a report instead of stdout, so that
+/// checks can run concurrently and still be printed in order.
+pub type Check = fn(&mut String) -> anyhow::Result<()>;
+
+pub fn print_see_also(out: &mut String, path: &str) -> fmt::Result {
+    print_see_link(out, &format!("{DOCS_PREFIX}/{path}"))
 }
 
-pub fn print_success_lines(lines: Lines<&[u8]>, all_lines: bool) {
-    lines
-        .map_while(Result::ok)
-        .enumerate()
-        .for_each(|(index, line)| {
-            if index == 0 || all_lines {
-                println!("\t{CHECK_SUCCESS} {line}");
-            } else {
-                println!("\t   {line}");
-            }
-        });
+/// Like `print_see_also`, for documentation that lives outside the workstation docs.
+pub fn print_see_link(out: &mut String, url: &str) -> fmt::Result {
+    writeln!(out, "\n\t   See: {url}")
+}
+
+pub fn print_success_lines(out: &mut String, lines: Lines<&[u8]>, all_lines: bool) -> fmt::Result {
+    for (index, line) in lines.map_while(Result::ok).enumerate() {
+        if index == 0 || all_lines {
+            writeln!(out, "\t{CHECK_SUCCESS} {line}")?;
+        } else {
+            writeln!(out, "\t   {line}")?;
+        }
+    }
+    Ok(())
 }
 
 pub fn perform_check(
+    out: &mut String,
     check_name: &str,
     command: &mut Command,
     doc_path: &str,
 ) -> anyhow::Result<()> {
-    println!("\n{CHECK_PREFIX} Checking {check_name}");
+    writeln!(out, "\n{CHECK_PREFIX} Checking {check_name}")?;
 
     match command.output() {
         Ok(output) => {
             if output.status.success() {
-                print_success_lines(output.stdout.lines(), false);
+                print_success_lines(out, output.stdout.lines(), false)?;
             } else {
-                println!("\t{CHECK_ERROR} {check_name} was found, but returned an unexpected Status Code: {}",  output.status.code().unwrap());
-                print_see_also(doc_path);
+                writeln!(out, "\t{CHECK_ERROR} {check_name} was found, but returned an unexpected Status Code: {}",  output.status.code().unwrap())?;
+                print_see_also(out, doc_path)?;
             }
         }
         Err(_error) => {
-            println!("\t{CHECK_ERROR} {check_name} is required, but was not found on the PATH");
-            print_see_also(doc_path);
+            writeln!(
+                out,
+                "\t{CHECK_ERROR} {check_name} is required, but was not found on the PATH"
+            )?;
+            print_see_also(out, doc_path)?;
         }
     }
 
     Ok(())
 }
 
-#[derive(Clone, Copy, EnumIter, Display)]
+#[derive(Clone, Copy, Debug, PartialEq, EnumIter, Display)]
 pub enum Ecosystem {
     #[strum(serialize = "Self")]
     P6mCli,
@@ -62,6 +74,26 @@ pub enum Ecosystem {
     JavaScript,
     Python,
     Kubernetes,
+    /// Every other ecosystem; only meaningful as a command line value.
+    All,
+}
+
+impl Ecosystem {
+    /// The ecosystems that can actually be checked, in display order.
+    pub fn checkable() -> impl Iterator<Item = Ecosystem> {
+        Ecosystem::value_variants()
+            .iter()
+            .copied()
+            .filter(|ecosystem| *ecosystem != Ecosystem::All)
+    }
+
+    /// Parses only the checkable ecosystems, for arguments where `all` makes no sense.
+    pub fn checkable_parser() -> impl TypedValueParser<Value = Ecosystem> {
+        PossibleValuesParser::new(
+            Ecosystem::checkable().filter_map(|ecosystem| ecosystem.to_possible_value()),
+        )
+        .map(|name| Ecosystem::from_str(&name, true).expect("Only checkable names are accepted"))
+    }
 }
 
 impl ValueEnum for Ecosystem {
@@ -74,6 +106,7 @@ impl ValueEnum for Ecosystem {
             Ecosystem::Java,
             Ecosystem::Python,
             Ecosystem::Kubernetes,
+            Ecosystem::All,
         ]
     }
 
@@ -86,6 +119,7 @@ impl ValueEnum for Ecosystem {
             Ecosystem::Java => PossibleValue::new("java"),
             Ecosystem::Python => PossibleValue::new("python"),
             Ecosystem::Kubernetes => PossibleValue::new("kubernetes"),
+            Ecosystem::All => PossibleValue::new("all"),
         })
     }
 }
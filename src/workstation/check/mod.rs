@@ -1,7 +1,11 @@
+use crate::cli::P6mEnvironment;
 use clap::{ArgMatches, ValueEnum};
+use common::Check;
+use tokio::task::{self, JoinHandle};
 
 mod check_archetect;
 mod check_artifact_management;
+mod check_auth;
 mod check_docker;
 mod check_dotnet;
 mod check_java;
@@ -12,15 +16,23 @@ mod check_scm;
 mod check_self;
 mod common;
 
-pub use common::Ecosystem;
+pub use common::{print_see_link, Ecosystem, CHECK_ERROR, CHECK_PREFIX, CHECK_SUCCESS, CHECK_WARN};
 
-pub async fn execute(args: &ArgMatches) -> anyhow::Result<()> {
-    if let Some(ecosystems) = args.get_many::<Ecosystem>("ecosystem") {
-        for ecosystem in ecosystems {
-            check_ecosystem(ecosystem, args).await?;
-        }
+pub async fn execute(environment: &P6mEnvironment, args: &ArgMatches) -> anyhow::Result<()> {
+    let selected = args.get_many::<Ecosystem>("ecosystem");
+    let excluded = args.get_many::<Ecosystem>("exclude");
+
+    if selected.is_some() || excluded.is_some() {
+        let selected: Vec<Ecosystem> = selected
+            .map(|ecosystems| ecosystems.copied().collect())
+            .unwrap_or_else(|| vec![Ecosystem::All]);
+        let excluded: Vec<Ecosystem> = excluded
+            .map(|ecosystems| ecosystems.copied().collect())
+            .unwrap_or_default();
+
+        check_ecosystems(environment, &select_ecosystems(&selected, &excluded), args).await?;
     } else {
-        execute_interactive(args).await?;
+        execute_interactive(environment, args).await?;
         // for ecosystem in Ecosystem::iter() {
         //     check_ecosystem(&ecosystem, args).await?;
         // }
@@ -29,9 +41,30 @@ pub async fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub async fn execute_interactive(args: &ArgMatches) -> anyhow::Result<()> {
-    let ecosystems = Ecosystem::value_variants()
-        .iter()
+/// Expands `all`, drops excluded ecosystems, and removes duplicates, keeping the order given.
+fn select_ecosystems(selected: &[Ecosystem], excluded: &[Ecosystem]) -> Vec<Ecosystem> {
+    let mut ecosystems: Vec<Ecosystem> = Vec::new();
+
+    for ecosystem in selected {
+        let expanded: Vec<Ecosystem> = match ecosystem {
+            Ecosystem::All => Ecosystem::checkable().collect(),
+            ecosystem => vec![*ecosystem],
+        };
+        for ecosystem in expanded {
+            if !excluded.contains(&ecosystem) && !ecosystems.contains(&ecosystem) {
+                ecosystems.push(ecosystem);
+            }
+        }
+    }
+
+    ecosystems
+}
+
+pub async fn execute_interactive(
+    environment: &P6mEnvironment,
+    args: &ArgMatches,
+) -> anyhow::Result<()> {
+    let ecosystems = Ecosystem::checkable()
         .map(|ecosystem| ecosystem.to_string())
         .collect::<Vec<String>>();
     let prompt = inquire::MultiSelect::new("Ecosystems:", ecosystems).with_default(&[0, 1]);
@@ -41,9 +74,7 @@ pub async fn execute_interactive(args: &ArgMatches) -> anyhow::Result<()> {
                 .iter()
                 .map(|ecosystem| Ecosystem::from_str(ecosystem, true).expect("Cannot fail"))
                 .collect::<Vec<Ecosystem>>();
-            for ecosystem in ecosystems {
-                check_ecosystem(&ecosystem, args).await?
-            }
+            check_ecosystems(environment, &ecosystems, args).await?;
         }
         Err(_) => {}
         Ok(None) => {}
@@ -52,33 +83,114 @@ pub async fn execute_interactive(args: &ArgMatches) -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn check_ecosystem(ecosystem: &Ecosystem, args: &ArgMatches) -> anyhow::Result<()> {
+fn checks(ecosystem: &Ecosystem) -> Vec<Check> {
     match ecosystem {
-        Ecosystem::Core => {
-            check_archetect::execute(args)?;
-            check_scm::execute(args)?;
-            check_docker::execute(args)?;
-            check_artifact_management::execute(args)?;
-        }
-        Ecosystem::DotNet => {
-            check_dotnet::execute(args)?;
-        }
-        Ecosystem::Java => {
-            check_java::execute(args)?;
-        }
-        Ecosystem::JavaScript => {
-            check_javascript::execute(args)?;
-        }
-        Ecosystem::Kubernetes => {
-            check_kubernetes::execute(args)?;
+        Ecosystem::Core => [
+            check_archetect::CHECKS,
+            check_scm::CHECKS,
+            check_docker::CHECKS,
+            check_artifact_management::CHECKS,
+        ]
+        .concat(),
+        Ecosystem::DotNet => check_dotnet::CHECKS.to_vec(),
+        Ecosystem::Java => check_java::CHECKS.to_vec(),
+        Ecosystem::JavaScript => check_javascript::CHECKS.to_vec(),
+        Ecosystem::Kubernetes => check_kubernetes::CHECKS.to_vec(),
+        Ecosystem::Python => check_python::CHECKS.to_vec(),
+        Ecosystem::P6mCli => vec![],
+        Ecosystem::All => Ecosystem::checkable()
+            .flat_map(|ecosystem| checks(&ecosystem))
+            .collect(),
+    }
+}
+
+/// Starts every check up front, since most of them wait on a subprocess, then prints the
+/// results in the order the ecosystems and checks were given.
+async fn check_ecosystems(
+    environment: &P6mEnvironment,
+    ecosystems: &[Ecosystem],
+    args: &ArgMatches,
+) -> anyhow::Result<()> {
+    let pending: Vec<(Ecosystem, Vec<JoinHandle<anyhow::Result<String>>>)> = ecosystems
+        .iter()
+        .map(|ecosystem| (*ecosystem, spawn_checks(ecosystem)))
+        .collect();
+
+    for (ecosystem, handles) in pending {
+        if ecosystem == Ecosystem::P6mCli {
+            check_self::execute(args).await?;
         }
-        Ecosystem::Python => {
-            check_python::execute(args)?;
+        for handle in handles {
+            print!("{}", handle.await??);
         }
-        Ecosystem::P6mCli => {
-            check_self::execute(args).await?;
+        if ecosystem == Ecosystem::Core {
+            print!("{}", auth_report(environment)?);
         }
     }
 
     Ok(())
 }
+
+fn spawn_checks(ecosystem: &Ecosystem) -> Vec<JoinHandle<anyhow::Result<String>>> {
+    checks(ecosystem)
+        .into_iter()
+        .map(|check| {
+            task::spawn_blocking(move || {
+                let mut out = String::new();
+                check(&mut out).map(|_| out)
+            })
+        })
+        .collect()
+}
+
+/// Runs an ecosystem's checks and returns their reports, in order, without printing them.
+pub async fn report(ecosystem: &Ecosystem) -> anyhow::Result<String> {
+    let mut out = String::new();
+    for handle in spawn_checks(ecosystem) {
+        out.push_str(&handle.await??);
+    }
+    Ok(out)
+}
+
+/// The report on the stored login, which is read but never refreshed.
+pub fn auth_report(environment: &P6mEnvironment) -> anyhow::Result<String> {
+    let mut out = String::new();
+    check_auth::check_auth(environment, &mut out)?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_ecosystems() {
+        let all: Vec<Ecosystem> = Ecosystem::checkable().collect();
+        assert!(!all.contains(&Ecosystem::All));
+
+        assert_eq!(select_ecosystems(&[Ecosystem::All], &[]), all);
+        assert_eq!(
+            select_ecosystems(&[Ecosystem::All], &[Ecosystem::DotNet, Ecosystem::Java]),
+            all.iter()
+                .copied()
+                .filter(|ecosystem| *ecosystem != Ecosystem::DotNet
+                    && *ecosystem != Ecosystem::Java)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            select_ecosystems(
+                &[Ecosystem::Java, Ecosystem::Core, Ecosystem::Java],
+                &[Ecosystem::Core]
+            ),
+            vec![Ecosystem::Java]
+        );
+    }
+
+    #[test]
+    fn test_checks_all() {
+        let every: usize = Ecosystem::checkable()
+            .map(|ecosystem| checks(&ecosystem).len())
+            .sum();
+        assert_eq!(checks(&Ecosystem::All).len(), every);
+    }
+}
@@ -1,19 +1,20 @@
+use crate::cli::{self, P6mEnvironment};
 use clap::ArgMatches;
 
 pub mod check;
 pub mod setup;
 
-pub async fn execute(args: &ArgMatches) -> anyhow::Result<()> {
-    match args.subcommand() {
+pub async fn execute(environment: P6mEnvironment, args: &ArgMatches) -> anyhow::Result<()> {
+    match cli::subcommand("p6m workstation", args)? {
         None => {
             let result =
                 inquire::Select::new("Workstation Command:", vec!["Check", "Setup"]).prompt();
             match result {
                 Ok("Check") => {
-                    return check::execute_interactive(args).await;
+                    return check::execute_interactive(&environment, args).await;
                 }
                 Ok("Setup") => {
-                    return setup::execute(args);
+                    return setup::execute(&environment, args);
                 }
                 Ok(_) => {
                     unreachable!("Prevented by Inquire")
@@ -22,10 +23,10 @@ pub async fn execute(args: &ArgMatches) -> anyhow::Result<()> {
             }
         }
         Some(("check", sub_args)) => {
-            return check::execute(sub_args).await;
+            return check::execute(&environment, sub_args).await;
         }
         Some(("setup", sub_args)) => {
-            return setup::execute(sub_args);
+            return setup::execute(&environment, sub_args);
         }
         Some((_, _)) => {
             unreachable!("Prevented by Clap")
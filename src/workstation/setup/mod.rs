@@ -1,5 +1,6 @@
+use crate::cli::P6mEnvironment;
 use clap::ArgMatches;
 
-pub fn execute(_args: &ArgMatches) -> anyhow::Result<()> {
+pub fn execute(_environment: &P6mEnvironment, _args: &ArgMatches) -> anyhow::Result<()> {
     unimplemented!("This has not yet been implemented");
 }